@@ -0,0 +1,5 @@
+out vec4 out_frag_color;
+
+void main() {
+  out_frag_color = vec4(vec3(gl_FragCoord.z), 1.0);
+}
\ No newline at end of file
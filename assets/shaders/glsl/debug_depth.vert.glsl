@@ -0,0 +1,7 @@
+uniform mat4 u_model;
+
+layout (location = 0) in vec3 position;
+
+void main() {
+  gl_Position = vec4(position, 1.0) * u_model;
+}
\ No newline at end of file
@@ -0,0 +1,11 @@
+uniform mat4 u_model;
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 color;
+
+out vec3 line_color;
+
+void main() {
+  line_color = color;
+  gl_Position = vec4(position, 1.0) * u_model;
+}
\ No newline at end of file
@@ -0,0 +1,9 @@
+in vec3 vertex_normal;
+
+out vec4 out_frag_color;
+
+void main() {
+  // Missing normals are zero, they show up gray
+  vec3 normal = length(vertex_normal) > 0.0 ? normalize(vertex_normal) : vec3(0.0);
+  out_frag_color = vec4(normal * 0.5 + 0.5, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,11 @@
+uniform mat4 u_model;
+
+layout (location = 0) in vec3 position;
+layout (location = 2) in vec3 normal;
+
+out vec3 vertex_normal;
+
+void main() {
+  vertex_normal = normal * mat3(transpose(inverse(u_model)));
+  gl_Position = vec4(position, 1.0) * u_model;
+}
\ No newline at end of file
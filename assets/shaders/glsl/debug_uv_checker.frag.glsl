@@ -0,0 +1,11 @@
+in vec2 vertex_uv;
+
+out vec4 out_frag_color;
+
+void main() {
+  // 8 by 8 squares per UV unit, tinted by the UV so flipped islands stand out
+  vec2 cell = floor(vertex_uv * 8.0);
+  float checker = mod(cell.x + cell.y, 2.0);
+  vec3 tint = vec3(0.5 + 0.5 * fract(vertex_uv), 1.0);
+  out_frag_color = vec4(mix(0.25, 1.0, checker) * tint, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,11 @@
+uniform mat4 u_model;
+
+layout (location = 0) in vec3 position;
+layout (location = 3) in vec2 uv;
+
+out vec2 vertex_uv;
+
+void main() {
+  vertex_uv = uv;
+  gl_Position = vec4(position, 1.0) * u_model;
+}
\ No newline at end of file
@@ -0,0 +1,14 @@
+uniform vec3 u_color;
+
+in vec3 vertex_barycentric;
+
+out vec4 out_frag_color;
+
+void main() {
+  // Distance to the closest edge in pixels
+  vec3 edge_distance = vertex_barycentric / fwidth(vertex_barycentric);
+  if (min(edge_distance.x, min(edge_distance.y, edge_distance.z)) > 1.0) {
+    discard;
+  }
+  out_frag_color = vec4(u_color, 1.0);
+}
\ No newline at end of file
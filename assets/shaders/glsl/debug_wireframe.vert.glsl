@@ -0,0 +1,14 @@
+uniform mat4 u_model;
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec3 barycentric;
+
+out vec3 vertex_barycentric;
+
+void main() {
+  vertex_barycentric = barycentric;
+  gl_Position = vec4(position, 1.0) * u_model;
+  // Slightly towards the camera, so the edges win against the shaded
+  // triangles they are drawn over
+  gl_Position.z -= 0.0005 * gl_Position.w;
+}
\ No newline at end of file
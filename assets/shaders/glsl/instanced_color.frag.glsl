@@ -0,0 +1,7 @@
+in vec3 color;
+
+out vec4 out_frag_color;
+
+void main() {
+  out_frag_color = vec4(color, 1.0);
+}
\ No newline at end of file
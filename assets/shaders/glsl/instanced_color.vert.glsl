@@ -0,0 +1,15 @@
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec4 i_model_0;
+layout (location = 2) in vec4 i_model_1;
+layout (location = 3) in vec4 i_model_2;
+layout (location = 4) in vec4 i_model_3;
+layout (location = 5) in vec3 i_color;
+
+out vec3 color;
+
+void main() {
+  mat4 model = mat4(i_model_0, i_model_1, i_model_2, i_model_3);
+
+  color = i_color;
+  gl_Position = vec4(position, 1.0) * model;
+}
\ No newline at end of file
@@ -1,18 +1,31 @@
-#version 300 es
-
-precision highp float;
-
 uniform vec3 u_color;
 uniform vec3 u_light_color;
 uniform vec3 u_light_position;
 uniform vec3 u_camera_position;
+// LOD cross-fade, see lod::LodGroup::with_cross_fade
+uniform float u_lod_fade;
 
 in vec3 fragment_position;
 in vec3 norm;
 
 out vec4 out_frag_color;
 
+// A 4x4 Bayer matrix, thresholds in (0, 1) spread evenly over every block of
+// 16 pixels
+float dither_threshold() {
+  int x = int(gl_FragCoord.x) & 3;
+  int y = int(gl_FragCoord.y) & 3;
+  int v = x ^ y;
+  int index = ((v & 1) << 3) | ((y & 1) << 2) | (v & 2) | ((y & 2) >> 1);
+  return (float(index) + 0.5) / 16.0;
+}
+
 void main() {
+  float threshold = dither_threshold();
+  if ((u_lod_fade > 0.0 && threshold < u_lod_fade) || (u_lod_fade < 0.0 && threshold >= 1.0 + u_lod_fade)) {
+    discard;
+  }
+
   float ambient_strength = 0.1;
   vec3 ambient = ambient_strength * u_light_color;
 
@@ -24,7 +37,6 @@ void main() {
   float specular_strength = 0.3;
   vec3 view_direction = normalize(u_camera_position - fragment_position);
   vec3 reflect_direction = reflect(-light_direction, norm);
-
   float spec = pow(max(dot(view_direction, reflect_direction), 0.0), 8.0);
   vec3 specular = specular_strength * spec * u_light_color; 
 
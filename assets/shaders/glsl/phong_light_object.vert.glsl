@@ -1,5 +1,3 @@
-#version 300 es
-
 uniform mat4 u_model;
 
 layout (location = 0) in vec3 position;
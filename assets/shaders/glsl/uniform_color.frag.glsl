@@ -1,5 +1,3 @@
-#version 330 core
-
 uniform vec3 u_color;
 
 out vec4 out_frag_color;
@@ -1,11 +1,11 @@
-#version 330 core
-
 uniform mat4 u_model;
+uniform float u_point_size;
 
 layout (location = 0) in vec3 position;
 
 out vec4 frag_color;
 
 void main() {
+  gl_PointSize = u_point_size;
   gl_Position = vec4(position , 1.0) * u_model;
 }
\ No newline at end of file
@@ -0,0 +1,7 @@
+in vec4 vertex_color;
+
+out vec4 out_frag_color;
+
+void main() {
+  out_frag_color = vertex_color;
+}
\ No newline at end of file
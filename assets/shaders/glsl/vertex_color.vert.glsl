@@ -0,0 +1,13 @@
+uniform mat4 u_model;
+uniform float u_point_size;
+
+layout (location = 0) in vec3 position;
+layout (location = 5) in vec4 color;
+
+out vec4 vertex_color;
+
+void main() {
+  vertex_color = color;
+  gl_PointSize = u_point_size;
+  gl_Position = vec4(position, 1.0) * u_model;
+}
\ No newline at end of file
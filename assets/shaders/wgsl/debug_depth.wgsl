@@ -0,0 +1,18 @@
+struct Uniforms {
+  u_model: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+  var position_out = vec4<f32>(position, 1.0) * uniforms.u_model;
+  // GL clip space depth is [-1, 1], wgpu expects [0, 1]
+  position_out.z = 0.5 * (position_out.z + position_out.w);
+  return position_out;
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_coord: vec4<f32>) -> @location(0) vec4<f32> {
+  return vec4<f32>(vec3<f32>(frag_coord.z), 1.0);
+}
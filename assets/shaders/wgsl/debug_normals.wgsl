@@ -0,0 +1,45 @@
+struct Uniforms {
+  u_model: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) normal: vec3<f32>,
+}
+
+// WGSL has no inverse(), so transpose(inverse(m)) is built from the cofactors.
+fn inverse_transpose(m: mat3x3<f32>) -> mat3x3<f32> {
+  let a = m[0];
+  let b = m[1];
+  let c = m[2];
+  let r0 = cross(b, c);
+  let r1 = cross(c, a);
+  let r2 = cross(a, b);
+  let det = dot(r2, c);
+  return mat3x3<f32>(r0, r1, r2) * (1.0 / det);
+}
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(2) normal: vec3<f32>) -> VertexOutput {
+  let model = uniforms.u_model;
+  let model3 = mat3x3<f32>(model[0].xyz, model[1].xyz, model[2].xyz);
+
+  var out: VertexOutput;
+  out.normal = normal * inverse_transpose(model3);
+  out.position = vec4<f32>(position, 1.0) * model;
+  // GL clip space depth is [-1, 1], wgpu expects [0, 1]
+  out.position.z = 0.5 * (out.position.z + out.position.w);
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  // Missing normals are zero, they show up gray
+  var normal = vec3<f32>(0.0);
+  if (length(in.normal) > 0.0) {
+    normal = normalize(in.normal);
+  }
+  return vec4<f32>(normal * 0.5 + 0.5, 1.0);
+}
@@ -0,0 +1,29 @@
+struct Uniforms {
+  u_model: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(3) uv: vec2<f32>) -> VertexOutput {
+  var out: VertexOutput;
+  out.uv = uv;
+  out.position = vec4<f32>(position, 1.0) * uniforms.u_model;
+  // GL clip space depth is [-1, 1], wgpu expects [0, 1]
+  out.position.z = 0.5 * (out.position.z + out.position.w);
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  // 8 by 8 squares per UV unit, tinted by the UV so flipped islands stand out
+  let cell = floor(in.uv * 8.0);
+  let checker = abs((cell.x + cell.y) % 2.0);
+  let tint = vec3<f32>(0.5 + 0.5 * fract(in.uv), 1.0);
+  return vec4<f32>(mix(0.25, 1.0, checker) * tint, 1.0);
+}
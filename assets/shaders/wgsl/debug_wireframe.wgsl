@@ -0,0 +1,34 @@
+struct Uniforms {
+  u_model: mat4x4<f32>,
+  u_color: vec3<f32>,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) barycentric: vec3<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(1) barycentric: vec3<f32>) -> VertexOutput {
+  var out: VertexOutput;
+  out.barycentric = barycentric;
+  out.position = vec4<f32>(position, 1.0) * uniforms.u_model;
+  // Slightly towards the camera, so the edges win against the shaded
+  // triangles they are drawn over
+  out.position.z -= 0.0005 * out.position.w;
+  // GL clip space depth is [-1, 1], wgpu expects [0, 1]
+  out.position.z = 0.5 * (out.position.z + out.position.w);
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  // Distance to the closest edge in pixels
+  let edge_distance = in.barycentric / fwidth(in.barycentric);
+  if (min(edge_distance.x, min(edge_distance.y, edge_distance.z)) > 1.0) {
+    discard;
+  }
+  return vec4<f32>(uniforms.u_color, 1.0);
+}
@@ -0,0 +1,28 @@
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) color: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+  @location(0) position: vec3<f32>,
+  @location(1) i_model_0: vec4<f32>,
+  @location(2) i_model_1: vec4<f32>,
+  @location(3) i_model_2: vec4<f32>,
+  @location(4) i_model_3: vec4<f32>,
+  @location(5) i_color: vec3<f32>,
+) -> VertexOutput {
+  let model = mat4x4<f32>(i_model_0, i_model_1, i_model_2, i_model_3);
+
+  var out: VertexOutput;
+  out.color = i_color;
+  out.position = vec4<f32>(position, 1.0) * model;
+  // GL clip space depth is [-1, 1], wgpu expects [0, 1]
+  out.position.z = 0.5 * (out.position.z + out.position.w);
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  return vec4<f32>(in.color, 1.0);
+}
@@ -0,0 +1,77 @@
+struct Uniforms {
+  u_model: mat4x4<f32>,
+  u_color: vec3<f32>,
+  u_light_color: vec3<f32>,
+  u_light_position: vec3<f32>,
+  u_camera_position: vec3<f32>,
+  // LOD cross-fade, see lod::LodGroup::with_cross_fade
+  u_lod_fade: f32,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) fragment_position: vec3<f32>,
+  @location(1) norm: vec3<f32>,
+}
+
+// WGSL has no inverse(), so transpose(inverse(m)) is built from the cofactors.
+fn inverse_transpose(m: mat3x3<f32>) -> mat3x3<f32> {
+  let a = m[0];
+  let b = m[1];
+  let c = m[2];
+  let r0 = cross(b, c);
+  let r1 = cross(c, a);
+  let r2 = cross(a, b);
+  let det = dot(r2, c);
+  return mat3x3<f32>(r0, r1, r2) * (1.0 / det);
+}
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(2) normal: vec3<f32>) -> VertexOutput {
+  let model = uniforms.u_model;
+  let model3 = mat3x3<f32>(model[0].xyz, model[1].xyz, model[2].xyz);
+
+  var out: VertexOutput;
+  out.fragment_position = (vec4<f32>(position, 1.0) * model).xyz;
+  out.norm = normalize(normal) * inverse_transpose(model3);
+  out.position = vec4<f32>(position, 1.0) * model;
+  // GL clip space depth is [-1, 1], wgpu expects [0, 1]
+  out.position.z = 0.5 * (out.position.z + out.position.w);
+  return out;
+}
+
+// A 4x4 Bayer matrix, thresholds in (0, 1) spread evenly over every block of
+// 16 pixels
+fn dither_threshold(frag_coord: vec2<f32>) -> f32 {
+  let x = u32(frag_coord.x) & 3u;
+  let y = u32(frag_coord.y) & 3u;
+  let v = x ^ y;
+  let index = ((v & 1u) << 3u) | ((y & 1u) << 2u) | (v & 2u) | ((y & 2u) >> 1u);
+  return (f32(index) + 0.5) / 16.0;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  let threshold = dither_threshold(in.position.xy);
+  let fade = uniforms.u_lod_fade;
+  if ((fade > 0.0 && threshold < fade) || (fade < 0.0 && threshold >= 1.0 + fade)) {
+    discard;
+  }
+
+  let ambient_strength = 0.1;
+  let ambient = ambient_strength * uniforms.u_light_color;
+
+  let light_direction = normalize(uniforms.u_light_position - in.fragment_position);
+  let diff = max(dot(in.norm, light_direction), 0.0);
+  let diffuse = diff * uniforms.u_light_color;
+
+  let specular_strength = 0.3;
+  let view_direction = normalize(uniforms.u_camera_position - in.fragment_position);
+  let reflect_direction = reflect(-light_direction, in.norm);
+  let spec = pow(max(dot(view_direction, reflect_direction), 0.0), 8.0);
+  let specular = specular_strength * spec * uniforms.u_light_color;
+
+  return vec4<f32>((ambient + diffuse + specular) * uniforms.u_color, 1.0);
+}
@@ -0,0 +1,26 @@
+// wgpu always draws points 1 pixel wide, u_point_size isn't read
+struct Uniforms {
+  u_model: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(5) color: vec4<f32>) -> VertexOutput {
+  var out: VertexOutput;
+  out.color = color;
+  out.position = vec4<f32>(position, 1.0) * uniforms.u_model;
+  // GL clip space depth is [-1, 1], wgpu expects [0, 1]
+  out.position.z = 0.5 * (out.position.z + out.position.w);
+  return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  return in.color;
+}
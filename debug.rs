@@ -0,0 +1,331 @@
+use crate::{
+    backend::Backend,
+    common::{Shader, ShaderProgram, Vec3},
+    entity::{Entity, VertexBuffer},
+    math::{get_scale_matrix, mat4_transform_point, vec3_add},
+    types::{BufferUsage, PolygonMode, PrimitiveTopology},
+    Vertex, BACKEND,
+};
+use std::{
+    cell::{OnceCell, RefCell},
+    collections::HashMap,
+    sync::Mutex,
+};
+
+// ---- Render modes ----
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RenderMode {
+    #[default]
+    Shaded,
+    Wireframe,
+    WireframeOverShaded,
+    // World space normals as colors
+    Normals,
+    // Window space depth as gray, near is black
+    Depth,
+    // A checkerboard in UV space, shows stretching and seams
+    UvChecker,
+}
+
+static RENDER_MODE: Mutex<RenderMode> = Mutex::new(RenderMode::Shaded);
+
+const WIREFRAME_COLOR: Vec3 = [0.1, 0.8, 0.2];
+
+// Applies to every entity drawn through `scene::Drawable` from the next draw
+// on, LOD groups included, though their levels aren't cross-faded then.
+// Point and line entities, entities without positions at location 0 and
+// instanced entities are always shaded.
+pub fn set_render_mode(mode: RenderMode) {
+    *RENDER_MODE.lock().unwrap() = mode;
+}
+
+pub fn render_mode() -> RenderMode {
+    *RENDER_MODE.lock().unwrap()
+}
+
+thread_local! {
+    static PROGRAMS: RefCell<HashMap<&'static str, ShaderProgram>> = RefCell::new(HashMap::new());
+    static POLYGON_MODE: OnceCell<bool> = const { OnceCell::new() };
+    static GIZMOS: RefCell<Gizmos> = RefCell::new(Gizmos {
+        vertices: Vec::new(),
+        entity: None,
+        transform: get_scale_matrix(1.0, 1.0, 1.0),
+    });
+}
+
+// The debug programs are compiled on first use
+fn program(name: &'static str) -> ShaderProgram {
+    PROGRAMS.with(|programs| {
+        programs
+            .borrow_mut()
+            .entry(name)
+            .or_insert_with(|| {
+                let program = ShaderProgram::new(name);
+                if name == "uniform_color" || name == "debug_wireframe" {
+                    program.set_uniform("u_color", WIREFRAME_COLOR).unwrap();
+                }
+                program
+            })
+            .clone()
+    })
+}
+
+fn polygon_mode() -> bool {
+    POLYGON_MODE.with(|polygon_mode| {
+        *polygon_mode.get_or_init(|| BACKEND.lock().unwrap().capabilities().polygon_mode)
+    })
+}
+
+// Locations line up with `MeshVertex`, the barycentric coordinates take the
+// color slot
+#[derive(Debug, Clone, Copy, Vertex)]
+#[repr(C)]
+struct DebugVertex {
+    #[location(0)]
+    position: Vec3,
+    #[location(1)]
+    barycentric: Vec3,
+    #[location(2)]
+    normal: Vec3,
+    #[location(3)]
+    uv: [f32; 2],
+}
+
+// Every triangle gets its own vertices, so each corner can have its own
+// barycentric coordinate. Missing normals and UVs are zero.
+fn debug_mesh(entity: &Entity) -> Entity {
+    let positions = entity
+        .read_attribute(0)
+        .expect("draw_entity only draws entities with positions in the debug modes");
+    let normals = entity.read_attribute(2);
+    let uvs = entity.read_attribute(3);
+
+    let vertices: Vec<DebugVertex> = entity
+        .triangle_indices()
+        .into_iter()
+        .enumerate()
+        .map(|(corner, index)| {
+            let index = index as usize;
+            let mut barycentric = [0.0; 3];
+            barycentric[corner % 3] = 1.0;
+
+            DebugVertex {
+                position: [
+                    positions[index][0],
+                    positions[index][1],
+                    positions[index][2],
+                ],
+                barycentric,
+                normal: normals.as_ref().map_or([0.0; 3], |normals| {
+                    [normals[index][0], normals[index][1], normals[index][2]]
+                }),
+                uv: uvs
+                    .as_ref()
+                    .map_or([0.0; 2], |uvs| [uvs[index][0], uvs[index][1]]),
+            }
+        })
+        .collect();
+
+    Entity::new(&vertices, None)
+}
+
+fn draw_debug_mesh(entity: &Entity, name: &'static str, model: [[f32; 4]; 4]) {
+    let mesh = entity
+        .debug_mesh
+        .get_or_init(|| Box::new(debug_mesh(entity)));
+    let program = program(name);
+    program.set_uniform("u_model", model).unwrap();
+
+    mesh.draw_with_shader(&BACKEND.lock().unwrap(), &program);
+}
+
+// GL draws the triangles as lines, the other backends draw the barycentric
+// copy and discard everything away from the edges
+fn draw_wireframe(entity: &Entity, model: [[f32; 4]; 4]) {
+    if !polygon_mode() {
+        draw_debug_mesh(entity, "debug_wireframe", model);
+        return;
+    }
+
+    let program = program("uniform_color");
+    program.set_uniform("u_model", model).unwrap();
+
+    let ctx = BACKEND.lock().unwrap();
+    ctx.set_polygon_mode(PolygonMode::Line);
+    entity.draw_with_shader(&ctx, &program);
+    ctx.set_polygon_mode(PolygonMode::Fill);
+}
+
+pub(crate) fn draw_entity(entity: &Entity) {
+    let mode = render_mode();
+    let triangles = matches!(
+        entity.topology,
+        PrimitiveTopology::Triangles
+            | PrimitiveTopology::TriangleStrip
+            | PrimitiveTopology::TriangleFan
+    );
+
+    // The debug modes need positions at location 0
+    let positions = entity
+        .buffers
+        .iter()
+        .flat_map(|buffer| &buffer.attributes)
+        .next()
+        .is_some_and(Option::is_some);
+
+    if mode == RenderMode::Shaded || !triangles || !positions {
+        entity.draw(&BACKEND.lock().unwrap());
+        return;
+    }
+
+    // The debug shaders place the entity with its `model`, without one it is
+    // drawn untransformed
+    let model = entity
+        .model
+        .unwrap_or_else(|| get_scale_matrix(1.0, 1.0, 1.0));
+
+    match mode {
+        RenderMode::Wireframe => draw_wireframe(entity, model),
+        RenderMode::WireframeOverShaded => {
+            entity.draw(&BACKEND.lock().unwrap());
+            draw_wireframe(entity, model);
+        }
+        RenderMode::Normals => draw_debug_mesh(entity, "debug_normals", model),
+        RenderMode::Depth => draw_debug_mesh(entity, "debug_depth", model),
+        RenderMode::UvChecker => draw_debug_mesh(entity, "debug_uv_checker", model),
+        RenderMode::Shaded => unreachable!(),
+    }
+}
+
+// ---- Gizmos ----
+
+#[derive(Debug, Clone, Copy, Vertex)]
+#[repr(C)]
+struct GizmoVertex {
+    #[location(0)]
+    position: Vec3,
+    #[location(1)]
+    color: Vec3,
+}
+
+struct Gizmos {
+    // The lines of the current frame, two vertices each
+    vertices: Vec<GizmoVertex>,
+    // The stream buffer they are drawn from, made on the first frame with
+    // gizmos
+    entity: Option<Entity>,
+    transform: [[f32; 4]; 4],
+}
+
+const CIRCLE_SEGMENTS: usize = 32;
+
+// The gizmos are drawn with this as their `u_model`, e.g. the view and
+// projection of the scene. The identity by default.
+pub fn set_gizmo_transform(transform: [[f32; 4]; 4]) {
+    GIZMOS.with(|gizmos| gizmos.borrow_mut().transform = transform);
+}
+
+pub fn line(a: Vec3, b: Vec3, color: Vec3) {
+    GIZMOS.with(|gizmos| {
+        gizmos.borrow_mut().vertices.extend([
+            GizmoVertex { position: a, color },
+            GizmoVertex { position: b, color },
+        ])
+    });
+}
+
+pub fn aabb(min: Vec3, max: Vec3, color: Vec3) {
+    let corner = |i: usize| {
+        [
+            if i & 1 == 0 { min[0] } else { max[0] },
+            if i & 2 == 0 { min[1] } else { max[1] },
+            if i & 4 == 0 { min[2] } else { max[2] },
+        ]
+    };
+
+    // Corners whose indices differ in one bit share an edge
+    for i in 0..8 {
+        for bit in [1, 2, 4] {
+            if i & bit == 0 {
+                line(corner(i), corner(i | bit), color);
+            }
+        }
+    }
+}
+
+// Three circles around the axes
+pub fn sphere(center: Vec3, radius: f32, color: Vec3) {
+    let point = |axis: usize, angle: f32| {
+        let mut offset = [0.0; 3];
+        offset[(axis + 1) % 3] = angle.cos() * radius;
+        offset[(axis + 2) % 3] = angle.sin() * radius;
+        vec3_add(center, offset)
+    };
+    let angle = |i: usize| i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+
+    for axis in 0..3 {
+        for i in 0..CIRCLE_SEGMENTS {
+            line(point(axis, angle(i)), point(axis, angle(i + 1)), color);
+        }
+    }
+}
+
+// The unit axes of the transform in red, green and blue
+pub fn axes(matrix: [[f32; 4]; 4]) {
+    let origin = mat4_transform_point(matrix, [0.0; 3]);
+
+    for axis in 0..3 {
+        let mut direction = [0.0; 3];
+        direction[axis] = 1.0;
+        line(origin, mat4_transform_point(matrix, direction), direction);
+    }
+}
+
+// A square of `size` on the XZ plane around the origin, with `divisions`
+// cells along each side
+pub fn grid(size: f32, divisions: u32, color: Vec3) {
+    let half = size / 2.0;
+
+    for i in 0..=divisions {
+        let offset = i as f32 / divisions.max(1) as f32 * size - half;
+        line([offset, 0.0, -half], [offset, 0.0, half], color);
+        line([-half, 0.0, offset], [half, 0.0, offset], color);
+    }
+}
+
+// Called at the end of every frame, after everything else is drawn
+pub(crate) fn draw_gizmos() {
+    GIZMOS.with(|gizmos| {
+        let Gizmos {
+            vertices,
+            entity,
+            transform,
+        } = &mut *gizmos.borrow_mut();
+
+        if vertices.is_empty() {
+            return;
+        }
+
+        match entity {
+            Some(entity) => entity.update_vertices(0, vertices),
+            None => {
+                *entity = Some(
+                    Entity::with_buffers(
+                        vec![VertexBuffer::from_vertices(vertices).with_usage(BufferUsage::Stream)],
+                        None,
+                        None,
+                    )
+                    .with_topology(PrimitiveTopology::Lines),
+                )
+            }
+        }
+        vertices.clear();
+
+        let program = program("debug_lines");
+        program.set_uniform("u_model", *transform).unwrap();
+        if let Some(entity) = entity {
+            entity.draw_with_shader(&BACKEND.lock().unwrap(), &program);
+        }
+    });
+}
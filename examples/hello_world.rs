@@ -1,11 +1,10 @@
 use reindeer::backend::Backend;
-use reindeer::common::{Shader, Vec3};
-use reindeer::entity::Entity;
+use reindeer::common::Shader;
 use reindeer::math::{
     get_rotation_matrix, get_scale_matrix, get_translation_matrix, mat4_mat4_mul,
 };
 use reindeer::scene::Drawable;
-use reindeer::{self, ShaderProgram, VERTEX_ATTRIBUTE_FVEC3};
+use reindeer::{self, mesh, ShaderProgram};
 
 #[cfg_attr(feature = "webgl", wasm_bindgen::prelude::wasm_bindgen(start))]
 pub fn run() {
@@ -16,109 +15,27 @@ pub fn run() {
     let object_color = [1.0, 0.5, 0.5];
 
     let shader_program = reindeer::ShaderProgram::new("phong_light_object");
-    shader_program.set_uniform("u_color", object_color);
-    shader_program.set_uniform("u_light_color", light_color);
-    shader_program.set_uniform("u_light_position", light_position);
-    shader_program.set_uniform("u_camera_position", [0.0, 0.0, -1.0]);
+    shader_program.set_uniform("u_color", object_color).unwrap();
+    shader_program
+        .set_uniform("u_light_color", light_color)
+        .unwrap();
+    shader_program
+        .set_uniform("u_light_position", light_position)
+        .unwrap();
+    shader_program
+        .set_uniform("u_camera_position", [0.0, 0.0, -1.0])
+        .unwrap();
 
     let shader_program_obj2 = ShaderProgram::new("uniform_color");
-    shader_program_obj2.set_uniform("u_color", light_color);
+    shader_program_obj2
+        .set_uniform("u_color", light_color)
+        .unwrap();
 
-    let vertices: Vec<Vec3> = vec![
-        [-0.5, -0.5, -0.5],
-        [0.0, 0.0, -1.0],
-        [0.5, -0.5, -0.5],
-        [0.0, 0.0, -1.0],
-        [0.5, 0.5, -0.5],
-        [0.0, 0.0, -1.0],
-        [0.5, 0.5, -0.5],
-        [0.0, 0.0, -1.0],
-        [-0.5, 0.5, -0.5],
-        [0.0, 0.0, -1.0],
-        [-0.5, -0.5, -0.5],
-        [0.0, 0.0, -1.0],
-        [-0.5, -0.5, 0.5],
-        [0.0, 0.0, 1.0],
-        [0.5, -0.5, 0.5],
-        [0.0, 0.0, 1.0],
-        [0.5, 0.5, 0.5],
-        [0.0, 0.0, 1.0],
-        [0.5, 0.5, 0.5],
-        [0.0, 0.0, 1.0],
-        [-0.5, 0.5, 0.5],
-        [0.0, 0.0, 1.0],
-        [-0.5, -0.5, 0.5],
-        [0.0, 0.0, 1.0],
-        [-0.5, 0.5, 0.5],
-        [-1.0, 0.0, 0.0],
-        [-0.5, 0.5, -0.5],
-        [-1.0, 0.0, 0.0],
-        [-0.5, -0.5, -0.5],
-        [-1.0, 0.0, 0.0],
-        [-0.5, -0.5, -0.5],
-        [-1.0, 0.0, 0.0],
-        [-0.5, -0.5, 0.5],
-        [-1.0, 0.0, 0.0],
-        [-0.5, 0.5, 0.5],
-        [-1.0, 0.0, 0.0],
-        [0.5, 0.5, 0.5],
-        [1.0, 0.0, 0.0],
-        [0.5, 0.5, -0.5],
-        [1.0, 0.0, 0.0],
-        [0.5, -0.5, -0.5],
-        [1.0, 0.0, 0.0],
-        [0.5, -0.5, -0.5],
-        [1.0, 0.0, 0.0],
-        [0.5, -0.5, 0.5],
-        [1.0, 0.0, 0.0],
-        [0.5, 0.5, 0.5],
-        [1.0, 0.0, 0.0],
-        [-0.5, -0.5, -0.5],
-        [0.0, -1.0, 0.0],
-        [0.5, -0.5, -0.5],
-        [0.0, -1.0, 0.0],
-        [0.5, -0.5, 0.5],
-        [0.0, -1.0, 0.0],
-        [0.5, -0.5, 0.5],
-        [0.0, -1.0, 0.0],
-        [-0.5, -0.5, 0.5],
-        [0.0, -1.0, 0.0],
-        [-0.5, -0.5, -0.5],
-        [0.0, -1.0, 0.0],
-        [-0.5, 0.5, -0.5],
-        [0.0, 1.0, 0.0],
-        [0.5, 0.5, -0.5],
-        [0.0, 1.0, 0.0],
-        [0.5, 0.5, 0.5],
-        [0.0, 1.0, 0.0],
-        [0.5, 0.5, 0.5],
-        [0.0, 1.0, 0.0],
-        [-0.5, 0.5, 0.5],
-        [0.0, 1.0, 0.0],
-        [-0.5, 0.5, -0.5],
-        [0.0, 1.0, 0.0],
-    ];
+    let cube = mesh::cube(1.0);
+    let obj = cube.to_entity(Some(shader_program));
+    let light_source = cube.to_entity(Some(shader_program_obj2));
 
-    let obj = Entity::new(
-        vertices.clone(),
-        Some(shader_program),
-        Some(vec![
-            Some(VERTEX_ATTRIBUTE_FVEC3),
-            None,
-            Some(VERTEX_ATTRIBUTE_FVEC3),
-        ]),
-    );
-    let light_source = Entity::new(
-        vertices,
-        Some(shader_program_obj2),
-        Some(vec![
-            Some(VERTEX_ATTRIBUTE_FVEC3),
-            None,
-            Some(VERTEX_ATTRIBUTE_FVEC3),
-        ]),
-    );
-
-    let scene = vec![obj, light_source];
+    let mut scene = vec![obj, light_source];
 
     reindeer::set_clear_color(1.0, 1.0, 1.0, 1.0);
 
@@ -141,18 +58,10 @@ pub fn run() {
             light_source_rotation_amount,
         );
 
+        scene[0].model = Some(rotation);
+        scene[1].model = Some(mat4_mat4_mul(light_source_model, light_source_rotation));
         scene.draw();
 
-        scene[0]
-            .shader
-            .as_ref()
-            .unwrap()
-            .set_uniform("u_model", rotation);
-
-        scene[1].shader.as_ref().unwrap().set_uniform(
-            "u_model",
-            mat4_mat4_mul(light_source_model, light_source_rotation),
-        );
         rotation_amount += rotation_angle;
         light_source_rotation_amount += rotation_angle;
 
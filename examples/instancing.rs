@@ -0,0 +1,97 @@
+use reindeer::backend::Backend;
+use reindeer::common::Vec3;
+use reindeer::entity::{Entity, InstancedEntity, VertexBuffer};
+use reindeer::math::{
+    get_rotation_matrix, get_scale_matrix, get_translation_matrix, mat4_mat4_mul,
+};
+use reindeer::scene::Drawable;
+use reindeer::types::BufferUsage;
+use reindeer::{self, ShaderProgram, VERTEX_ATTRIBUTE_FVEC3, VERTEX_ATTRIBUTE_FVEC4};
+
+const GRID_SIZE: usize = 5;
+
+// Model matrix rows followed by the color of one instance
+fn instance_data(rotation_amount: f32) -> Vec<f32> {
+    let spacing = 1.6 / GRID_SIZE as f32;
+    let scale = get_scale_matrix(0.08, 0.08, 0.08);
+
+    (0..GRID_SIZE * GRID_SIZE)
+        .flat_map(|index| {
+            let (row, column) = ((index / GRID_SIZE) as f32, (index % GRID_SIZE) as f32);
+            let translation = get_translation_matrix(
+                (column + 0.5) * spacing - 0.8,
+                (row + 0.5) * spacing - 0.8,
+                0.0,
+            );
+            let rotation = get_rotation_matrix(
+                rotation_amount + row,
+                rotation_amount + column,
+                rotation_amount,
+            );
+            let model = mat4_mat4_mul(translation, mat4_mat4_mul(scale, rotation));
+            let color = [
+                column / GRID_SIZE as f32,
+                row / GRID_SIZE as f32,
+                1.0 - column / GRID_SIZE as f32,
+            ];
+
+            model.into_iter().flatten().chain(color)
+        })
+        .collect()
+}
+
+#[cfg_attr(feature = "webgl", wasm_bindgen::prelude::wasm_bindgen(start))]
+pub fn run() {
+    reindeer::init();
+
+    let vertices: Vec<Vec3> = vec![
+        [-0.5, -0.5, -0.5],
+        [0.5, -0.5, -0.5],
+        [0.5, 0.5, -0.5],
+        [-0.5, 0.5, -0.5],
+        [-0.5, -0.5, 0.5],
+        [0.5, -0.5, 0.5],
+        [0.5, 0.5, 0.5],
+        [-0.5, 0.5, 0.5],
+    ];
+
+    let indices: Vec<u32> = vec![
+        0, 1, 2, 2, 3, 0, 4, 5, 6, 6, 7, 4, 0, 4, 7, 7, 3, 0, 1, 5, 6, 6, 2, 1, 0, 1, 5, 5, 4, 0,
+        3, 2, 6, 6, 7, 3,
+    ];
+
+    let cube = Entity::new_indexed(
+        &vertices,
+        indices,
+        Some(ShaderProgram::new("instanced_color")),
+    );
+
+    let mut rotation_amount = 0.0;
+
+    let instances = VertexBuffer::new(
+        &instance_data(rotation_amount),
+        vec![
+            Some(VERTEX_ATTRIBUTE_FVEC4),
+            Some(VERTEX_ATTRIBUTE_FVEC4),
+            Some(VERTEX_ATTRIBUTE_FVEC4),
+            Some(VERTEX_ATTRIBUTE_FVEC4),
+            Some(VERTEX_ATTRIBUTE_FVEC3),
+        ],
+    )
+    .with_usage(BufferUsage::Stream);
+
+    let mut scene = vec![InstancedEntity::new(cube, instances)];
+
+    reindeer::set_clear_color(1.0, 1.0, 1.0, 1.0);
+
+    reindeer::lib::Context::draw_loop(move || {
+        scene[0].update_instances(&instance_data(rotation_amount));
+        scene.draw();
+
+        rotation_amount += 0.01;
+    });
+}
+
+fn main() {
+    run();
+}
@@ -0,0 +1,218 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse::ParseStream, parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Fields,
+    Ident, LitInt, Token, Type,
+};
+
+struct LocatedField {
+    ident: Ident,
+    ty: Type,
+    location: u32,
+    normalized: bool,
+}
+
+// `#[derive(Vertex)]` on a struct whose fields are all marked with
+// `#[location(n)]` or `#[location(n, normalized)]`. The fields are written to
+// the vertex buffer in location order, skipped locations become `None`.
+#[proc_macro_derive(Vertex, attributes(location))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(&input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let mut fields = located_fields(input)?;
+    fields.sort_by_key(|field| field.location);
+
+    for pair in fields.windows(2) {
+        if pair[0].location == pair[1].location {
+            return Err(Error::new(
+                pair[1].ident.span(),
+                format!("location {} is used more than once", pair[1].location),
+            ));
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let writes = fields.iter().map(|field| {
+        let ident = &field.ident;
+        quote! { ::reindeer::entity::VertexData::write_bytes(&self.#ident, bytes); }
+    });
+
+    let location_count = fields.last().map(|field| field.location + 1).unwrap_or(0);
+    let attributes = (0..location_count).map(|location| {
+        match fields.iter().find(|field| field.location == location) {
+            Some(field) => {
+                let ty = &field.ty;
+                let normalized = field.normalized;
+                quote! {
+                    Some(::reindeer::common::VertexAttribute {
+                        normalized: #normalized,
+                        ..<#ty as ::reindeer::entity::VertexAttributeType>::ATTRIBUTE
+                    })
+                }
+            }
+            None => quote! { None },
+        }
+    });
+
+    // Arrays multiply the component count, a `[f32; 16]` or a matrix doesn't
+    // fit into one location. Generic field types are only known once the
+    // struct is used, so those are checked in `attributes`.
+    let (checks, generic_checks): (Vec<_>, Vec<_>) = fields
+        .iter()
+        .map(|field| {
+            let ty = &field.ty;
+            let message = format!(
+                "`{}` has more than 4 components, split it over several locations",
+                field.ident
+            );
+            let check = quote! {
+                assert!(
+                    <#ty as ::reindeer::entity::VertexAttributeType>::ATTRIBUTE.count <= 4,
+                    #message
+                )
+            };
+
+            if input.generics.params.is_empty() {
+                (Some(quote! { const _: () = #check; }), None)
+            } else {
+                (None, Some(quote! { const { #check }; }))
+            }
+        })
+        .unzip();
+
+    Ok(quote! {
+        #(#checks)*
+
+        impl #impl_generics ::reindeer::entity::VertexData for #name #type_generics #where_clause {
+            fn write_bytes(&self, bytes: &mut Vec<u8>) {
+                #(#writes)*
+            }
+        }
+
+        impl #impl_generics ::reindeer::entity::Vertex for #name #type_generics #where_clause {
+            fn attributes() -> Vec<Option<::reindeer::common::VertexAttribute>> {
+                #(#generic_checks)*
+                vec![#(#attributes),*]
+            }
+        }
+    })
+}
+
+fn located_fields(input: &DeriveInput) -> Result<Vec<LocatedField>, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    input.ident.span(),
+                    "Vertex can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                input.ident.span(),
+                "Vertex can only be derived for structs",
+            ))
+        }
+    };
+
+    fields
+        .iter()
+        .map(|field| {
+            let attribute = field
+                .attrs
+                .iter()
+                .find(|attribute| attribute.path().is_ident("location"))
+                .ok_or_else(|| {
+                    Error::new(
+                        field.span(),
+                        "every field of a Vertex needs a #[location(n)] attribute",
+                    )
+                })?;
+
+            let (location, normalized) = attribute.parse_args_with(parse_location)?;
+
+            Ok(LocatedField {
+                ident: field.ident.clone().unwrap(),
+                ty: field.ty.clone(),
+                location,
+                normalized,
+            })
+        })
+        .collect()
+}
+
+fn parse_location(input: ParseStream) -> Result<(u32, bool), Error> {
+    let location: LitInt = input.parse()?;
+    let mut normalized = false;
+
+    while !input.is_empty() {
+        input.parse::<Token![,]>()?;
+        if input.is_empty() {
+            break;
+        }
+
+        let flag: Ident = input.parse()?;
+        if flag == "normalized" {
+            normalized = true;
+        } else {
+            return Err(Error::new(flag.span(), "expected `normalized`"));
+        }
+    }
+
+    Ok((location.base10_parse()?, normalized))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn error(input: DeriveInput) -> String {
+        expand(&input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn rejects_bad_locations() {
+        assert_eq!(
+            error(parse_quote! {
+                struct Duplicate {
+                    #[location(0)]
+                    a: f32,
+                    #[location(0)]
+                    b: f32,
+                }
+            }),
+            "location 0 is used more than once"
+        );
+        assert_eq!(
+            error(parse_quote! {
+                struct Missing {
+                    #[location(0)]
+                    a: f32,
+                    b: f32,
+                }
+            }),
+            "every field of a Vertex needs a #[location(n)] attribute"
+        );
+        assert_eq!(
+            error(parse_quote! {
+                struct Flag {
+                    #[location(0, signed)]
+                    a: f32,
+                }
+            }),
+            "expected `normalized`"
+        );
+    }
+}
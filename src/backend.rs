@@ -1,19 +1,31 @@
+use crate::{
+    common::{Capabilities, QueryResult},
+    types::{BufferTarget, BufferUsage, IndexType, PolygonMode, PrimitiveTopology, ScalarType},
+};
+
 pub trait Backend {
     type Context;
     type Shader;
     type Program;
     type Buffer;
     type Vao;
+    type Query;
+    // Where `Uniform::set_uniform` writes, looked up once per uniform
+    type UniformLocation;
 
     fn new() -> Self::Context;
 
+    fn capabilities(&self) -> Capabilities;
+
     fn use_program(&self, program: &Self::Program);
 
     fn create_buffer(&self) -> Self::Buffer;
 
-    fn bind_buffer(&self, buffer_type: u32, buffer: &Self::Buffer);
+    fn bind_buffer(&self, target: BufferTarget, buffer: &Self::Buffer);
+
+    fn buffer_data(&self, data: &[u8], target: BufferTarget, usage: BufferUsage);
 
-    fn buffer_data(&self, vertices: &[f32], buffer_type: u32, usage_hint: u32);
+    fn buffer_sub_data(&self, data: &[u8], target: BufferTarget, offset: i32);
 
     fn create_vertex_array(&self) -> Self::Vao;
 
@@ -23,7 +35,7 @@ pub trait Backend {
         &self,
         index: u32,
         size: i32,
-        type_: u32,
+        type_: ScalarType,
         normalized: bool,
         stride: i32,
         offset: i32,
@@ -31,12 +43,49 @@ pub trait Backend {
 
     fn enable_vertex_attrib_array(&self, index: u32);
 
-    fn draw_arrays(&self, mode: u32, first: i32, vertex_count: i32);
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32);
+
+    fn draw_arrays(&self, topology: PrimitiveTopology, first: i32, vertex_count: i32);
+
+    fn draw_elements(
+        &self,
+        topology: PrimitiveTopology,
+        index_count: i32,
+        index_type: IndexType,
+        offset: i32,
+    );
+
+    fn draw_arrays_instanced(
+        &self,
+        topology: PrimitiveTopology,
+        first: i32,
+        vertex_count: i32,
+        instance_count: i32,
+    );
+
+    fn draw_elements_instanced(
+        &self,
+        topology: PrimitiveTopology,
+        index_count: i32,
+        index_type: IndexType,
+        offset: i32,
+        instance_count: i32,
+    );
+
+    fn create_timer_query(&self) -> Self::Query;
+
+    fn begin_timer_query(&self, query: &Self::Query);
+
+    fn end_timer_query(&self, query: &Self::Query);
+
+    fn timer_query_result(&self, query: &Self::Query) -> QueryResult;
 
     fn set_clear_color(&mut self, red: f32, green: f32, blue: f32, alpha: f32);
 
     fn clear(&self);
 
+    fn set_polygon_mode(&self, mode: PolygonMode);
+
     fn before_draw(&mut self);
 
     fn after_draw(&mut self);
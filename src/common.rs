@@ -1,38 +1,338 @@
-use crate::{
-    backend::Backend,
-    lib::{shader::ShaderLib, Context},
-};
-use std::collections::HashSet;
+use crate::{backend::Backend, lib::Context, types::ScalarType, BACKEND};
+use std::{collections::HashMap, fmt, marker::PhantomData, time::Duration};
 
 pub type Vec3 = [f32; 3];
 
 // ---- Shaders ----
 
-pub trait ShaderUtils {
-    fn compile_program(name: &str) -> <Context as Backend>::Program;
+pub trait ShaderUtils: Backend {
+    fn compile_program(&self, name: &str) -> Self::Program;
+    // Every uniform of the linked program, arrays once under their own name
+    fn active_uniforms(&self, program: &Self::Program) -> Vec<UniformInfo>;
+    // Only called with the names of active uniforms
+    fn uniform_location(&self, program: &Self::Program, name: &str) -> Self::UniformLocation;
 }
 
+pub trait Uniform<Input>: Backend {
+    fn set_uniform(&self, program: &Self::Program, location: &Self::UniformLocation, input: Input);
+}
+
+// GLSL uniform types. Samplers are told apart by their dimension only,
+// isampler2D or sampler2DShadow are `Sampler2D` as well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UniformType {
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Int,
+    IVec2,
+    IVec3,
+    IVec4,
+    UInt,
+    UVec2,
+    UVec3,
+    UVec4,
+    Bool,
+    BVec2,
+    BVec3,
+    BVec4,
+    Mat2,
+    Mat3,
+    Mat4,
+    Sampler2D,
+    Sampler3D,
+    SamplerCube,
+    Sampler2DArray,
+    // Anything else, e.g. non-square matrices
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UniformInfo {
+    pub name: String,
+    pub uniform_type: UniformType,
+    // 1 unless the uniform is an array
+    pub array_size: u32,
+}
+
+impl UniformInfo {
+    // Whether `count` elements of the type can be written to the uniform
+    fn check<Input: UniformInput>(&self, count: usize) -> Result<(), UniformError> {
+        if !Input::accepts(self.uniform_type) {
+            return Err(UniformError::TypeMismatch {
+                name: self.name.clone(),
+                declared: self.uniform_type,
+                input: Input::TYPE,
+            });
+        }
+
+        if count > self.array_size as usize {
+            return Err(UniformError::TooManyElements {
+                name: self.name.clone(),
+                array_size: self.array_size,
+                count,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+// The GLSL type a value passed to `set_uniform` is written to. A slice
+// writes the first elements of an array uniform.
+pub trait UniformInput {
+    const TYPE: UniformType;
+
+    // Whether the value can be written to a uniform declared as this type
+    fn accepts(declared: UniformType) -> bool {
+        declared == Self::TYPE
+    }
+
+    // How many array elements the value writes
+    fn count(&self) -> usize {
+        1
+    }
+}
+
+// The texture unit a sampler uniform reads from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureUnit(pub u32);
+
+impl UniformInput for TextureUnit {
+    const TYPE: UniformType = UniformType::Sampler2D;
+
+    fn accepts(declared: UniformType) -> bool {
+        matches!(
+            declared,
+            UniformType::Sampler2D
+                | UniformType::Sampler3D
+                | UniformType::SamplerCube
+                | UniformType::Sampler2DArray
+        )
+    }
+}
+
+macro_rules! uniform_input {
+    ($($input:ty => $uniform_type:ident),* $(,)?) => {
+        $(
+            impl UniformInput for $input {
+                const TYPE: UniformType = UniformType::$uniform_type;
+            }
+        )*
+    };
+}
+
+uniform_input!(
+    f32 => Float,
+    [f32; 2] => Vec2,
+    [f32; 3] => Vec3,
+    [f32; 4] => Vec4,
+    i32 => Int,
+    [i32; 2] => IVec2,
+    [i32; 3] => IVec3,
+    [i32; 4] => IVec4,
+    u32 => UInt,
+    [u32; 2] => UVec2,
+    [u32; 3] => UVec3,
+    [u32; 4] => UVec4,
+    bool => Bool,
+    [[f32; 2]; 2] => Mat2,
+    [[f32; 3]; 3] => Mat3,
+    [[f32; 4]; 4] => Mat4,
+);
+
+impl<Input: UniformInput> UniformInput for &[Input] {
+    const TYPE: UniformType = Input::TYPE;
+
+    fn accepts(declared: UniformType) -> bool {
+        Input::accepts(declared)
+    }
+
+    fn count(&self) -> usize {
+        self.len()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UniformError {
+    // GL and WebGL also drop uniforms that the shader never reads
+    UnknownName(String),
+    TypeMismatch {
+        name: String,
+        declared: UniformType,
+        input: UniformType,
+    },
+    // More elements than the array uniform has
+    TooManyElements {
+        name: String,
+        array_size: u32,
+        count: usize,
+    },
+}
+
+impl fmt::Display for UniformError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UniformError::UnknownName(name) => {
+                write!(f, "The program has no active uniform named {name}")
+            }
+            UniformError::TypeMismatch {
+                name,
+                declared,
+                input,
+            } => write!(
+                f,
+                "Uniform {name} is declared as {declared:?}, but a {input:?} was given"
+            ),
+            UniformError::TooManyElements {
+                name,
+                array_size,
+                count,
+            } => write!(
+                f,
+                "Uniform {name} has {array_size} elements, but {count} were given"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UniformError {}
+
 #[derive(Debug, Clone)]
 pub struct ShaderProgram {
     pub id: <Context as Backend>::Program,
-    pub uniforms: HashSet<String>,
+    pub uniforms: HashMap<String, UniformInfo>,
+    pub(crate) locations: HashMap<String, <Context as Backend>::UniformLocation>,
 }
 
 impl ShaderProgram {
     pub fn new(name: &str) -> Self {
-        let id = ShaderLib::compile_program(name);
+        let ctx = BACKEND.lock().unwrap();
+        let id = ctx.compile_program(name);
 
-        // TODO: Parse uniform names in self.uniforms
+        let uniforms: HashMap<String, UniformInfo> = ctx
+            .active_uniforms(&id)
+            .into_iter()
+            .map(|uniform| (uniform.name.clone(), uniform))
+            .collect();
+        let locations = uniforms
+            .keys()
+            .map(|name| (name.clone(), ctx.uniform_location(&id, name)))
+            .collect();
 
         Self {
             id,
-            uniforms: HashSet::new(),
+            uniforms,
+            locations,
         }
     }
+
+    // Checks the name and type once, setting the uniform through the handle
+    // skips the lookup by name, e.g. for values that change every frame
+    pub fn uniform_handle<Input: UniformInput>(
+        &self,
+        name: &str,
+    ) -> Result<UniformHandle<Input>, UniformError> {
+        let uniform = self.check_uniform::<Input>(name)?;
+
+        Ok(UniformHandle {
+            program: self.id.clone(),
+            location: self.locations[name].clone(),
+            uniform: uniform.clone(),
+            input: PhantomData,
+        })
+    }
+
+    // Whether a value of the type can be written to the uniform
+    pub fn check_uniform<Input: UniformInput>(
+        &self,
+        name: &str,
+    ) -> Result<&UniformInfo, UniformError> {
+        let uniform = self.uniform(name)?;
+        uniform.check::<Input>(1)?;
+        Ok(uniform)
+    }
+
+    fn uniform(&self, name: &str) -> Result<&UniformInfo, UniformError> {
+        self.uniforms
+            .get(name)
+            .ok_or_else(|| UniformError::UnknownName(String::from(name)))
+    }
 }
 
 pub trait Shader<Input> {
-    fn set_uniform(&self, name: &str, input: Input);
+    fn set_uniform(&self, name: &str, input: Input) -> Result<(), UniformError>;
+}
+
+impl<Input: UniformInput> Shader<Input> for ShaderProgram
+where
+    Context: Uniform<Input>,
+{
+    fn set_uniform(&self, name: &str, input: Input) -> Result<(), UniformError> {
+        self.uniform(name)?.check::<Input>(input.count())?;
+
+        BACKEND
+            .lock()
+            .unwrap()
+            .set_uniform(&self.id, &self.locations[name], input);
+        Ok(())
+    }
+}
+
+// A uniform of one program, see `ShaderProgram::uniform_handle`
+#[derive(Debug, Clone)]
+pub struct UniformHandle<Input> {
+    program: <Context as Backend>::Program,
+    location: <Context as Backend>::UniformLocation,
+    uniform: UniformInfo,
+    input: PhantomData<Input>,
+}
+
+impl<Input: UniformInput> UniformHandle<Input>
+where
+    Context: Uniform<Input>,
+{
+    // Fails like `set_uniform` when a slice is longer than the array uniform
+    pub fn set(&self, input: Input) -> Result<(), UniformError> {
+        self.uniform.check::<Input>(input.count())?;
+
+        BACKEND
+            .lock()
+            .unwrap()
+            .set_uniform(&self.program, &self.location, input);
+        Ok(())
+    }
+}
+
+// ---- Capabilities ----
+
+#[derive(Debug, Clone, Default)]
+pub struct Capabilities {
+    pub version: String,
+    pub renderer: String,
+    pub vendor: String,
+    pub max_texture_size: u32,
+    pub max_vertex_attributes: u32,
+    pub max_uniform_block_size: u32,
+    pub max_samples: u32,
+    pub timer_query: bool,
+    // `Backend::set_polygon_mode` can draw triangles as lines
+    pub polygon_mode: bool,
+    // Points are clamped to this many pixels, see `Entity::with_point_size`
+    pub max_point_size: f32,
+    pub extensions: Vec<String>,
+}
+
+// ---- Timer queries ----
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryResult {
+    Pending,
+    Ready(Duration),
+    // The GPU was disjoint while the query ran (e.g. it was throttled), the
+    // measurement is meaningless
+    Discarded,
 }
 
 // ---- Vertex attributes ----
@@ -40,22 +340,144 @@ pub trait Shader<Input> {
 #[derive(Debug, Clone)]
 pub struct VertexAttribute {
     pub count: u32,
-    pub item_type: u32,
+    pub item_type: ScalarType,
     pub item_size: u32,
+    // Integer data is mapped to [0, 1] ([-1, 1] when signed) instead of being
+    // converted as is
+    pub normalized: bool,
 }
 
 impl VertexAttribute {
-    pub fn new(count: u32, item_type: u32, item_size: u32) -> VertexAttribute {
+    pub const fn new(count: u32, item_type: ScalarType, normalized: bool) -> VertexAttribute {
         VertexAttribute {
             count,
-            item_size,
+            item_size: item_type.size(),
             item_type,
+            normalized,
         }
     }
+
+    pub fn size(&self) -> u32 {
+        self.count * self.item_size
+    }
 }
 
-pub const VERTEX_ATTRIBUTE_FVEC3: VertexAttribute = VertexAttribute {
-    count: 3,
-    item_type: crate::types::FLOAT,
-    item_size: 4,
-};
+pub const VERTEX_ATTRIBUTE_FVEC2: VertexAttribute = VertexAttribute::new(2, ScalarType::F32, false);
+pub const VERTEX_ATTRIBUTE_FVEC3: VertexAttribute = VertexAttribute::new(3, ScalarType::F32, false);
+pub const VERTEX_ATTRIBUTE_FVEC4: VertexAttribute = VertexAttribute::new(4, ScalarType::F32, false);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uniform(uniform_type: UniformType) -> UniformInfo {
+        UniformInfo {
+            name: String::from("u_test"),
+            uniform_type,
+            array_size: 1,
+        }
+    }
+
+    #[test]
+    fn matching_types() {
+        assert_eq!(uniform(UniformType::Float).check::<f32>(1), Ok(()));
+        assert_eq!(uniform(UniformType::Vec3).check::<[f32; 3]>(1), Ok(()));
+        assert_eq!(uniform(UniformType::IVec2).check::<[i32; 2]>(1), Ok(()));
+        assert_eq!(uniform(UniformType::Mat4).check::<[[f32; 4]; 4]>(1), Ok(()));
+    }
+
+    #[test]
+    fn type_mismatch() {
+        assert_eq!(
+            uniform(UniformType::Vec3).check::<[f32; 4]>(1),
+            Err(UniformError::TypeMismatch {
+                name: String::from("u_test"),
+                declared: UniformType::Vec3,
+                input: UniformType::Vec4,
+            })
+        );
+        // Integers aren't converted, GL rejects them for float uniforms
+        assert!(uniform(UniformType::Float).check::<i32>(1).is_err());
+        assert!(uniform(UniformType::Int).check::<u32>(1).is_err());
+        assert!(uniform(UniformType::Other)
+            .check::<[[f32; 3]; 3]>(1)
+            .is_err());
+    }
+
+    #[test]
+    fn arrays() {
+        let weights = UniformInfo {
+            array_size: 4,
+            ..uniform(UniformType::Vec4)
+        };
+        let values = [[0.0; 4]; 5];
+
+        assert_eq!(weights.check::<&[[f32; 4]]>(values[..4].len()), Ok(()));
+        assert_eq!(
+            weights.check::<&[[f32; 4]]>(values.as_slice().count()),
+            Err(UniformError::TooManyElements {
+                name: String::from("u_test"),
+                array_size: 4,
+                count: 5,
+            })
+        );
+        assert!(weights.check::<&[[f32; 3]]>(1).is_err());
+    }
+
+    #[test]
+    fn samplers() {
+        for uniform_type in [
+            UniformType::Sampler2D,
+            UniformType::Sampler3D,
+            UniformType::SamplerCube,
+            UniformType::Sampler2DArray,
+        ] {
+            assert_eq!(uniform(uniform_type).check::<TextureUnit>(1), Ok(()));
+        }
+        assert!(uniform(UniformType::Int).check::<TextureUnit>(1).is_err());
+        assert!(uniform(UniformType::Sampler2D).check::<i32>(1).is_err());
+    }
+
+    // A program as `ShaderProgram::new` leaves it, without a backend
+    #[cfg(feature = "wgpu")]
+    fn program() -> ShaderProgram {
+        use crate::lib::{Program, UniformLocation};
+
+        let weights = UniformInfo {
+            name: String::from("u_weights"),
+            uniform_type: UniformType::Float,
+            array_size: 4,
+        };
+        ShaderProgram {
+            id: Program::Wgpu(0),
+            uniforms: HashMap::from([(weights.name.clone(), weights)]),
+            locations: HashMap::from([(String::from("u_weights"), UniformLocation::Wgpu(16))]),
+        }
+    }
+
+    #[cfg(feature = "wgpu")]
+    #[test]
+    fn handles() {
+        use crate::lib::UniformLocation;
+
+        let program = program();
+        let handle = program.uniform_handle::<&[f32]>("u_weights").unwrap();
+        assert!(matches!(handle.location, UniformLocation::Wgpu(16)));
+
+        assert_eq!(
+            program.uniform_handle::<f32>("u_missing").unwrap_err(),
+            UniformError::UnknownName(String::from("u_missing"))
+        );
+        assert!(program.uniform_handle::<[f32; 2]>("u_weights").is_err());
+
+        // Rejected before the backend is touched
+        assert_eq!(
+            handle.set(&[0.0; 5]),
+            Err(UniformError::TooManyElements {
+                name: String::from("u_weights"),
+                array_size: 4,
+                count: 5,
+            })
+        );
+    }
+}
@@ -0,0 +1,483 @@
+use crate::{
+    backend::Backend,
+    common::{Capabilities, QueryResult, ShaderUtils, TextureUnit, Uniform, UniformInfo},
+    types::{BufferTarget, BufferUsage, IndexType, PolygonMode, PrimitiveTopology, ScalarType},
+};
+use once_cell::sync::OnceCell;
+use std::str::FromStr;
+
+#[cfg(feature = "opengl")]
+use crate::opengl;
+#[cfg(feature = "vulkan")]
+use crate::vulkan;
+#[cfg(feature = "webgl")]
+use crate::webgl;
+#[cfg(feature = "wgpu")]
+use crate::webgpu;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    #[cfg(feature = "opengl")]
+    OpenGl,
+    #[cfg(feature = "webgl")]
+    WebGl,
+    #[cfg(feature = "vulkan")]
+    Vulkan,
+    #[cfg(feature = "wgpu")]
+    Wgpu,
+}
+
+impl BackendKind {
+    pub const AVAILABLE: &'static [BackendKind] = &[
+        #[cfg(feature = "opengl")]
+        BackendKind::OpenGl,
+        #[cfg(feature = "webgl")]
+        BackendKind::WebGl,
+        #[cfg(feature = "vulkan")]
+        BackendKind::Vulkan,
+        #[cfg(feature = "wgpu")]
+        BackendKind::Wgpu,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "opengl")]
+            BackendKind::OpenGl => "opengl",
+            #[cfg(feature = "webgl")]
+            BackendKind::WebGl => "webgl",
+            #[cfg(feature = "vulkan")]
+            BackendKind::Vulkan => "vulkan",
+            #[cfg(feature = "wgpu")]
+            BackendKind::Wgpu => "wgpu",
+        }
+    }
+
+    // REINDEER_BACKEND=<name> overrides the default, which is the first
+    // compiled in backend
+    fn from_env_or_default() -> BackendKind {
+        Self::from_env(std::env::var("REINDEER_BACKEND").ok().as_deref())
+            .unwrap_or_else(|err| panic!("REINDEER_BACKEND: {}", err))
+    }
+
+    fn from_env(requested: Option<&str>) -> Result<BackendKind, String> {
+        match requested {
+            Some(requested) => requested.parse(),
+            None => Ok(Self::AVAILABLE[0]),
+        }
+    }
+}
+
+// The name of a backend as given by `BackendKind::name`. Backends that
+// exist but aren't compiled in get their own error.
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<BackendKind, String> {
+        if let Some(kind) = Self::AVAILABLE.iter().find(|kind| kind.name() == name) {
+            return Ok(*kind);
+        }
+
+        if ["opengl", "webgl", "vulkan", "wgpu"].contains(&name) {
+            Err(format!(
+                "Backend {} is not compiled in, enable its feature",
+                name
+            ))
+        } else {
+            Err(format!(
+                "Unknown backend {}, expected opengl, webgl, vulkan or wgpu",
+                name
+            ))
+        }
+    }
+}
+
+static SELECTED: OnceCell<BackendKind> = OnceCell::new();
+
+pub fn select(kind: BackendKind) {
+    SELECTED
+        .set(kind)
+        .expect("The backend has to be selected once, before reindeer::init");
+}
+
+pub fn selected() -> BackendKind {
+    *SELECTED.get_or_init(BackendKind::from_env_or_default)
+}
+
+#[allow(clippy::large_enum_variant)]
+pub enum Context {
+    #[cfg(feature = "opengl")]
+    OpenGl(opengl::Context),
+    #[cfg(feature = "webgl")]
+    WebGl(webgl::Context),
+    #[cfg(feature = "vulkan")]
+    Vulkan(vulkan::Context),
+    #[cfg(feature = "wgpu")]
+    Wgpu(webgpu::Context),
+}
+
+// Every resource handle remembers which backend created it
+macro_rules! handle {
+    ($name:ident) => {
+        #[derive(Debug, Clone)]
+        pub enum $name {
+            #[cfg(feature = "opengl")]
+            OpenGl(<opengl::Context as Backend>::$name),
+            #[cfg(feature = "webgl")]
+            WebGl(<webgl::Context as Backend>::$name),
+            #[cfg(feature = "vulkan")]
+            Vulkan(<vulkan::Context as Backend>::$name),
+            #[cfg(feature = "wgpu")]
+            Wgpu(<webgpu::Context as Backend>::$name),
+        }
+    };
+}
+
+handle!(Buffer);
+handle!(Program);
+handle!(Shader);
+handle!(Vao);
+handle!(Query);
+handle!(UniformLocation);
+
+macro_rules! wrap {
+    (@ $handle:ident ; $variant:ident ; $body:expr) => {
+        $handle::$variant($body)
+    };
+    (@ ; $variant:ident ; $body:expr) => {
+        $body
+    };
+}
+
+// Calls `$body` on the backend context behind `$context`, with the handles
+// unwrapped into the same backend's types and the result optionally wrapped
+// into a handle again.
+macro_rules! dispatch {
+    ($context:expr, |$ctx:ident $(, $arg:ident: $kind:ident)*| $(-> $handle:ident)? $body:expr) => {
+        #[allow(unreachable_patterns)]
+        match ($context, $($arg),*) {
+            #[cfg(feature = "opengl")]
+            (Context::OpenGl($ctx), $($kind::OpenGl($arg)),*) => wrap!(@ $($handle)? ; OpenGl ; $body),
+            #[cfg(feature = "webgl")]
+            (Context::WebGl($ctx), $($kind::WebGl($arg)),*) => wrap!(@ $($handle)? ; WebGl ; $body),
+            #[cfg(feature = "vulkan")]
+            (Context::Vulkan($ctx), $($kind::Vulkan($arg)),*) => wrap!(@ $($handle)? ; Vulkan ; $body),
+            #[cfg(feature = "wgpu")]
+            (Context::Wgpu($ctx), $($kind::Wgpu($arg)),*) => wrap!(@ $($handle)? ; Wgpu ; $body),
+            _ => panic!("Resource was created by a different backend"),
+        }
+    };
+}
+
+impl Backend for Context {
+    type Context = Context;
+    type Shader = Shader;
+    type Program = Program;
+    type Buffer = Buffer;
+    type Vao = Vao;
+    type Query = Query;
+    type UniformLocation = UniformLocation;
+
+    fn new() -> Self::Context {
+        match selected() {
+            #[cfg(feature = "opengl")]
+            BackendKind::OpenGl => Context::OpenGl(opengl::Context::new()),
+            #[cfg(feature = "webgl")]
+            BackendKind::WebGl => Context::WebGl(webgl::Context::new()),
+            #[cfg(feature = "vulkan")]
+            BackendKind::Vulkan => Context::Vulkan(vulkan::Context::new()),
+            #[cfg(feature = "wgpu")]
+            BackendKind::Wgpu => Context::Wgpu(webgpu::Context::new()),
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        dispatch!(self, |ctx| ctx.capabilities())
+    }
+
+    fn use_program(&self, program: &Self::Program) {
+        dispatch!(self, |ctx, program: Program| ctx.use_program(program))
+    }
+
+    fn create_buffer(&self) -> Self::Buffer {
+        dispatch!(self, |ctx| -> Buffer ctx.create_buffer())
+    }
+
+    fn bind_buffer(&self, target: BufferTarget, buffer: &Self::Buffer) {
+        dispatch!(self, |ctx, buffer: Buffer| ctx.bind_buffer(target, buffer))
+    }
+
+    fn buffer_data(&self, data: &[u8], target: BufferTarget, usage: BufferUsage) {
+        dispatch!(self, |ctx| ctx.buffer_data(data, target, usage))
+    }
+
+    fn buffer_sub_data(&self, data: &[u8], target: BufferTarget, offset: i32) {
+        dispatch!(self, |ctx| ctx.buffer_sub_data(data, target, offset))
+    }
+
+    fn create_vertex_array(&self) -> Self::Vao {
+        dispatch!(self, |ctx| -> Vao ctx.create_vertex_array())
+    }
+
+    fn bind_vertex_array(&self, vao: &Self::Vao) {
+        dispatch!(self, |ctx, vao: Vao| ctx.bind_vertex_array(vao))
+    }
+
+    fn vertex_attrib_pointer(
+        &self,
+        index: u32,
+        size: i32,
+        type_: ScalarType,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    ) {
+        dispatch!(self, |ctx| ctx.vertex_attrib_pointer(
+            index, size, type_, normalized, stride, offset
+        ))
+    }
+
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        dispatch!(self, |ctx| ctx.enable_vertex_attrib_array(index))
+    }
+
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        dispatch!(self, |ctx| ctx.vertex_attrib_divisor(index, divisor))
+    }
+
+    fn draw_arrays(&self, topology: PrimitiveTopology, first: i32, vertex_count: i32) {
+        dispatch!(self, |ctx| ctx.draw_arrays(topology, first, vertex_count))
+    }
+
+    fn draw_elements(
+        &self,
+        topology: PrimitiveTopology,
+        index_count: i32,
+        index_type: IndexType,
+        offset: i32,
+    ) {
+        dispatch!(self, |ctx| ctx.draw_elements(
+            topology,
+            index_count,
+            index_type,
+            offset
+        ))
+    }
+
+    fn draw_arrays_instanced(
+        &self,
+        topology: PrimitiveTopology,
+        first: i32,
+        vertex_count: i32,
+        instance_count: i32,
+    ) {
+        dispatch!(self, |ctx| ctx.draw_arrays_instanced(
+            topology,
+            first,
+            vertex_count,
+            instance_count
+        ))
+    }
+
+    fn draw_elements_instanced(
+        &self,
+        topology: PrimitiveTopology,
+        index_count: i32,
+        index_type: IndexType,
+        offset: i32,
+        instance_count: i32,
+    ) {
+        dispatch!(self, |ctx| ctx.draw_elements_instanced(
+            topology,
+            index_count,
+            index_type,
+            offset,
+            instance_count
+        ))
+    }
+
+    fn create_timer_query(&self) -> Self::Query {
+        dispatch!(self, |ctx| -> Query ctx.create_timer_query())
+    }
+
+    fn begin_timer_query(&self, query: &Self::Query) {
+        dispatch!(self, |ctx, query: Query| ctx.begin_timer_query(query))
+    }
+
+    fn end_timer_query(&self, query: &Self::Query) {
+        dispatch!(self, |ctx, query: Query| ctx.end_timer_query(query))
+    }
+
+    fn timer_query_result(&self, query: &Self::Query) -> QueryResult {
+        dispatch!(self, |ctx, query: Query| ctx.timer_query_result(query))
+    }
+
+    fn set_clear_color(&mut self, red: f32, green: f32, blue: f32, alpha: f32) {
+        dispatch!(self, |ctx| ctx.set_clear_color(red, green, blue, alpha))
+    }
+
+    fn clear(&self) {
+        dispatch!(self, |ctx| ctx.clear())
+    }
+
+    fn set_polygon_mode(&self, mode: PolygonMode) {
+        dispatch!(self, |ctx| ctx.set_polygon_mode(mode))
+    }
+
+    fn before_draw(&mut self) {
+        dispatch!(self, |ctx| ctx.before_draw())
+    }
+
+    fn after_draw(&mut self) {
+        dispatch!(self, |ctx| ctx.after_draw())
+    }
+
+    fn draw_loop(mut draw_frame: impl FnMut() + 'static) {
+        // The gizmos of the frame go on top of everything else
+        let draw_frame = move || {
+            draw_frame();
+            crate::debug::draw_gizmos();
+        };
+
+        match selected() {
+            #[cfg(feature = "opengl")]
+            BackendKind::OpenGl => opengl::Context::draw_loop(draw_frame),
+            #[cfg(feature = "webgl")]
+            BackendKind::WebGl => webgl::Context::draw_loop(draw_frame),
+            #[cfg(feature = "vulkan")]
+            BackendKind::Vulkan => vulkan::Context::draw_loop(draw_frame),
+            #[cfg(feature = "wgpu")]
+            BackendKind::Wgpu => webgpu::Context::draw_loop(draw_frame),
+        }
+    }
+}
+
+impl Context {
+    pub fn kind(&self) -> BackendKind {
+        match self {
+            #[cfg(feature = "opengl")]
+            Context::OpenGl(_) => BackendKind::OpenGl,
+            #[cfg(feature = "webgl")]
+            Context::WebGl(_) => BackendKind::WebGl,
+            #[cfg(feature = "vulkan")]
+            Context::Vulkan(_) => BackendKind::Vulkan,
+            #[cfg(feature = "wgpu")]
+            Context::Wgpu(_) => BackendKind::Wgpu,
+        }
+    }
+
+    // Only the offscreen backends keep their frames around
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_pixels(&self) -> Vec<u8> {
+        match self {
+            #[cfg(feature = "vulkan")]
+            Context::Vulkan(ctx) => ctx.read_pixels(),
+            #[cfg(feature = "wgpu")]
+            Context::Wgpu(ctx) => ctx.read_pixels(),
+            #[allow(unreachable_patterns)]
+            _ => panic!("The {} backend can't read back pixels", self.kind().name()),
+        }
+    }
+}
+
+impl ShaderUtils for Context {
+    fn compile_program(&self, name: &str) -> Self::Program {
+        dispatch!(self, |ctx| -> Program ctx.compile_program(name))
+    }
+
+    fn active_uniforms(&self, program: &Self::Program) -> Vec<UniformInfo> {
+        dispatch!(self, |ctx, program: Program| ctx.active_uniforms(program))
+    }
+
+    fn uniform_location(&self, program: &Self::Program, name: &str) -> Self::UniformLocation {
+        dispatch!(self, |ctx, program: Program| -> UniformLocation ctx.uniform_location(program, name))
+    }
+}
+
+macro_rules! uniform {
+    ($($input:ty),*) => {
+        $(
+            impl Uniform<$input> for Context {
+                fn set_uniform(
+                    &self,
+                    program: &Self::Program,
+                    location: &Self::UniformLocation,
+                    input: $input,
+                ) {
+                    dispatch!(self, |ctx, program: Program, location: UniformLocation| {
+                        ctx.set_uniform(program, location, input)
+                    })
+                }
+            }
+        )*
+    };
+}
+
+uniform!(
+    f32,
+    [f32; 2],
+    [f32; 3],
+    [f32; 4],
+    i32,
+    [i32; 2],
+    [i32; 3],
+    [i32; 4],
+    u32,
+    [u32; 2],
+    [u32; 3],
+    [u32; 4],
+    bool,
+    [[f32; 2]; 2],
+    [[f32; 3]; 3],
+    [[f32; 4]; 4],
+    TextureUnit,
+    &[f32],
+    &[[f32; 2]],
+    &[[f32; 3]],
+    &[[f32; 4]],
+    &[i32],
+    &[[i32; 2]],
+    &[[i32; 3]],
+    &[[i32; 4]],
+    &[u32],
+    &[[u32; 2]],
+    &[[u32; 3]],
+    &[[u32; 4]],
+    &[bool],
+    &[[[f32; 2]; 2]],
+    &[[[f32; 3]; 3]],
+    &[[[f32; 4]; 4]],
+    &[TextureUnit]
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backend_names() {
+        for &kind in BackendKind::AVAILABLE {
+            assert_eq!(kind.name().parse(), Ok(kind));
+        }
+        assert_eq!(BackendKind::from_env(None), Ok(BackendKind::AVAILABLE[0]));
+        #[cfg(feature = "vulkan")]
+        assert_eq!(
+            BackendKind::from_env(Some("vulkan")),
+            Ok(BackendKind::Vulkan)
+        );
+
+        let error = BackendKind::from_env(Some("metal")).unwrap_err();
+        assert!(error.starts_with("Unknown backend metal"), "{error}");
+        // Names are lower case, like the features
+        assert!("Vulkan".parse::<BackendKind>().is_err());
+    }
+
+    #[cfg(not(feature = "opengl"))]
+    #[test]
+    fn backend_not_compiled_in() {
+        let error = BackendKind::from_env(Some("opengl")).unwrap_err();
+        assert!(
+            error.starts_with("Backend opengl is not compiled in"),
+            "{error}"
+        );
+    }
+}
@@ -1,128 +1,969 @@
-use super::{backend::Backend, lib, types};
+use super::{
+    backend::Backend,
+    lib,
+    types::{BufferTarget, BufferUsage, IndexType, PrimitiveTopology, ScalarType},
+};
 use crate::{
-    common::{ShaderProgram, Vec3, VertexAttribute},
+    common::{ShaderProgram, Uniform, Vec3, VertexAttribute, VERTEX_ATTRIBUTE_FVEC3},
     BACKEND,
 };
+use std::cell::OnceCell;
+
+#[derive(Debug, Clone)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn index_type(&self) -> IndexType {
+        match self {
+            Indices::U16(_) => IndexType::U16,
+            Indices::U32(_) => IndexType::U32,
+        }
+    }
+
+    pub fn to_u32(&self) -> Vec<u32> {
+        match self {
+            Indices::U16(indices) => indices.iter().map(|&index| index as u32).collect(),
+            Indices::U32(indices) => indices.clone(),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Indices::U16(indices) => indices.iter().flat_map(|i| i.to_ne_bytes()).collect(),
+            Indices::U32(indices) => indices.iter().flat_map(|i| i.to_ne_bytes()).collect(),
+        }
+    }
+}
+
+// Picks 16 bit indices when they are enough
+impl From<Vec<u32>> for Indices {
+    fn from(indices: Vec<u32>) -> Self {
+        if indices.iter().all(|&index| index <= u16::MAX as u32) {
+            Indices::U16(indices.into_iter().map(|index| index as u16).collect())
+        } else {
+            Indices::U32(indices)
+        }
+    }
+}
+
+impl From<Vec<u16>> for Indices {
+    fn from(indices: Vec<u16>) -> Self {
+        Indices::U16(indices)
+    }
+}
+
+// The triangles of the topology as a list. A trailing partial triangle is
+// dropped, points and lines have no triangles.
+pub(crate) fn triangle_list(indices: &[u32], topology: PrimitiveTopology) -> Vec<u32> {
+    match topology {
+        PrimitiveTopology::Triangles => indices[..indices.len() / 3 * 3].to_vec(),
+        // Every other triangle of a strip is wound the other way
+        PrimitiveTopology::TriangleStrip => indices
+            .windows(3)
+            .enumerate()
+            .flat_map(|(i, corners)| match i % 2 {
+                0 => [corners[0], corners[1], corners[2]],
+                _ => [corners[1], corners[0], corners[2]],
+            })
+            .collect(),
+        // The first vertex with every following pair
+        PrimitiveTopology::TriangleFan => indices
+            .windows(2)
+            .skip(1)
+            .flat_map(|pair| [indices[0], pair[0], pair[1]])
+            .collect(),
+        PrimitiveTopology::Points | PrimitiveTopology::Lines | PrimitiveTopology::LineStrip => {
+            Vec::new()
+        }
+    }
+}
+
+// Anything that can be laid out in a vertex buffer. Half floats are written
+// as their `u16` bits.
+pub trait VertexData: Copy {
+    fn write_bytes(&self, bytes: &mut Vec<u8>);
+}
+
+// The attribute a field of a `#[derive(Vertex)]` struct is described by
+pub trait VertexAttributeType {
+    const ATTRIBUTE: VertexAttribute;
+}
+
+macro_rules! vertex_data {
+    ($($type:ty => $scalar:ident),*) => {
+        $(
+            impl VertexData for $type {
+                fn write_bytes(&self, bytes: &mut Vec<u8>) {
+                    bytes.extend_from_slice(&self.to_ne_bytes());
+                }
+            }
+
+            impl VertexAttributeType for $type {
+                const ATTRIBUTE: VertexAttribute =
+                    VertexAttribute::new(1, ScalarType::$scalar, false);
+            }
+        )*
+    };
+}
+
+vertex_data!(i8 => I8, u8 => U8, i16 => I16, u16 => U16, i32 => I32, u32 => U32, f32 => F32);
+
+impl<T: VertexData, const N: usize> VertexData for [T; N] {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        for item in self {
+            item.write_bytes(bytes);
+        }
+    }
+}
+
+impl<T: VertexAttributeType, const N: usize> VertexAttributeType for [T; N] {
+    const ATTRIBUTE: VertexAttribute =
+        VertexAttribute::new(N as u32 * T::ATTRIBUTE.count, T::ATTRIBUTE.item_type, false);
+}
+
+// A vertex that knows its attribute layout, usually through
+// `#[derive(Vertex)]`:
+//
+// #[derive(Clone, Copy, Vertex)]
+// struct PositionNormal {
+//     #[location(0)]
+//     position: Vec3,
+//     #[location(2)]
+//     normal: Vec3,
+// }
+pub trait Vertex: VertexData {
+    fn attributes() -> Vec<Option<VertexAttribute>>;
+}
+
+// Plain positions
+impl Vertex for Vec3 {
+    fn attributes() -> Vec<Option<VertexAttribute>> {
+        vec![Some(VERTEX_ATTRIBUTE_FVEC3)]
+    }
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+// One component of an attribute converted the way the GPU would
+fn read_scalar(bytes: &[u8], item_type: ScalarType, normalized: bool) -> f32 {
+    macro_rules! integer {
+        ($type:ty) => {{
+            let value = <$type>::from_ne_bytes(bytes.try_into().unwrap());
+            if normalized {
+                (value as f32 / <$type>::MAX as f32).max(-1.0)
+            } else {
+                value as f32
+            }
+        }};
+    }
+
+    match item_type {
+        ScalarType::I8 => integer!(i8),
+        ScalarType::U8 => integer!(u8),
+        ScalarType::I16 => integer!(i16),
+        ScalarType::U16 => integer!(u16),
+        ScalarType::I32 => integer!(i32),
+        ScalarType::U32 => integer!(u32),
+        ScalarType::F16 => f16_to_f32(u16::from_ne_bytes(bytes.try_into().unwrap())),
+        ScalarType::F32 => f32::from_ne_bytes(bytes.try_into().unwrap()),
+    }
+}
+
+// Truncates the mantissa, values too large for a half float become infinite
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+
+    if value.is_nan() {
+        sign | 0x7e00
+    } else if exponent >= 0x1f {
+        sign | 0x7c00
+    } else if exponent <= 0 {
+        // Subnormal, or zero when even that is too small
+        if exponent < -10 {
+            sign
+        } else {
+            sign | ((mantissa | 0x80_0000) >> (14 - exponent)) as u16
+        }
+    } else {
+        sign | ((exponent as u16) << 10) | (mantissa >> 13) as u16
+    }
+}
+
+// The inverse of `read_scalar`, integers are rounded and saturate
+fn write_scalar(value: f32, item_type: ScalarType, normalized: bool, bytes: &mut [u8]) {
+    macro_rules! integer {
+        ($type:ty) => {{
+            let value = if normalized {
+                value * <$type>::MAX as f32
+            } else {
+                value
+            };
+            bytes.copy_from_slice(&(value.round() as $type).to_ne_bytes());
+        }};
+    }
+
+    match item_type {
+        ScalarType::I8 => integer!(i8),
+        ScalarType::U8 => integer!(u8),
+        ScalarType::I16 => integer!(i16),
+        ScalarType::U16 => integer!(u16),
+        ScalarType::I32 => integer!(i32),
+        ScalarType::U32 => integer!(u32),
+        ScalarType::F16 => bytes.copy_from_slice(&f32_to_f16(value).to_ne_bytes()),
+        ScalarType::F32 => bytes.copy_from_slice(&value.to_ne_bytes()),
+    }
+}
+
+fn to_bytes<T: VertexData>(data: &[T]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for item in data {
+        item.write_bytes(&mut bytes);
+    }
+    bytes
+}
+
+// One buffer of interleaved attributes. `None` skips a shader location
+// without taking up space in the buffer.
+#[derive(Debug, Clone)]
+pub struct VertexBuffer {
+    pub data: Vec<u8>,
+    pub attributes: Vec<Option<VertexAttribute>>,
+    pub usage: BufferUsage,
+}
+
+impl VertexBuffer {
+    pub fn new<T: VertexData>(
+        data: &[T],
+        attributes: Vec<Option<VertexAttribute>>,
+    ) -> VertexBuffer {
+        VertexBuffer {
+            data: to_bytes(data),
+            attributes,
+            usage: BufferUsage::Static,
+        }
+    }
+
+    pub fn from_vertices<T: Vertex>(vertices: &[T]) -> VertexBuffer {
+        VertexBuffer::new(vertices, T::attributes())
+    }
+
+    // Dynamic for data updated every few frames, Stream for every frame
+    pub fn with_usage(mut self, usage: BufferUsage) -> VertexBuffer {
+        self.usage = usage;
+        self
+    }
+
+    // Overwrites the data from `first_vertex` on and returns the byte offset
+    // written at
+    fn write_range(&mut self, first_vertex: usize, bytes: &[u8]) -> usize {
+        let offset = first_vertex * self.stride() as usize;
+        assert!(
+            offset + bytes.len() <= self.data.len(),
+            "update_range writes past the end of the vertex buffer"
+        );
+
+        self.data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        offset
+    }
+
+    pub fn stride(&self) -> u32 {
+        self.attributes
+            .iter()
+            .flatten()
+            .map(|attribute| attribute.size())
+            .sum()
+    }
+
+    // One value per vertex, converted to floats like in the shader. Missing
+    // components are filled in with (0, 0, 0, 1).
+    pub fn read_attribute(&self, index: usize) -> Option<Vec<[f32; 4]>> {
+        let attribute = self.attributes.get(index)?.as_ref()?;
+        let offset: usize = self.attributes[..index]
+            .iter()
+            .flatten()
+            .map(|attribute| attribute.size() as usize)
+            .sum();
+        let item_size = attribute.item_size as usize;
+        let count = attribute.count as usize;
+
+        let values = self
+            .data
+            .chunks_exact(self.stride() as usize)
+            .map(|vertex| {
+                let mut value = [0.0, 0.0, 0.0, 1.0];
+                for (i, component) in value.iter_mut().take(count).enumerate() {
+                    let start = offset + i * item_size;
+                    *component = read_scalar(
+                        &vertex[start..start + item_size],
+                        attribute.item_type,
+                        attribute.normalized,
+                    );
+                }
+                value
+            })
+            .collect();
+
+        Some(values)
+    }
+
+    // Overwrites an attribute with one value per vertex, converted to its
+    // type. Components past the attribute's count are left out. False when
+    // the buffer has no attribute at the index.
+    pub fn write_attribute(&mut self, index: usize, values: &[[f32; 4]]) -> bool {
+        let Some(Some(attribute)) = self.attributes.get(index).cloned() else {
+            return false;
+        };
+        let offset: usize = self.attributes[..index]
+            .iter()
+            .flatten()
+            .map(|attribute| attribute.size() as usize)
+            .sum();
+        let item_size = attribute.item_size as usize;
+        let stride = self.stride() as usize;
+
+        for (vertex, value) in self.data.chunks_exact_mut(stride).zip(values) {
+            for (i, &component) in value.iter().take(attribute.count as usize).enumerate() {
+                let start = offset + i * item_size;
+                write_scalar(
+                    component,
+                    attribute.item_type,
+                    attribute.normalized,
+                    &mut vertex[start..start + item_size],
+                );
+            }
+        }
+
+        true
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        match self.stride() {
+            0 => 0,
+            stride => self.data.len() / stride as usize,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Entity {
-    pub vertices: Vec<f32>,
+    pub buffers: Vec<VertexBuffer>,
+    pub buffer_handles: Vec<<lib::Context as Backend>::Buffer>,
+    // The element buffer holding `indices`
+    pub index_buffer: Option<<lib::Context as Backend>::Buffer>,
+    pub indices: Option<Indices>,
     pub vao: <lib::Context as Backend>::Vao,
     pub shader: Option<ShaderProgram>,
+    pub topology: PrimitiveTopology,
+    // Written to `u_point_size` before point lists are drawn, the shader
+    // passes it on to gl_PointSize
+    pub point_size: f32,
+    // Written to `u_model` before every draw when set, so entities can share
+    // a program. The debug render modes place the entity with it too.
+    pub model: Option<[[f32; 4]; 4]>,
+    // Unindexed copy with barycentric coordinates for the debug render modes,
+    // made on first use
+    pub(crate) debug_mesh: OnceCell<Box<Entity>>,
     /*
     pub transformations: Vec<Transformation> -> Transformation::Scale(0.2) or Transformation::Transalte(0.2, 0.0, 0.0), ...
     */
-    pub attributes: Option<Vec<Option<VertexAttribute>>>,
 }
 
 impl Entity {
-    pub fn new(
+    pub fn new<T: Vertex>(
         // ctx: &lib::Context,
-        vertices: Vec<Vec3>,
+        vertices: &[T],
+        shader: Option<ShaderProgram>,
+    ) -> Entity {
+        Entity::with_buffers(vec![VertexBuffer::from_vertices(vertices)], None, shader)
+    }
+
+    pub fn new_indexed<T: Vertex>(
+        vertices: &[T],
+        indices: impl Into<Indices>,
+        shader: Option<ShaderProgram>,
+    ) -> Entity {
+        Entity::with_buffers(
+            vec![VertexBuffer::from_vertices(vertices)],
+            Some(indices.into()),
+            shader,
+        )
+    }
+
+    // Shader locations are numbered across the buffers in order, e.g.
+    // positions in the first buffer are location 0, normals in the second
+    // buffer are location 1.
+    pub fn with_buffers(
+        buffers: Vec<VertexBuffer>,
+        indices: Option<Indices>,
         shader: Option<ShaderProgram>,
-        vertex_attributes: Option<Vec<Option<VertexAttribute>>>,
     ) -> Entity {
         let vao = BACKEND.lock().unwrap().create_vertex_array();
         BACKEND.lock().unwrap().bind_vertex_array(&vao);
 
-        let data = vertices.into_iter().flatten().collect::<Vec<f32>>();
+        let index_buffer = indices.as_ref().map(|indices| {
+            let element_buffer = BACKEND.lock().unwrap().create_buffer();
+            BACKEND
+                .lock()
+                .unwrap()
+                .bind_buffer(BufferTarget::ElementArray, &element_buffer);
+            BACKEND.lock().unwrap().buffer_data(
+                &indices.to_bytes(),
+                BufferTarget::ElementArray,
+                BufferUsage::Static,
+            );
+            element_buffer
+        });
+
+        let mut entity = Entity {
+            buffers: Vec::new(),
+            buffer_handles: Vec::new(),
+            index_buffer,
+            indices,
+            vao,
+            shader,
+            topology: PrimitiveTopology::Triangles,
+            point_size: 1.0,
+            model: None,
+            debug_mesh: OnceCell::new(),
+        };
+
+        for vertex_buffer in buffers {
+            entity.push_vertex_buffer(vertex_buffer);
+        }
+
+        entity
+    }
+
+    // The new buffer's attributes continue the shader locations after the
+    // existing ones
+    pub fn push_vertex_buffer(&mut self, vertex_buffer: VertexBuffer) {
+        self.attach_buffer(vertex_buffer, 0);
+    }
+
+    // A divisor of 1 advances the buffer's attributes once per instance
+    // instead of once per vertex
+    fn attach_buffer(&mut self, vertex_buffer: VertexBuffer, divisor: u32) {
+        let first_location: u32 = self
+            .buffers
+            .iter()
+            .map(|buffer| buffer.attributes.len())
+            .sum::<usize>()
+            .try_into()
+            .expect("Vertex attribute index cast to u32 failed");
+
+        BACKEND.lock().unwrap().bind_vertex_array(&self.vao);
 
         let buffer = BACKEND.lock().unwrap().create_buffer();
         BACKEND
             .lock()
             .unwrap()
-            .bind_buffer(types::ARRAY_BUFFER, &buffer);
+            .bind_buffer(BufferTarget::Array, &buffer);
+        BACKEND.lock().unwrap().buffer_data(
+            &vertex_buffer.data,
+            BufferTarget::Array,
+            vertex_buffer.usage,
+        );
+
+        let stride = vertex_buffer
+            .stride()
+            .try_into()
+            .expect("Calculating stride failed. Cast to i32 failed.");
+        let mut offset = 0;
+
+        for (attribute_options, location) in vertex_buffer.attributes.iter().zip(first_location..) {
+            if let Some(attribute) = attribute_options {
+                BACKEND.lock().unwrap().enable_vertex_attrib_array(location);
+                BACKEND.lock().unwrap().vertex_attrib_pointer(
+                    location,
+                    attribute
+                        .count
+                        .try_into()
+                        .expect("Vertex attribute count cast to i32 failed"),
+                    attribute.item_type,
+                    attribute.normalized,
+                    stride,
+                    offset,
+                );
+                if divisor != 0 {
+                    BACKEND
+                        .lock()
+                        .unwrap()
+                        .vertex_attrib_divisor(location, divisor);
+                }
+
+                let offset_increment: i32 = attribute
+                    .size()
+                    .try_into()
+                    .expect("Failed to increment vertex attribute offset. Cast to i32 failed.");
+
+                offset += offset_increment;
+            }
+        }
+
+        self.buffer_handles.push(buffer);
+        self.buffers.push(vertex_buffer);
+    }
+
+    pub fn push_vertex_attribute<T: VertexData>(&mut self, attribute: VertexAttribute, data: &[T]) {
+        self.push_vertex_buffer(VertexBuffer::new(data, vec![Some(attribute)]));
+    }
+
+    // Replaces the whole content of a buffer. Data of the same size is
+    // written in place, otherwise the buffer is reallocated.
+    pub fn update_vertices<T: VertexData>(&mut self, buffer: usize, data: &[T]) {
+        self.debug_mesh.take();
+        let bytes = to_bytes(data);
+        let vertex_buffer = &mut self.buffers[buffer];
+
+        BACKEND
+            .lock()
+            .unwrap()
+            .bind_buffer(BufferTarget::Array, &self.buffer_handles[buffer]);
+
+        if bytes.len() == vertex_buffer.data.len() {
+            BACKEND
+                .lock()
+                .unwrap()
+                .buffer_sub_data(&bytes, BufferTarget::Array, 0);
+        } else {
+            BACKEND
+                .lock()
+                .unwrap()
+                .buffer_data(&bytes, BufferTarget::Array, vertex_buffer.usage);
+        }
+
+        vertex_buffer.data = bytes;
+    }
+
+    // Overwrites the vertices of a buffer starting at `first_vertex`. The
+    // buffer isn't grown, a range past its end panics before anything is
+    // written.
+    pub fn update_range<T: VertexData>(&mut self, buffer: usize, first_vertex: usize, data: &[T]) {
+        self.debug_mesh.take();
+        let bytes = to_bytes(data);
+        let offset = self.buffers[buffer].write_range(first_vertex, &bytes);
+
+        BACKEND
+            .lock()
+            .unwrap()
+            .bind_buffer(BufferTarget::Array, &self.buffer_handles[buffer]);
+        BACKEND.lock().unwrap().buffer_sub_data(
+            &bytes,
+            BufferTarget::Array,
+            offset
+                .try_into()
+                .expect("Vertex buffer offset cast to i32 failed"),
+        );
+    }
+
+    // Replaces the indices, an entity without them gets an element buffer
+    pub fn update_indices(&mut self, indices: impl Into<Indices>) {
+        self.debug_mesh.take();
+        let indices = indices.into();
+
+        BACKEND.lock().unwrap().bind_vertex_array(&self.vao);
+        let index_buffer = self
+            .index_buffer
+            .get_or_insert_with(|| BACKEND.lock().unwrap().create_buffer());
         BACKEND
             .lock()
             .unwrap()
-            .buffer_data(&data, types::ARRAY_BUFFER, types::STATIC_DRAW);
-
-        if let Some(attributes) = &vertex_attributes {
-            let stride = attributes
-                .iter()
-                .map(|opt| {
-                    if opt.is_some() {
-                        (opt.as_ref().unwrap().count * opt.as_ref().unwrap().item_size)
-                            .try_into()
-                            .expect("Calculating stride failed. Cast to i32 failed.")
-                    } else {
-                        0
-                    }
-                })
-                .sum();
-
-            let mut offset = 0;
-
-            for (index, attribute_options) in attributes.iter().enumerate() {
-                match attribute_options {
-                    Some(attribute) => {
-                        let ind = index
-                            .try_into()
-                            .expect("Vertex attribute index cast to u32 failed");
-
-                        BACKEND.lock().unwrap().enable_vertex_attrib_array(ind);
-                        BACKEND.lock().unwrap().vertex_attrib_pointer(
-                            ind,
-                            attribute
-                                .count
-                                .try_into()
-                                .expect("Vertex attribute count cast to i32 failed"),
-                            attribute.item_type,
-                            false, // TODO
-                            stride,
-                            offset,
-                        );
-
-                        let offset_increment: i32 =
-                            (attribute.count * attribute.item_size).try_into().expect(
-                                "Failed to increment vertex attribute offset. Cast to i32 failed.",
-                            );
-
-                        offset += offset_increment;
-                    }
-                    None => (),
+            .bind_buffer(BufferTarget::ElementArray, index_buffer);
+        BACKEND.lock().unwrap().buffer_data(
+            &indices.to_bytes(),
+            BufferTarget::ElementArray,
+            BufferUsage::Static,
+        );
+
+        self.indices = Some(indices);
+    }
+
+    // Indices of the entity's triangles, consecutive vertices without an index
+    // buffer. Strips and fans are turned into lists, points and lines have no
+    // triangles.
+    pub fn triangle_indices(&self) -> Vec<u32> {
+        let indices = match &self.indices {
+            Some(indices) => indices.to_u32(),
+            None => (0..self.vertex_count() as u32).collect(),
+        };
+
+        triangle_list(&indices, self.topology)
+    }
+
+    // Every buffer holds the same number of vertices, the first one is asked
+    pub fn vertex_count(&self) -> usize {
+        self.buffers
+            .first()
+            .map(|buffer| buffer.vertex_count())
+            .unwrap_or(0)
+    }
+
+    // The CPU-side values of the attribute at a shader location, see
+    // `VertexBuffer::read_attribute`
+    pub fn read_attribute(&self, location: u32) -> Option<Vec<[f32; 4]>> {
+        let mut first = 0;
+
+        for buffer in &self.buffers {
+            let count = buffer.attributes.len() as u32;
+            if location < first + count {
+                return buffer.read_attribute((location - first) as usize);
+            }
+            first += count;
+        }
+
+        None
+    }
+
+    // Writes the attribute at a shader location and uploads its buffer, see
+    // `VertexBuffer::write_attribute`
+    pub fn write_attribute(&mut self, location: u32, values: &[[f32; 4]]) -> bool {
+        let mut first = 0;
+
+        for buffer in 0..self.buffers.len() {
+            let count = self.buffers[buffer].attributes.len() as u32;
+            if location < first + count {
+                let index = (location - first) as usize;
+                if !self.buffers[buffer].write_attribute(index, values) {
+                    return false;
                 }
+
+                let data = self.buffers[buffer].data.clone();
+                self.update_vertices(buffer, &data);
+                return true;
             }
+            first += count;
         }
 
-        Entity {
-            vertices: data,
-            vao,
-            shader,
-            attributes: vertex_attributes,
+        false
+    }
+
+    // The vertices (or indices) are read as triangles unless set otherwise
+    pub fn with_topology(mut self, topology: PrimitiveTopology) -> Entity {
+        self.topology = topology;
+        self
+    }
+
+    // Points larger than `Capabilities::max_point_size` are clamped, wgpu
+    // always draws them 1 pixel wide
+    pub fn with_point_size(mut self, point_size: f32) -> Entity {
+        let max_point_size = crate::capabilities().max_point_size;
+        if point_size > max_point_size {
+            log::warn!(
+                "Point size {point_size} is larger than the backend supports, points are drawn {max_point_size} pixels wide"
+            );
         }
+
+        self.point_size = point_size;
+        self
+    }
+
+    pub fn with_model(mut self, model: [[f32; 4]; 4]) -> Entity {
+        self.model = Some(model);
+        self
     }
 
     pub fn draw(&self, ctx: &lib::Context) {
-        if let Some(shader) = &self.shader {
+        self.draw_instances(ctx, self.shader.as_ref(), None);
+    }
+
+    // Draws the geometry with another program, e.g. one of the debug shaders
+    pub(crate) fn draw_with_shader(&self, ctx: &lib::Context, shader: &ShaderProgram) {
+        self.draw_instances(ctx, Some(shader), None);
+    }
+
+    fn draw_instances(
+        &self,
+        ctx: &lib::Context,
+        shader: Option<&ShaderProgram>,
+        instance_count: Option<i32>,
+    ) {
+        if let Some(shader) = shader {
             ctx.use_program(&shader.id);
+            if self.topology == PrimitiveTopology::Points {
+                if let Some(location) = shader.locations.get("u_point_size") {
+                    ctx.set_uniform(&shader.id, location, self.point_size);
+                }
+            }
+            if let Some(model) = self.model {
+                if shader.check_uniform::<[[f32; 4]; 4]>("u_model").is_ok() {
+                    ctx.set_uniform(&shader.id, &shader.locations["u_model"], model);
+                }
+            }
         }
         ctx.bind_vertex_array(&self.vao);
 
-        let divide_vertex_count_by: usize = self
-            .attributes
-            .as_ref()
-            .and_then(|a| {
-                a.iter().fold(Some(0), |acc, item| {
-                    Some(acc.unwrap() + item.as_ref().map(|attribute| attribute.count).unwrap_or(0))
-                })
-            })
-            .unwrap_or(1)
-            .try_into()
-            .unwrap();
+        let vertex_count = self.vertex_count().try_into().unwrap();
 
-        ctx.draw_arrays(
-            types::TRIANGLES,
-            0,
-            (self.vertices.len() / divide_vertex_count_by)
-                .try_into()
-                .unwrap(),
-        );
+        match (&self.indices, instance_count) {
+            (Some(indices), None) => ctx.draw_elements(
+                self.topology,
+                indices.len().try_into().unwrap(),
+                indices.index_type(),
+                0,
+            ),
+            (Some(indices), Some(instance_count)) => ctx.draw_elements_instanced(
+                self.topology,
+                indices.len().try_into().unwrap(),
+                indices.index_type(),
+                0,
+                instance_count,
+            ),
+            (None, None) => ctx.draw_arrays(self.topology, 0, vertex_count),
+            (None, Some(instance_count)) => {
+                ctx.draw_arrays_instanced(self.topology, 0, vertex_count, instance_count)
+            }
+        }
     }
 
     pub fn bind_shader(&mut self, shader: ShaderProgram) {
         self.shader = Some(shader);
     }
+}
+
+// An entity drawn once per instance in a single call. The per-instance
+// attributes (model matrices, colors, ...) follow the entity's shader
+// locations, a mat4 takes four `VERTEX_ATTRIBUTE_FVEC4` locations.
+#[derive(Debug, Clone)]
+pub struct InstancedEntity {
+    pub entity: Entity,
+    pub instance_buffer: usize,
+}
+
+impl InstancedEntity {
+    pub fn new(mut entity: Entity, instances: VertexBuffer) -> InstancedEntity {
+        entity.attach_buffer(instances, 1);
+
+        InstancedEntity {
+            instance_buffer: entity.buffers.len() - 1,
+            entity,
+        }
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.entity.buffers[self.instance_buffer].vertex_count()
+    }
+
+    // The instance count follows the length of the new data
+    pub fn update_instances<T: VertexData>(&mut self, data: &[T]) {
+        self.entity.update_vertices(self.instance_buffer, data);
+    }
+
+    pub fn update_instance_range<T: VertexData>(&mut self, first_instance: usize, data: &[T]) {
+        self.entity
+            .update_range(self.instance_buffer, first_instance, data);
+    }
+
+    pub fn draw(&self, ctx: &lib::Context) {
+        self.entity.draw_instances(
+            ctx,
+            self.entity.shader.as_ref(),
+            Some(self.instance_count().try_into().unwrap()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_and_fans_to_lists() {
+        let indices = [0, 1, 2, 3, 4];
+        assert_eq!(
+            triangle_list(&indices, PrimitiveTopology::TriangleStrip),
+            [0, 1, 2, 2, 1, 3, 2, 3, 4]
+        );
+        assert_eq!(
+            triangle_list(&indices, PrimitiveTopology::TriangleFan),
+            [0, 1, 2, 0, 2, 3, 0, 3, 4]
+        );
+        assert_eq!(
+            triangle_list(&indices, PrimitiveTopology::Triangles),
+            [0, 1, 2]
+        );
+        assert!(triangle_list(&indices, PrimitiveTopology::LineStrip).is_empty());
+        assert!(triangle_list(&indices[..2], PrimitiveTopology::TriangleStrip).is_empty());
+        assert!(triangle_list(&[], PrimitiveTopology::TriangleFan).is_empty());
+    }
+
+    #[test]
+    fn write_range() {
+        let mut buffer = VertexBuffer::new(&[[0.0f32; 3]; 4], vec![Some(VERTEX_ATTRIBUTE_FVEC3)]);
+
+        let offset = buffer.write_range(2, &to_bytes(&[[1.0f32, 2.0, 3.0]; 2]));
+        assert_eq!(offset, 24);
+        assert_eq!(
+            buffer.read_attribute(0).unwrap(),
+            [
+                [0.0, 0.0, 0.0, 1.0],
+                [0.0, 0.0, 0.0, 1.0],
+                [1.0, 2.0, 3.0, 1.0],
+                [1.0, 2.0, 3.0, 1.0],
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "past the end of the vertex buffer")]
+    fn write_range_past_the_end() {
+        let mut buffer = VertexBuffer::new(&[[0.0f32; 3]; 4], vec![Some(VERTEX_ATTRIBUTE_FVEC3)]);
+        buffer.write_range(3, &to_bytes(&[[1.0f32, 2.0, 3.0]; 2]));
+    }
+
+    #[test]
+    fn narrow_indices() {
+        let indices = Indices::from(vec![0u32, 65535]);
+        assert!(matches!(&indices, Indices::U16(indices) if indices == &[0, 65535]));
+        assert_eq!(indices.index_type(), IndexType::U16);
+        assert_eq!(indices.to_bytes().len(), 4);
+
+        let indices = Indices::from(vec![0u32, 65536]);
+        assert!(matches!(&indices, Indices::U32(indices) if indices == &[0, 65536]));
+        assert_eq!(indices.index_type(), IndexType::U32);
+        assert_eq!(indices.to_bytes().len(), 8);
+    }
+
+    // Declared out of location order, with location 1 skipped
+    #[derive(Clone, Copy, crate::Vertex)]
+    struct GapVertex {
+        #[location(3)]
+        uv: [f32; 2],
+        #[location(0)]
+        position: Vec3,
+        #[location(2)]
+        color: Vec3,
+    }
+
+    #[test]
+    fn derived_attributes() {
+        let layout: Vec<_> = GapVertex::attributes()
+            .into_iter()
+            .map(|attribute| {
+                attribute
+                    .map(|attribute| (attribute.count, attribute.item_type, attribute.normalized))
+            })
+            .collect();
+
+        assert_eq!(
+            layout,
+            [
+                Some((3, ScalarType::F32, false)),
+                None,
+                Some((3, ScalarType::F32, false)),
+                Some((2, ScalarType::F32, false)),
+            ]
+        );
+    }
+
+    #[test]
+    fn derived_writes_in_location_order() {
+        let vertex = GapVertex {
+            uv: [7.0, 8.0],
+            position: [1.0, 2.0, 3.0],
+            color: [4.0, 5.0, 6.0],
+        };
+        let mut bytes = Vec::new();
+        vertex.write_bytes(&mut bytes);
+
+        let expected: Vec<u8> = (1..=8)
+            .flat_map(|value| (value as f32).to_ne_bytes())
+            .collect();
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn read_attributes() {
+        let mut vertex = Vec::new();
+        vertex.extend([0x3800u16, 0xc000].map(u16::to_ne_bytes).concat());
+        vertex.extend([255u8, 0, 51, 255]);
+        vertex.extend((-3i16).to_ne_bytes());
+
+        let attributes = vec![
+            Some(VertexAttribute::new(2, ScalarType::F16, false)),
+            None,
+            Some(VertexAttribute::new(4, ScalarType::U8, true)),
+            Some(VertexAttribute::new(1, ScalarType::I16, false)),
+        ];
+        let buffer = VertexBuffer::new(&vertex, attributes);
+
+        assert_eq!(buffer.read_attribute(0).unwrap(), [[0.5, -2.0, 0.0, 1.0]]);
+        assert_eq!(buffer.read_attribute(1), None);
+        assert_eq!(buffer.read_attribute(2).unwrap(), [[1.0, 0.0, 0.2, 1.0]]);
+        assert_eq!(buffer.read_attribute(3).unwrap(), [[-3.0, 0.0, 0.0, 1.0]]);
+    }
 
-    pub fn push_vertex_attribute(&mut self, _attribute: VertexAttribute) {}
+    #[test]
+    fn half_floats() {
+        for value in [0.0, 1.0, -2.5, 0.333, 65504.0, 6.2e-5] {
+            let half = f16_to_f32(f32_to_f16(value));
+            assert!(
+                (half - value).abs() <= value.abs() / 1024.0,
+                "{value} {half}"
+            );
+        }
+        // The smallest subnormal
+        assert_eq!(f16_to_f32(f32_to_f16(2f32.powi(-24))), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(f32_to_f16(1.0e6)), f32::INFINITY);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+    }
+
+    #[test]
+    fn write_then_read_attributes() {
+        let attributes = vec![
+            Some(VertexAttribute::new(2, ScalarType::F16, false)),
+            None,
+            Some(VertexAttribute::new(4, ScalarType::U8, true)),
+            Some(VertexAttribute::new(1, ScalarType::I16, false)),
+        ];
+        let mut buffer = VertexBuffer::new(&[[0u8; 10]; 2], attributes);
+
+        let uvs = [[0.5, 0.25, 0.0, 1.0], [1.0, -1.0, 0.0, 1.0]];
+        let colors = [[1.0, 0.0, 0.2, 1.0], [2.0, -1.0, 0.5, 0.0]];
+        assert!(buffer.write_attribute(0, &uvs));
+        assert!(buffer.write_attribute(2, &colors));
+        assert!(buffer.write_attribute(3, &[[-3.4, 0.0, 0.0, 1.0], [40000.0, 0.0, 0.0, 1.0]]));
+        assert!(!buffer.write_attribute(1, &uvs));
+
+        assert_eq!(buffer.read_attribute(0).unwrap(), uvs);
+        // Normalized integers saturate and are rounded to the nearest step
+        assert_eq!(
+            buffer.read_attribute(2).unwrap(),
+            [
+                [1.0, 0.0, 51.0 / 255.0, 1.0],
+                [1.0, 0.0, 128.0 / 255.0, 0.0]
+            ]
+        );
+        assert_eq!(
+            buffer.read_attribute(3).unwrap(),
+            [[-3.0, 0.0, 0.0, 1.0], [32767.0, 0.0, 0.0, 1.0]]
+        );
+    }
 }
@@ -1,42 +1,71 @@
 #[macro_use]
 extern crate lazy_static;
+// `#[derive(Vertex)]` refers to `::reindeer`
+extern crate self as reindeer;
 
 pub mod backend;
 pub mod common;
 pub mod math;
 
+#[cfg(not(any(
+    feature = "opengl",
+    feature = "webgl",
+    feature = "wgpu",
+    feature = "vulkan"
+)))]
+compile_error!("At least one of the opengl, webgl, wgpu and vulkan features has to be set");
+
 #[cfg(feature = "webgl")]
 pub mod webgl;
-#[cfg(feature = "webgl")]
-pub use web_sys::WebGl2RenderingContext as types;
-#[cfg(feature = "webgl")]
-pub use webgl as lib;
 
 #[cfg(feature = "opengl")]
 pub mod opengl;
-#[cfg(feature = "opengl")]
-use gl as types;
-// #[cfg(feature = "opengl")]
-// use glfw::Context;
-#[cfg(feature = "opengl")]
-pub use opengl as lib;
 
+#[cfg(feature = "wgpu")]
+pub mod webgpu;
+
+#[cfg(feature = "vulkan")]
+pub mod vulkan;
+
+pub mod dispatch;
+pub mod types;
+pub use dispatch as lib;
+pub use dispatch::BackendKind;
+pub use reindeer_derive::Vertex;
+
+pub mod debug;
 pub mod entity;
+pub mod lod;
+pub mod mesh;
+pub mod profiler;
 pub mod scene;
 
 use crate::backend::Backend;
-pub use common::{ShaderProgram, VERTEX_ATTRIBUTE_FVEC3};
+pub use common::{
+    Capabilities, ShaderProgram, VERTEX_ATTRIBUTE_FVEC2, VERTEX_ATTRIBUTE_FVEC3,
+    VERTEX_ATTRIBUTE_FVEC4,
+};
 use include_dir::{include_dir, Dir, DirEntry::File};
-use std::sync::Mutex;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
 use std::{collections::HashMap, str};
 
 pub const WINDOW_WIDTH: u16 = 500;
 pub const WINDOW_HEIGHT: u16 = 500;
 
-#[cfg(feature = "webgl")]
-const SHADERS_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/assets/shaders/webgl");
-#[cfg(feature = "opengl")]
-const SHADERS_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/assets/shaders/opengl");
+#[cfg(any(feature = "opengl", feature = "webgl", feature = "vulkan"))]
+const GLSL_SHADERS_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/assets/shaders/glsl");
+#[cfg(feature = "wgpu")]
+const WGSL_SHADERS_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/assets/shaders/wgsl");
+
+const SHADERS_DIRS: &[&Dir] = &[
+    #[cfg(any(feature = "opengl", feature = "webgl", feature = "vulkan"))]
+    &GLSL_SHADERS_DIR,
+    #[cfg(feature = "wgpu")]
+    &WGSL_SHADERS_DIR,
+];
 
 use once_cell::sync::Lazy;
 use send_wrapper::SendWrapper;
@@ -48,27 +77,66 @@ lazy_static! {
     pub static ref SHADERS: HashMap<&'static str, &'static str> = {
         let mut map = HashMap::<&str, &str>::new();
 
-        SHADERS_DIR.entries().iter().for_each(|entry| {
-            if let File(f) = entry {
-                let name = f.path().to_str().unwrap();
-                let content = f.contents_utf8().unwrap();
-                map.insert(<&str>::clone(&name), <&str>::clone(&content));
-            } else {
-                panic!("SHADERS initialization failed");
-            }
-        });
+        SHADERS_DIRS
+            .iter()
+            .flat_map(|dir| dir.entries())
+            .for_each(|entry| {
+                if let File(f) = entry {
+                    let name = f.path().to_str().unwrap();
+                    let content = f.contents_utf8().unwrap();
+                    map.insert(<&str>::clone(&name), <&str>::clone(&content));
+                } else {
+                    panic!("SHADERS initialization failed");
+                }
+            });
 
         map
     };
 }
 
+// Has to be called before `init`, otherwise REINDEER_BACKEND or the first
+// compiled in backend is used
+pub fn select_backend(kind: BackendKind) {
+    dispatch::select(kind);
+}
+
+static DEBUG: AtomicBool = AtomicBool::new(false);
+
+// Has to be called before `init`. GL gets a debug context that logs its
+// messages, WebGL checks for errors after every call.
+pub fn set_debug(enabled: bool) {
+    DEBUG.store(enabled, Ordering::Relaxed);
+}
+
+#[cfg(any(feature = "opengl", feature = "webgl"))]
+pub(crate) fn debug() -> bool {
+    DEBUG.load(Ordering::Relaxed)
+}
+
 pub fn init() {
     BACKEND.lock().unwrap().valid();
 }
 
+#[cfg(feature = "wgpu")]
+pub async fn init_async() {
+    if dispatch::selected() == BackendKind::Wgpu {
+        webgpu::prepare().await;
+    }
+    init();
+}
+
+pub fn capabilities() -> Capabilities {
+    BACKEND.lock().unwrap().capabilities()
+}
+
 pub fn set_clear_color(red: f32, green: f32, blue: f32, alpha: f32) {
     BACKEND
         .lock()
         .unwrap()
         .set_clear_color(red, green, blue, alpha)
 }
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_pixels() -> Vec<u8> {
+    BACKEND.lock().unwrap().read_pixels()
+}
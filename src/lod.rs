@@ -0,0 +1,249 @@
+use crate::{
+    common::{Shader, ShaderProgram, Vec3},
+    debug,
+    entity::Entity,
+    math::{vec3_length, vec3_scale, vec3_sub},
+    mesh::Mesh,
+    profiler::now,
+};
+use std::{cell::Cell, sync::Mutex, time::Duration};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LodMetric {
+    // Distance from the viewer to the center of the bounding sphere, a level
+    // is used while the distance is below its threshold
+    Distance,
+    // Height of the projected bounding sphere as a fraction of the screen's,
+    // a level is used while the size is above its threshold
+    ScreenSize,
+}
+
+#[derive(Debug, Clone)]
+pub struct LodLevel {
+    pub entity: Entity,
+    pub threshold: f32,
+}
+
+struct Viewer {
+    position: Vec3,
+    vertical_fov: f32,
+}
+
+static VIEWER: Mutex<Viewer> = Mutex::new(Viewer {
+    position: [0.0; 3],
+    vertical_fov: std::f32::consts::FRAC_PI_3,
+});
+
+// Where the groups are looked at from, in the space of their bounding
+// spheres. The vertical field of view (in radians) is only used by
+// `LodMetric::ScreenSize`.
+pub fn set_viewer(position: Vec3, vertical_fov: f32) {
+    *VIEWER.lock().unwrap() = Viewer {
+        position,
+        vertical_fov,
+    };
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Transition {
+    from: Option<usize>,
+    start: Duration,
+}
+
+// Several versions of the same object, from the most detailed to the least.
+// Only the level picked for the viewer is drawn, none once the group is past
+// the last threshold.
+#[derive(Debug, Clone)]
+pub struct LodGroup {
+    pub levels: Vec<LodLevel>,
+    pub metric: LodMetric,
+    // The bounding sphere, it has to be moved along with the entities
+    pub center: Vec3,
+    pub radius: f32,
+    // Level switches are dithered over this long, see `with_cross_fade`
+    pub fade_duration: Duration,
+    current: Cell<Option<usize>>,
+    transition: Cell<Option<Transition>>,
+}
+
+impl LodGroup {
+    // The bounding sphere is taken from the positions of the first level
+    pub fn new(levels: Vec<LodLevel>, metric: LodMetric) -> LodGroup {
+        let positions = levels
+            .first()
+            .and_then(|level| level.entity.read_attribute(0))
+            .unwrap_or_default();
+
+        let (min, max) =
+            positions
+                .iter()
+                .fold(([f32::MAX; 3], [f32::MIN; 3]), |(min, max), position| {
+                    (
+                        std::array::from_fn(|i| min[i].min(position[i])),
+                        std::array::from_fn(|i| max[i].max(position[i])),
+                    )
+                });
+        let center = if positions.is_empty() {
+            [0.0; 3]
+        } else {
+            vec3_scale(std::array::from_fn(|i| min[i] + max[i]), 0.5)
+        };
+        let radius = positions
+            .iter()
+            .map(|position| vec3_length(vec3_sub([position[0], position[1], position[2]], center)))
+            .fold(0.0, f32::max);
+
+        LodGroup {
+            levels,
+            metric,
+            center,
+            radius,
+            fade_duration: Duration::ZERO,
+            current: Cell::new(None),
+            transition: Cell::new(None),
+        }
+    }
+
+    // One level per threshold, the first is the mesh itself and every other
+    // one is simplified to half the triangles of the one before
+    pub fn from_mesh(
+        mesh: &Mesh,
+        shader: Option<ShaderProgram>,
+        metric: LodMetric,
+        thresholds: &[f32],
+    ) -> LodGroup {
+        let triangles = mesh.indices.len() / 3;
+
+        let levels = thresholds
+            .iter()
+            .enumerate()
+            .map(|(level, &threshold)| {
+                let entity = if level == 0 {
+                    mesh.to_entity(shader.clone())
+                } else {
+                    mesh.simplify(level_target(triangles, level))
+                        .to_entity(shader.clone())
+                };
+                LodLevel { entity, threshold }
+            })
+            .collect();
+
+        LodGroup::new(levels, metric)
+    }
+
+    pub fn with_bounds(mut self, center: Vec3, radius: f32) -> LodGroup {
+        self.center = center;
+        self.radius = radius;
+        self
+    }
+
+    // While the levels switch, the old and the new one are drawn with
+    // complementary dither patterns. Only shaders with a `u_lod_fade`
+    // uniform fade, like phong_light_object, others switch at once.
+    pub fn with_cross_fade(mut self, duration: Duration) -> LodGroup {
+        self.fade_duration = duration;
+        self
+    }
+
+    // The level for the current viewer
+    pub fn select(&self) -> Option<usize> {
+        let viewer = VIEWER.lock().unwrap();
+        let distance = vec3_length(vec3_sub(self.center, viewer.position));
+
+        match self.metric {
+            LodMetric::Distance => self
+                .levels
+                .iter()
+                .position(|level| distance < level.threshold),
+            LodMetric::ScreenSize => {
+                // From inside the sphere it covers the whole screen
+                let size = if distance <= self.radius {
+                    f32::INFINITY
+                } else {
+                    self.radius / (distance * (viewer.vertical_fov / 2.0).tan())
+                };
+                self.levels.iter().position(|level| size > level.threshold)
+            }
+        }
+    }
+
+    pub(crate) fn draw(&self) {
+        let level = self.select();
+        let now = now();
+
+        if level != self.current.get() {
+            if !self.fade_duration.is_zero() {
+                self.transition.set(Some(Transition {
+                    from: self.current.get(),
+                    start: now,
+                }));
+            }
+            self.current.set(level);
+        }
+
+        let transition = self
+            .transition
+            .get()
+            .filter(|transition| now - transition.start < self.fade_duration);
+        self.transition.set(transition);
+
+        match transition {
+            Some(transition) => {
+                let progress =
+                    (now - transition.start).as_secs_f32() / self.fade_duration.as_secs_f32();
+
+                if let Some(from) = transition.from {
+                    self.draw_level(from, progress);
+                }
+                if let Some(level) = level {
+                    self.draw_level(level, progress - 1.0);
+                }
+            }
+            None => {
+                if let Some(level) = level {
+                    self.draw_level(level, 0.0);
+                }
+            }
+        }
+    }
+
+    // A positive fade discards that fraction of the pixels, a negative one
+    // keeps only the complementary ones. The fade is reset afterwards since
+    // the program may be shared with entities outside of the LOD.
+    fn draw_level(&self, level: usize, fade: f32) {
+        let entity = &self.levels[level].entity;
+        let shader = entity
+            .shader
+            .as_ref()
+            .filter(|shader| shader.uniforms.contains_key("u_lod_fade"));
+
+        if let Some(shader) = shader {
+            shader.set_uniform("u_lod_fade", fade).unwrap();
+        }
+        debug::draw_entity(entity);
+        if let Some(shader) = shader.filter(|_| fade != 0.0) {
+            shader.set_uniform("u_lod_fade", 0.0).unwrap();
+        }
+    }
+}
+
+// Halved once per level, but never below one triangle or past the width of
+// usize
+fn level_target(triangles: usize, level: usize) -> usize {
+    triangles.checked_shr(level as u32).unwrap_or(0).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_targets() {
+        assert_eq!(level_target(1000, 0), 1000);
+        assert_eq!(level_target(1000, 3), 125);
+        assert_eq!(level_target(1000, 20), 1);
+        // Shifts past the width of usize
+        assert_eq!(level_target(1000, 64), 1);
+        assert_eq!(level_target(1000, 100), 1);
+    }
+}
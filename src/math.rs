@@ -141,3 +141,51 @@ pub fn get_scale_matrix(x: f32, y: f32, z: f32) -> [[f32; 4]; 4] {
         [0.0, 0.0, 0.0, 1.0],
     ]
 }
+
+// The point as (x, y, z, 1), the matrix on the left like in the shaders
+pub fn mat4_transform_point(matrix: [[f32; 4]; 4], point: [f32; 3]) -> [f32; 3] {
+    std::array::from_fn(|row| {
+        matrix[row][0] * point[0]
+            + matrix[row][1] * point[1]
+            + matrix[row][2] * point[2]
+            + matrix[row][3]
+    })
+}
+
+pub fn vec3_add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+pub fn vec3_sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn vec3_scale(vector: [f32; 3], scalar: f32) -> [f32; 3] {
+    [vector[0] * scalar, vector[1] * scalar, vector[2] * scalar]
+}
+
+pub fn vec3_dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn vec3_cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub fn vec3_length(vector: [f32; 3]) -> f32 {
+    vec3_dot(vector, vector).sqrt()
+}
+
+// Zero vectors stay zero
+pub fn vec3_normalize(vector: [f32; 3]) -> [f32; 3] {
+    let length = vec3_length(vector);
+    if length == 0.0 {
+        vector
+    } else {
+        vec3_scale(vector, 1.0 / length)
+    }
+}
@@ -0,0 +1,41 @@
+use crate::{common::Vec3, entity::Entity};
+use std::fmt;
+
+// Errors of the STL and PLY readers
+#[derive(Debug)]
+pub enum FormatError {
+    Io(std::io::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatError::Io(error) => write!(f, "{error}"),
+            FormatError::Invalid(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl From<std::io::Error> for FormatError {
+    fn from(error: std::io::Error) -> Self {
+        FormatError::Io(error)
+    }
+}
+
+pub(super) fn invalid(message: impl Into<String>) -> FormatError {
+    FormatError::Invalid(message.into())
+}
+
+// The writers expect the `MeshVertex` layout: positions at location 0,
+// normals at 2, UVs at 3, and colors at 5 like the readers attach them
+pub(super) fn positions(entity: &Entity) -> Vec<Vec3> {
+    entity
+        .read_attribute(0)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|[x, y, z, _]| [x, y, z])
+        .collect()
+}
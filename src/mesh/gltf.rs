@@ -0,0 +1,528 @@
+use super::{Mesh, MeshVertex};
+use crate::{
+    common::{Shader, ShaderProgram, Vec3},
+    entity::{Entity, VertexAttributeType, VertexBuffer, VertexData},
+    math::mat4_mat4_mul,
+    types::PrimitiveTopology,
+};
+use ::gltf::{image::Format, mesh::Mode};
+
+pub use ::gltf::Error as GltfError;
+
+type Mat4 = [[f32; 4]; 4];
+
+const IDENTITY: Mat4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+// Decoded image, always 8 bit RGBA
+#[derive(Debug, Clone)]
+pub struct GltfTexture {
+    pub name: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    // Fragments with an alpha below the cutoff are discarded
+    Mask(f32),
+    Blend,
+}
+
+// Metallic-roughness PBR material. Textures are indices into
+// `Gltf::textures`.
+#[derive(Debug, Clone)]
+pub struct GltfMaterial {
+    pub name: Option<String>,
+    pub base_color: [f32; 4],
+    pub base_color_texture: Option<usize>,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub occlusion_texture: Option<usize>,
+    pub emissive: Vec3,
+    pub emissive_texture: Option<usize>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+
+// A triangle list, or points and lines, with the attributes that don't fit
+// into `MeshVertex`. Every attribute has one element per vertex of `mesh`.
+#[derive(Debug, Clone)]
+pub struct GltfPrimitive {
+    pub mesh: Mesh,
+    pub tangents: Option<Vec<[f32; 4]>>,
+    pub colors: Option<Vec<[f32; 4]>>,
+    pub uvs_1: Option<Vec<[f32; 2]>>,
+    pub joints: Option<Vec<[u16; 4]>>,
+    pub weights: Option<Vec<[f32; 4]>>,
+    pub material: Option<usize>,
+    // Strips and fans are read as triangle lists
+    pub topology: PrimitiveTopology,
+}
+
+#[derive(Debug, Clone)]
+pub struct GltfMesh {
+    pub name: Option<String>,
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GltfNode {
+    pub name: Option<String>,
+    // Relative to the parent, row-major like the matrices in `math`
+    pub transform: Mat4,
+    pub mesh: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GltfScene {
+    pub name: Option<String>,
+    pub nodes: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Gltf {
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<GltfMaterial>,
+    pub textures: Vec<GltfTexture>,
+    pub nodes: Vec<GltfNode>,
+    pub scenes: Vec<GltfScene>,
+    pub default_scene: Option<usize>,
+}
+
+// Attaches an optional attribute at `target`, leaving the locations before it
+// unused
+fn push_attribute<T: VertexAttributeType + VertexData>(
+    entity: &mut Entity,
+    location: &mut usize,
+    target: usize,
+    data: &Option<Vec<T>>,
+) {
+    if let Some(data) = data {
+        let mut attributes = vec![None; target - *location];
+        attributes.push(Some(T::ATTRIBUTE));
+        entity.push_vertex_buffer(VertexBuffer::new(data, attributes));
+        *location = target + 1;
+    }
+}
+
+impl GltfPrimitive {
+    // The `MeshVertex` attributes at locations 0, 2 and 3, then tangents at 4,
+    // colors at 5, the second UV set at 6, joints at 7 and weights at 8, where
+    // present
+    pub fn to_entity(&self, shader: Option<ShaderProgram>) -> Entity {
+        let mut entity = self.mesh.to_entity(shader).with_topology(self.topology);
+        let mut location = 4;
+
+        push_attribute(&mut entity, &mut location, 4, &self.tangents);
+        push_attribute(&mut entity, &mut location, 5, &self.colors);
+        push_attribute(&mut entity, &mut location, 6, &self.uvs_1);
+        push_attribute(&mut entity, &mut location, 7, &self.joints);
+        push_attribute(&mut entity, &mut location, 8, &self.weights);
+
+        entity
+    }
+}
+
+impl Gltf {
+    fn scene_index(&self) -> Option<usize> {
+        self.default_scene
+            .or_else(|| (!self.scenes.is_empty()).then_some(0))
+    }
+
+    // Every node of a scene with the transform from its local space to the
+    // scene's, parents before children
+    pub fn world_transforms(&self, scene: usize) -> Vec<(usize, Mat4)> {
+        let mut transforms = Vec::new();
+        let mut stack: Vec<(usize, Mat4)> = self.scenes[scene]
+            .nodes
+            .iter()
+            .rev()
+            .map(|&node| (node, IDENTITY))
+            .collect();
+
+        while let Some((node, parent)) = stack.pop() {
+            let transform = mat4_mat4_mul(parent, self.nodes[node].transform);
+            transforms.push((node, transform));
+            stack.extend(
+                self.nodes[node]
+                    .children
+                    .iter()
+                    .rev()
+                    .map(|&child| (child, transform)),
+            );
+        }
+
+        transforms
+    }
+
+    // One entity per primitive in the default scene (or the first one), with
+    // a phong_light_object shader whose `u_color` is the base color, and the
+    // node's transform as its model. The light and camera uniforms are left
+    // to the caller. Points and lines have no normals to light, they get a
+    // uniform_color shader.
+    pub fn to_entities(&self) -> Vec<Entity> {
+        let Some(scene) = self.scene_index() else {
+            return Vec::new();
+        };
+
+        self.world_transforms(scene)
+            .into_iter()
+            .filter_map(|(node, transform)| Some((self.nodes[node].mesh?, transform)))
+            .flat_map(|(mesh, transform)| {
+                self.meshes[mesh].primitives.iter().map(move |primitive| {
+                    let base_color = primitive
+                        .material
+                        .map_or([1.0; 4], |material| self.materials[material].base_color);
+
+                    let shader = ShaderProgram::new(match primitive.topology {
+                        PrimitiveTopology::Triangles => "phong_light_object",
+                        _ => "uniform_color",
+                    });
+                    shader
+                        .set_uniform("u_color", [base_color[0], base_color[1], base_color[2]])
+                        .unwrap();
+
+                    primitive.to_entity(Some(shader)).with_model(transform)
+                })
+            })
+            .collect()
+    }
+}
+
+fn transpose(matrix: Mat4) -> Mat4 {
+    std::array::from_fn(|row| std::array::from_fn(|column| matrix[column][row]))
+}
+
+fn to_rgba8(image: ::gltf::image::Data) -> Vec<u8> {
+    let channels = match image.format {
+        Format::R8 | Format::R16 => 1,
+        Format::R8G8 | Format::R16G16 => 2,
+        Format::R8G8B8 | Format::R16G16B16 | Format::R32G32B32FLOAT => 3,
+        Format::R8G8B8A8 | Format::R16G16B16A16 | Format::R32G32B32A32FLOAT => 4,
+    };
+
+    let values: Vec<u8> = match image.format {
+        Format::R8 | Format::R8G8 | Format::R8G8B8 | Format::R8G8B8A8 => image.pixels,
+        Format::R16 | Format::R16G16 | Format::R16G16B16 | Format::R16G16B16A16 => image
+            .pixels
+            .chunks_exact(2)
+            .map(|value| (u16::from_ne_bytes([value[0], value[1]]) >> 8) as u8)
+            .collect(),
+        Format::R32G32B32FLOAT | Format::R32G32B32A32FLOAT => image
+            .pixels
+            .chunks_exact(4)
+            .map(|value| {
+                let value = f32::from_ne_bytes([value[0], value[1], value[2], value[3]]);
+                (value.clamp(0.0, 1.0) * 255.0).round() as u8
+            })
+            .collect(),
+    };
+
+    values
+        .chunks_exact(channels)
+        .flat_map(|pixel| match *pixel {
+            [r] => [r, r, r, 255],
+            [r, g] => [r, g, 0, 255],
+            [r, g, b] => [r, g, b, 255],
+            [r, g, b, a] => [r, g, b, a],
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+fn unindex<T: Copy>(data: &mut Option<Vec<T>>, indices: &[u32]) {
+    if let Some(values) = data {
+        *values = indices
+            .iter()
+            .map(|&index| values[index as usize])
+            .collect();
+    }
+}
+
+// Turns strips and fans into lists and line loops into strips
+fn topology(mode: Mode, mut indices: Vec<u32>) -> (PrimitiveTopology, Vec<u32>) {
+    match mode {
+        Mode::Points => (PrimitiveTopology::Points, indices),
+        Mode::Lines => (PrimitiveTopology::Lines, indices),
+        Mode::LineStrip => (PrimitiveTopology::LineStrip, indices),
+        Mode::LineLoop => {
+            indices.extend(indices.first().copied());
+            (PrimitiveTopology::LineStrip, indices)
+        }
+        Mode::Triangles => (PrimitiveTopology::Triangles, indices),
+        Mode::TriangleStrip => (
+            PrimitiveTopology::Triangles,
+            (0..indices.len().saturating_sub(2))
+                .flat_map(|i| {
+                    if i % 2 == 0 {
+                        [indices[i], indices[i + 1], indices[i + 2]]
+                    } else {
+                        [indices[i + 1], indices[i], indices[i + 2]]
+                    }
+                })
+                .collect(),
+        ),
+        Mode::TriangleFan => (
+            PrimitiveTopology::Triangles,
+            (1..indices.len().saturating_sub(1))
+                .flat_map(|i| [indices[0], indices[i], indices[i + 1]])
+                .collect(),
+        ),
+    }
+}
+
+fn read_primitive(
+    source: ::gltf::Primitive,
+    buffers: &[::gltf::buffer::Data],
+) -> Option<GltfPrimitive> {
+    let reader = source.reader(|buffer| Some(&buffers[buffer.index()]));
+
+    let positions: Vec<Vec3> = reader.read_positions()?.collect();
+    let indices = reader
+        .read_indices()
+        .map(|indices| indices.into_u32().collect())
+        .unwrap_or_else(|| (0..positions.len() as u32).collect());
+
+    let (topology, indices) = topology(source.mode(), indices);
+
+    let normals: Option<Vec<Vec3>> = reader.read_normals().map(Iterator::collect);
+    let uvs: Option<Vec<[f32; 2]>> = reader
+        .read_tex_coords(0)
+        .map(|uvs| uvs.into_f32().collect());
+
+    let mut primitive = GltfPrimitive {
+        mesh: Mesh {
+            vertices: positions
+                .iter()
+                .enumerate()
+                .map(|(i, &position)| MeshVertex {
+                    position,
+                    normal: normals.as_ref().map_or([0.0; 3], |normals| normals[i]),
+                    uv: uvs.as_ref().map_or([0.0; 2], |uvs| uvs[i]),
+                })
+                .collect(),
+            indices,
+        },
+        tangents: reader.read_tangents().map(Iterator::collect),
+        colors: reader
+            .read_colors(0)
+            .map(|colors| colors.into_rgba_f32().collect()),
+        uvs_1: reader
+            .read_tex_coords(1)
+            .map(|uvs| uvs.into_f32().collect()),
+        joints: reader
+            .read_joints(0)
+            .map(|joints| joints.into_u16().collect()),
+        weights: reader
+            .read_weights(0)
+            .map(|weights| weights.into_f32().collect()),
+        material: source.material().index(),
+        topology,
+    };
+
+    // Without normals the faces are flat, so every triangle gets its own
+    // vertices. Points and lines are left without normals.
+    if normals.is_none() && topology == PrimitiveTopology::Triangles {
+        let indices = std::mem::take(&mut primitive.mesh.indices);
+        unindex(&mut primitive.tangents, &indices);
+        unindex(&mut primitive.colors, &indices);
+        unindex(&mut primitive.uvs_1, &indices);
+        unindex(&mut primitive.joints, &indices);
+        unindex(&mut primitive.weights, &indices);
+
+        primitive.mesh.indices = indices;
+        primitive.mesh.compute_flat_normals();
+    }
+
+    Some(primitive)
+}
+
+fn read_material(material: ::gltf::Material) -> GltfMaterial {
+    let pbr = material.pbr_metallic_roughness();
+    let texture =
+        |info: Option<::gltf::texture::Info>| info.map(|info| info.texture().source().index());
+
+    GltfMaterial {
+        name: material.name().map(str::to_string),
+        base_color: pbr.base_color_factor(),
+        base_color_texture: texture(pbr.base_color_texture()),
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        metallic_roughness_texture: texture(pbr.metallic_roughness_texture()),
+        normal_texture: material
+            .normal_texture()
+            .map(|normal| normal.texture().source().index()),
+        occlusion_texture: material
+            .occlusion_texture()
+            .map(|occlusion| occlusion.texture().source().index()),
+        emissive: material.emissive_factor(),
+        emissive_texture: texture(material.emissive_texture()),
+        alpha_mode: match material.alpha_mode() {
+            ::gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            ::gltf::material::AlphaMode::Mask => {
+                AlphaMode::Mask(material.alpha_cutoff().unwrap_or(0.5))
+            }
+            ::gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+        double_sided: material.double_sided(),
+    }
+}
+
+fn convert(
+    document: ::gltf::Document,
+    buffers: Vec<::gltf::buffer::Data>,
+    images: Vec<::gltf::image::Data>,
+) -> Gltf {
+    Gltf {
+        meshes: document
+            .meshes()
+            .map(|mesh| GltfMesh {
+                name: mesh.name().map(str::to_string),
+                primitives: mesh
+                    .primitives()
+                    .filter_map(|primitive| read_primitive(primitive, &buffers))
+                    .collect(),
+            })
+            .collect(),
+        materials: document.materials().map(read_material).collect(),
+        textures: document
+            .images()
+            .zip(images)
+            .map(|(image, data)| GltfTexture {
+                name: image.name().map(str::to_string),
+                width: data.width,
+                height: data.height,
+                pixels: to_rgba8(data),
+            })
+            .collect(),
+        nodes: document
+            .nodes()
+            .map(|node| GltfNode {
+                name: node.name().map(str::to_string),
+                // glTF matrices are column-major
+                transform: transpose(node.transform().matrix()),
+                mesh: node.mesh().map(|mesh| mesh.index()),
+                children: node.children().map(|child| child.index()).collect(),
+            })
+            .collect(),
+        scenes: document
+            .scenes()
+            .map(|scene| GltfScene {
+                name: scene.name().map(str::to_string),
+                nodes: scene.nodes().map(|node| node.index()).collect(),
+            })
+            .collect(),
+        default_scene: document.default_scene().map(|scene| scene.index()),
+    }
+}
+
+// A `.glb` or a `.gltf` whose buffers and images are embedded as data URIs,
+// e.g. fetched in the browser
+pub fn parse_gltf(bytes: &[u8]) -> Result<Gltf, GltfError> {
+    let (document, buffers, images) = ::gltf::import_slice(bytes)?;
+    Ok(convert(document, buffers, images))
+}
+
+// Also resolves buffers and images next to the file
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_gltf(path: impl AsRef<std::path::Path>) -> Result<Gltf, GltfError> {
+    let (document, buffers, images) = ::gltf::import(path)?;
+    Ok(convert(document, buffers, images))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A quad as a triangle strip without normals, under a translated parent
+    const SCENE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "buffers": [{
+            "byteLength": 56,
+            "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAACAPwAAgD8AAAAAAAABAAIAAwA="
+        }],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 48 },
+            { "buffer": 0, "byteOffset": 48, "byteLength": 8 }
+        ],
+        "accessors": [
+            {
+                "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+                "min": [0, 0, 0], "max": [1, 1, 0]
+            },
+            { "bufferView": 1, "componentType": 5123, "count": 4, "type": "SCALAR" }
+        ],
+        "materials": [{ "pbrMetallicRoughness": { "baseColorFactor": [1, 0.5, 0, 1] } }],
+        "meshes": [{
+            "name": "quad",
+            "primitives": [{
+                "attributes": { "POSITION": 0 }, "indices": 1, "mode": 5, "material": 0
+            }]
+        }],
+        "nodes": [
+            { "name": "parent", "translation": [1, 2, 3], "children": [1] },
+            { "name": "child", "scale": [2, 2, 2], "mesh": 0 }
+        ],
+        "scenes": [{ "nodes": [0] }],
+        "scene": 0
+    }"#;
+
+    #[test]
+    fn strip_without_normals() {
+        let gltf = parse_gltf(SCENE.as_bytes()).unwrap();
+        let primitive = &gltf.meshes[0].primitives[0];
+
+        assert_eq!(primitive.topology, PrimitiveTopology::Triangles);
+        assert_eq!(primitive.mesh.vertices.len(), 6);
+        assert_eq!(primitive.mesh.indices.len(), 6);
+        assert!(primitive
+            .mesh
+            .vertices
+            .iter()
+            .all(|vertex| vertex.normal == [0.0, 0.0, 1.0]));
+
+        let material = primitive.material.map(|material| &gltf.materials[material]);
+        assert_eq!(material.unwrap().base_color, [1.0, 0.5, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn world_transforms() {
+        let gltf = parse_gltf(SCENE.as_bytes()).unwrap();
+        let transforms = gltf.world_transforms(0);
+
+        assert_eq!(
+            transforms.iter().map(|&(node, _)| node).collect::<Vec<_>>(),
+            [0, 1]
+        );
+        assert_eq!(
+            transforms[1].1,
+            [
+                [2.0, 0.0, 0.0, 1.0],
+                [0.0, 2.0, 0.0, 2.0],
+                [0.0, 0.0, 2.0, 3.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ]
+        );
+    }
+
+    #[test]
+    fn fans_and_loops() {
+        let (topology_fan, fan) = topology(Mode::TriangleFan, vec![0, 1, 2, 3]);
+        assert_eq!(topology_fan, PrimitiveTopology::Triangles);
+        assert_eq!(fan, [0, 1, 2, 0, 2, 3]);
+
+        let (topology_loop, line_loop) = topology(Mode::LineLoop, vec![0, 1, 2]);
+        assert_eq!(topology_loop, PrimitiveTopology::LineStrip);
+        assert_eq!(line_loop, [0, 1, 2, 0]);
+    }
+}
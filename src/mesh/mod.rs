@@ -0,0 +1,70 @@
+mod format;
+#[cfg(feature = "gltf")]
+mod gltf;
+mod obj;
+mod ply;
+mod primitives;
+mod processing;
+mod simplify;
+mod stl;
+
+#[cfg(all(feature = "gltf", not(target_arch = "wasm32")))]
+pub use self::gltf::load_gltf;
+#[cfg(feature = "gltf")]
+pub use self::gltf::{
+    parse_gltf, AlphaMode, Gltf, GltfError, GltfMaterial, GltfMesh, GltfNode, GltfPrimitive,
+    GltfScene, GltfTexture,
+};
+pub use format::FormatError;
+#[cfg(not(target_arch = "wasm32"))]
+pub use obj::load_obj;
+pub use obj::{parse_mtl, parse_obj, Obj, ObjError, ObjGroup, ObjMaterial};
+#[cfg(not(target_arch = "wasm32"))]
+pub use ply::load_ply;
+pub use ply::{parse_ply, write_ply, Ply, PlyFormat};
+pub use primitives::{capsule, cone, cube, cylinder, icosphere, plane, torus, uv_sphere};
+pub use processing::{optimize_vertex_cache, weld};
+#[cfg(not(target_arch = "wasm32"))]
+pub use stl::load_stl;
+pub use stl::{parse_stl, write_stl, write_stl_ascii};
+
+use crate::{
+    common::{ShaderProgram, Vec3},
+    entity::Entity,
+    Vertex,
+};
+
+// Locations line up with the bundled shaders, 1 is the color slot of
+// phong_light_object
+#[derive(Debug, Clone, Copy, PartialEq, Vertex)]
+#[repr(C)]
+pub struct MeshVertex {
+    #[location(0)]
+    pub position: Vec3,
+    #[location(2)]
+    pub normal: Vec3,
+    #[location(3)]
+    pub uv: [f32; 2],
+}
+
+// Indexed triangles, counter-clockwise when looked at from the outside
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn to_entity(&self, shader: Option<ShaderProgram>) -> Entity {
+        Entity::new_indexed(&self.vertices, self.indices.clone(), shader)
+    }
+
+    fn push_vertex(&mut self, position: Vec3, normal: Vec3, uv: [f32; 2]) -> u32 {
+        self.vertices.push(MeshVertex {
+            position,
+            normal,
+            uv,
+        });
+        (self.vertices.len() - 1) as u32
+    }
+}
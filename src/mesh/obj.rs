@@ -0,0 +1,490 @@
+use super::{Mesh, MeshVertex};
+use crate::{
+    common::{Shader, ShaderProgram, Vec3},
+    entity::Entity,
+    math::{vec3_add, vec3_cross, vec3_dot, vec3_sub},
+};
+use std::{collections::HashMap, fmt, str::SplitWhitespace};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(error) => write!(f, "{error}"),
+            ObjError::Parse { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl From<std::io::Error> for ObjError {
+    fn from(error: std::io::Error) -> Self {
+        ObjError::Io(error)
+    }
+}
+
+fn parse_error(line: usize, message: impl Into<String>) -> ObjError {
+    ObjError::Parse {
+        line,
+        message: message.into(),
+    }
+}
+
+// The subset of MTL that maps onto the bundled shaders
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjMaterial {
+    pub name: String,
+    pub ambient: Vec3,
+    pub diffuse: Vec3,
+    pub specular: Vec3,
+    pub shininess: f32,
+    pub opacity: f32,
+    pub diffuse_map: Option<String>,
+}
+
+impl ObjMaterial {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ambient: [0.0; 3],
+            diffuse: [0.8; 3],
+            specular: [0.0; 3],
+            shininess: 0.0,
+            opacity: 1.0,
+            diffuse_map: None,
+        }
+    }
+}
+
+// The faces of one `g`/`o` with one `usemtl`
+#[derive(Debug, Clone)]
+pub struct ObjGroup {
+    pub name: String,
+    pub material: Option<String>,
+    pub mesh: Mesh,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Obj {
+    pub groups: Vec<ObjGroup>,
+    // `mtllib` file names, relative to the OBJ file
+    pub material_libraries: Vec<String>,
+    pub materials: Vec<ObjMaterial>,
+}
+
+impl Obj {
+    pub fn material(&self, name: &str) -> Option<&ObjMaterial> {
+        self.materials.iter().find(|material| material.name == name)
+    }
+
+    // One entity per group with a phong_light_object shader whose `u_color`
+    // is the diffuse color of the group's material. The light and camera
+    // uniforms are left to the caller.
+    pub fn to_entities(&self) -> Vec<Entity> {
+        self.groups
+            .iter()
+            .map(|group| {
+                let diffuse = group
+                    .material
+                    .as_deref()
+                    .and_then(|name| self.material(name))
+                    .map_or([0.8; 3], |material| material.diffuse);
+
+                let shader = ShaderProgram::new("phong_light_object");
+                shader.set_uniform("u_color", diffuse).unwrap();
+
+                group.mesh.to_entity(Some(shader))
+            })
+            .collect()
+    }
+}
+
+fn parse_floats<const N: usize>(
+    parts: SplitWhitespace,
+    line: usize,
+    required: usize,
+) -> Result<[f32; N], ObjError> {
+    let mut values = [0.0; N];
+    let mut count = 0;
+
+    for (value, part) in values.iter_mut().zip(parts) {
+        *value = part
+            .parse()
+            .map_err(|_| parse_error(line, format!("invalid number `{part}`")))?;
+        count += 1;
+    }
+
+    if count < required {
+        return Err(parse_error(line, format!("expected {required} numbers")));
+    }
+    Ok(values)
+}
+
+// OBJ indices start at 1, negative ones count back from the last element
+fn resolve_index(index: &str, count: usize, line: usize) -> Result<usize, ObjError> {
+    let value: i64 = index
+        .parse()
+        .map_err(|_| parse_error(line, format!("invalid index `{index}`")))?;
+
+    let resolved = if value < 0 {
+        count as i64 + value
+    } else {
+        value - 1
+    };
+
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(parse_error(line, format!("index {value} is out of range")));
+    }
+    Ok(resolved as usize)
+}
+
+// Ear clipping in the plane of the polygon, so concave faces work too. Falls
+// back to a fan for degenerate polygons.
+fn triangulate(points: &[Vec3]) -> Vec<[usize; 3]> {
+    let mut normal = [0.0; 3];
+    for (i, &point) in points.iter().enumerate() {
+        normal = vec3_add(normal, vec3_cross(point, points[(i + 1) % points.len()]));
+    }
+
+    let is_left = |a: Vec3, b: Vec3, p: Vec3| {
+        vec3_dot(vec3_cross(vec3_sub(b, a), vec3_sub(p, a)), normal) >= 0.0
+    };
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let [previous, current, next] =
+                [(i + count - 1) % count, i, (i + 1) % count].map(|i| remaining[i]);
+            let [a, b, c] = [previous, current, next].map(|i| points[i]);
+
+            let convex = vec3_dot(vec3_cross(vec3_sub(b, a), vec3_sub(c, b)), normal) > 0.0;
+            convex
+                && remaining
+                    .iter()
+                    .filter(|&&other| ![previous, current, next].contains(&other))
+                    .all(|&other| {
+                        let p = points[other];
+                        !(is_left(a, b, p) && is_left(b, c, p) && is_left(c, a, p))
+                    })
+        });
+
+        match ear {
+            Some(i) => {
+                triangles.push([
+                    remaining[(i + count - 1) % count],
+                    remaining[i],
+                    remaining[(i + 1) % count],
+                ]);
+                remaining.remove(i);
+            }
+            None => break,
+        }
+    }
+
+    for i in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+    triangles
+}
+
+struct GroupBuilder {
+    group: ObjGroup,
+    lookup: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+    missing_normals: bool,
+}
+
+impl GroupBuilder {
+    fn new(name: &str, material: Option<String>) -> Self {
+        Self {
+            group: ObjGroup {
+                name: name.to_string(),
+                material,
+                mesh: Mesh::default(),
+            },
+            lookup: HashMap::new(),
+            missing_normals: false,
+        }
+    }
+
+    fn finish(mut self) -> Option<ObjGroup> {
+        if self.group.mesh.indices.is_empty() {
+            return None;
+        }
+
+        // Corners without a `vn` get smooth normals from the group's faces
+        if self.missing_normals {
+            let mut smoothed = self.group.mesh.clone();
+            smoothed.compute_smooth_normals();
+
+            for (vertex, smoothed) in self.group.mesh.vertices.iter_mut().zip(smoothed.vertices) {
+                if vertex.normal == [0.0; 3] {
+                    vertex.normal = smoothed.normal;
+                }
+            }
+        }
+
+        Some(self.group)
+    }
+}
+
+pub fn parse_obj(source: &str) -> Result<Obj, ObjError> {
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+
+    let mut obj = Obj::default();
+    let mut name = String::from("default");
+    let mut builder = GroupBuilder::new(&name, None);
+
+    for (number, line) in source.lines().enumerate() {
+        let number = number + 1;
+        let line = line.split('#').next().unwrap_or_default();
+        let mut parts = line.split_whitespace();
+
+        let Some(keyword) = parts.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => positions.push(parse_floats(parts, number, 3)?),
+            "vt" => uvs.push(parse_floats(parts, number, 1)?),
+            "vn" => normals.push(parse_floats(parts, number, 3)?),
+            "f" => {
+                let corners = parts
+                    .map(|corner| {
+                        let mut indices = corner.split('/');
+                        let position = resolve_index(
+                            indices.next().unwrap_or_default(),
+                            positions.len(),
+                            number,
+                        )?;
+                        let uv = match indices.next() {
+                            Some(index) if !index.is_empty() => {
+                                Some(resolve_index(index, uvs.len(), number)?)
+                            }
+                            _ => None,
+                        };
+                        let normal = match indices.next() {
+                            Some(index) if !index.is_empty() => {
+                                Some(resolve_index(index, normals.len(), number)?)
+                            }
+                            _ => None,
+                        };
+                        Ok((position, uv, normal))
+                    })
+                    .collect::<Result<Vec<_>, ObjError>>()?;
+
+                if corners.len() < 3 {
+                    return Err(parse_error(number, "a face needs at least 3 vertices"));
+                }
+
+                let indices = corners
+                    .iter()
+                    .map(|&key @ (position, uv, normal)| {
+                        let mesh = &mut builder.group.mesh;
+                        builder.missing_normals |= normal.is_none();
+
+                        *builder.lookup.entry(key).or_insert_with(|| {
+                            mesh.vertices.push(MeshVertex {
+                                position: positions[position],
+                                normal: normal.map_or([0.0; 3], |normal| normals[normal]),
+                                uv: uv.map_or([0.0; 2], |uv| uvs[uv]),
+                            });
+                            (mesh.vertices.len() - 1) as u32
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                let points: Vec<Vec3> = corners
+                    .iter()
+                    .map(|&(position, _, _)| positions[position])
+                    .collect();
+                for triangle in triangulate(&points) {
+                    builder
+                        .group
+                        .mesh
+                        .indices
+                        .extend(triangle.map(|corner| indices[corner]));
+                }
+            }
+            "g" | "o" => {
+                name = parts.collect::<Vec<_>>().join(" ");
+                let material = builder.group.material.clone();
+                obj.groups.extend(
+                    std::mem::replace(&mut builder, GroupBuilder::new(&name, material)).finish(),
+                );
+            }
+            "usemtl" => {
+                let material = parts.next().map(str::to_string);
+                obj.groups.extend(
+                    std::mem::replace(&mut builder, GroupBuilder::new(&name, material)).finish(),
+                );
+            }
+            "mtllib" => obj.material_libraries.extend(parts.map(str::to_string)),
+            // Smoothing groups, lines, points, curves
+            _ => {}
+        }
+    }
+
+    obj.groups.extend(builder.finish());
+    Ok(obj)
+}
+
+pub fn parse_mtl(source: &str) -> Result<Vec<ObjMaterial>, ObjError> {
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let number = number + 1;
+        let line = line.split('#').next().unwrap_or_default();
+        let mut parts = line.split_whitespace();
+
+        let Some(keyword) = parts.next() else {
+            continue;
+        };
+
+        if keyword == "newmtl" {
+            let name = parts.collect::<Vec<_>>().join(" ");
+            materials.push(ObjMaterial::new(&name));
+            continue;
+        }
+
+        let Some(material) = materials.last_mut() else {
+            return Err(parse_error(number, "expected `newmtl`"));
+        };
+
+        match keyword {
+            "Ka" => material.ambient = parse_floats(parts, number, 3)?,
+            "Kd" => material.diffuse = parse_floats(parts, number, 3)?,
+            "Ks" => material.specular = parse_floats(parts, number, 3)?,
+            "Ns" => material.shininess = parse_floats::<1>(parts, number, 1)?[0],
+            "d" => material.opacity = parse_floats::<1>(parts, number, 1)?[0],
+            "Tr" => material.opacity = 1.0 - parse_floats::<1>(parts, number, 1)?[0],
+            // Options like `-s 1 1 1` come before the file name
+            "map_Kd" => material.diffuse_map = parts.last().map(str::to_string),
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+// Reads an OBJ file and the MTL files it refers to. In the browser the files
+// have to be fetched first, then passed to `parse_obj` and `parse_mtl`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_obj(path: impl AsRef<std::path::Path>) -> Result<Obj, ObjError> {
+    let path = path.as_ref();
+    let mut obj = parse_obj(&std::fs::read_to_string(path)?)?;
+
+    for library in &obj.material_libraries {
+        let library = path.with_file_name(library);
+        obj.materials
+            .extend(parse_mtl(&std::fs::read_to_string(library)?)?);
+    }
+
+    Ok(obj)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangles(mesh: &Mesh) -> Vec<[Vec3; 3]> {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize].position))
+            .collect()
+    }
+
+    // Twice the signed area in the XY plane, positive for counterclockwise
+    fn area([a, b, c]: [Vec3; 3]) -> f32 {
+        vec3_cross(vec3_sub(b, a), vec3_sub(c, a))[2]
+    }
+
+    #[test]
+    fn concave_face() {
+        // An arrow pointing down, a fan from the first corner would cover
+        // the notch at (2, 1)
+        let source = "
+            v 0 0 0
+            v 4 0 0
+            v 4 4 0
+            v 2 1 0
+            v 0 4 0
+            f 2 3 4 5 1
+        ";
+        let obj = parse_obj(source).unwrap();
+        let triangles = triangles(&obj.groups[0].mesh);
+
+        assert_eq!(triangles.len(), 3);
+        assert!(triangles.iter().all(|&triangle| area(triangle) > 0.0));
+        let total: f32 = triangles.into_iter().map(area).sum();
+        assert_eq!(total, 2.0 * 10.0);
+    }
+
+    #[test]
+    fn shared_corners_and_missing_normals() {
+        let source = "
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            f -4/1 -3/1 -2/1
+            f 1/1 3/1 4/1
+        ";
+        let obj = parse_obj(source).unwrap();
+        let mesh = &obj.groups[0].mesh;
+
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3]);
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|vertex| vertex.normal == [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn groups_and_materials() {
+        let source = "
+            mtllib scene.mtl
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            o first
+            usemtl red
+            f 1 2 3
+            o second
+            f 1 3 2
+        ";
+        let obj = parse_obj(source).unwrap();
+
+        assert_eq!(obj.material_libraries, ["scene.mtl"]);
+        let groups: Vec<_> = obj
+            .groups
+            .iter()
+            .map(|group| (group.name.as_str(), group.material.as_deref()))
+            .collect();
+        assert_eq!(groups, [("first", Some("red")), ("second", Some("red"))]);
+
+        let materials = parse_mtl("newmtl red\nKd 1 0 0\nd 0.5\nmap_Kd -s 1 1 1 red.png").unwrap();
+        assert_eq!(materials[0].diffuse, [1.0, 0.0, 0.0]);
+        assert_eq!(materials[0].opacity, 0.5);
+        assert_eq!(materials[0].diffuse_map.as_deref(), Some("red.png"));
+    }
+
+    #[test]
+    fn invalid_faces() {
+        assert!(parse_obj("v 0 0 0\nv 1 0 0\nf 1 2").is_err());
+        assert!(parse_obj("v 0 0 0\nv 1 0 0\nf 1 2 3").is_err());
+        assert!(parse_obj("v 0 0 0\nv 1 0 0\nf 1 2 -3").is_err());
+        assert!(parse_mtl("Kd 1 0 0").is_err());
+    }
+}
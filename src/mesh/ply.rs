@@ -0,0 +1,561 @@
+use super::{
+    format::{invalid, positions, FormatError},
+    Mesh, MeshVertex,
+};
+use crate::{
+    common::{ShaderProgram, Vec3, VERTEX_ATTRIBUTE_FVEC4},
+    entity::{Entity, VertexBuffer},
+    types::PrimitiveTopology,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlyType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Result<PlyType, FormatError> {
+        Ok(match name {
+            "char" | "int8" => PlyType::I8,
+            "uchar" | "uint8" => PlyType::U8,
+            "short" | "int16" => PlyType::I16,
+            "ushort" | "uint16" => PlyType::U16,
+            "int" | "int32" => PlyType::I32,
+            "uint" | "uint32" => PlyType::U32,
+            "float" | "float32" => PlyType::F32,
+            "double" | "float64" => PlyType::F64,
+            _ => return Err(invalid(format!("unknown PLY type `{name}`"))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8,
+        }
+    }
+
+    // Colors are stored as integers from 0 to the type's maximum or as floats
+    // from 0 to 1
+    fn normalize(self, value: f64) -> f32 {
+        let max = match self {
+            PlyType::I8 => i8::MAX as f64,
+            PlyType::U8 => u8::MAX as f64,
+            PlyType::I16 => i16::MAX as f64,
+            PlyType::U16 => u16::MAX as f64,
+            PlyType::I32 => i32::MAX as f64,
+            PlyType::U32 => u32::MAX as f64,
+            PlyType::F32 | PlyType::F64 => 1.0,
+        };
+        (value / max) as f32
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(PlyType),
+    // Count type, item type
+    List(PlyType, PlyType),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<(String, Property)>,
+}
+
+// The data after the header, read one value at a time
+enum Body<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary { bytes: &'a [u8], big_endian: bool },
+}
+
+impl Body<'_> {
+    fn read(&mut self, ply_type: PlyType) -> Result<f64, FormatError> {
+        match self {
+            Body::Ascii(tokens) => {
+                let token = tokens
+                    .next()
+                    .ok_or_else(|| invalid("unexpected end of the PLY file"))?;
+                token
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid number `{token}` in the PLY file")))
+            }
+            Body::Binary { bytes, big_endian } => {
+                let size = ply_type.size();
+                if bytes.len() < size {
+                    return Err(invalid("unexpected end of the PLY file"));
+                }
+                let (value, rest) = bytes.split_at(size);
+                *bytes = rest;
+
+                macro_rules! number {
+                    ($type:ty) => {{
+                        let value = value.try_into().unwrap();
+                        (if *big_endian {
+                            <$type>::from_be_bytes(value)
+                        } else {
+                            <$type>::from_le_bytes(value)
+                        }) as f64
+                    }};
+                }
+
+                Ok(match ply_type {
+                    PlyType::I8 => number!(i8),
+                    PlyType::U8 => number!(u8),
+                    PlyType::I16 => number!(i16),
+                    PlyType::U16 => number!(u16),
+                    PlyType::I32 => number!(i32),
+                    PlyType::U32 => number!(u32),
+                    PlyType::F32 => number!(f32),
+                    PlyType::F64 => number!(f64),
+                })
+            }
+        }
+    }
+}
+
+// Vertices with their faces, or a point cloud when `mesh.indices` is empty
+#[derive(Debug, Clone, Default)]
+pub struct Ply {
+    pub mesh: Mesh,
+    pub colors: Option<Vec<[f32; 4]>>,
+}
+
+impl Ply {
+    pub fn is_point_cloud(&self) -> bool {
+        self.mesh.indices.is_empty()
+    }
+
+    // The `MeshVertex` attributes, and the colors at location 5 when present.
+    // Point clouds are drawn as points.
+    pub fn to_entity(&self, shader: Option<ShaderProgram>) -> Entity {
+        let mut entity = if self.is_point_cloud() {
+            Entity::new(&self.mesh.vertices, shader).with_topology(PrimitiveTopology::Points)
+        } else {
+            self.mesh.to_entity(shader)
+        };
+
+        if let Some(colors) = &self.colors {
+            entity.push_vertex_buffer(VertexBuffer::new(
+                colors,
+                vec![None, Some(VERTEX_ATTRIBUTE_FVEC4)],
+            ));
+        }
+
+        entity
+    }
+}
+
+fn parse_header(header: &str) -> Result<(PlyFormat, Vec<Element>), FormatError> {
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err(invalid("not a PLY file"));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    for line in lines {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+
+        match parts.as_slice() {
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => PlyFormat::Ascii,
+                    "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                    "binary_big_endian" => PlyFormat::BinaryBigEndian,
+                    _ => return Err(invalid(format!("unknown PLY format `{name}`"))),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| invalid(format!("invalid element count `{count}`")))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_type, item_type, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid("property before the first element"))?
+                .properties
+                .push((
+                    name.to_string(),
+                    Property::List(PlyType::parse(count_type)?, PlyType::parse(item_type)?),
+                )),
+            ["property", property_type, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid("property before the first element"))?
+                .properties
+                .push((
+                    name.to_string(),
+                    Property::Scalar(PlyType::parse(property_type)?),
+                )),
+            _ => {}
+        }
+    }
+
+    Ok((
+        format.ok_or_else(|| invalid("missing PLY format"))?,
+        elements,
+    ))
+}
+
+// ASCII or binary PLY. Vertices may have normals (`nx`, `ny`, `nz`), UVs
+// (`u`/`v`, `s`/`t` or `texture_u`/`texture_v`) and colors (`red`, `green`,
+// `blue`, `alpha`). Faces are triangulated as fans, faces without normals
+// get smooth ones.
+pub fn parse_ply(bytes: &[u8]) -> Result<Ply, FormatError> {
+    const END_HEADER: &[u8] = b"end_header";
+    let end = bytes
+        .windows(END_HEADER.len())
+        .position(|window| window == END_HEADER)
+        .ok_or_else(|| invalid("missing end_header"))?;
+    let header =
+        std::str::from_utf8(&bytes[..end]).map_err(|_| invalid("the PLY header isn't UTF-8"))?;
+
+    // The body starts after the line break following end_header
+    let body_start = bytes[end..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map_or(bytes.len(), |newline| end + newline + 1);
+
+    let (format, elements) = parse_header(header)?;
+    let mut body = match format {
+        PlyFormat::Ascii => Body::Ascii(
+            std::str::from_utf8(&bytes[body_start..])
+                .map_err(|_| invalid("the ASCII PLY data isn't UTF-8"))?
+                .split_ascii_whitespace(),
+        ),
+        PlyFormat::BinaryLittleEndian | PlyFormat::BinaryBigEndian => Body::Binary {
+            bytes: &bytes[body_start..],
+            big_endian: format == PlyFormat::BinaryBigEndian,
+        },
+    };
+
+    let mut ply = Ply::default();
+    let mut has_normals = false;
+
+    for element in &elements {
+        let find = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|(name, _)| names.contains(&name.as_str()))
+        };
+
+        let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+        let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+        let uv = [
+            find(&["u", "s", "texture_u"]),
+            find(&["v", "t", "texture_v"]),
+        ];
+        let color = [
+            find(&["red"]),
+            find(&["green"]),
+            find(&["blue"]),
+            find(&["alpha"]),
+        ];
+        let face = find(&["vertex_indices", "vertex_index"]);
+
+        // Counts come from the header, every element takes a byte of the
+        // body at least, so they can't ask for more than that
+        if element.count > 0 && element.properties.is_empty() {
+            return Err(invalid(format!(
+                "element {} has no properties",
+                element.name
+            )));
+        }
+        let capacity = element.count.min(bytes.len() - body_start);
+
+        if element.name == "vertex" {
+            has_normals = normal.iter().all(Option::is_some);
+            if color[..3].iter().all(Option::is_some) {
+                ply.colors = Some(Vec::with_capacity(capacity));
+            }
+        }
+
+        for _ in 0..element.count {
+            let mut scalars = vec![0.0; element.properties.len()];
+            let mut lists = vec![Vec::new(); element.properties.len()];
+
+            for (i, (_, property)) in element.properties.iter().enumerate() {
+                match property {
+                    Property::Scalar(ply_type) => scalars[i] = body.read(*ply_type)?,
+                    Property::List(count_type, item_type) => {
+                        let count = body.read(*count_type)? as usize;
+                        lists[i] = (0..count)
+                            .map(|_| body.read(*item_type))
+                            .collect::<Result<Vec<_>, _>>()?;
+                    }
+                }
+            }
+
+            match element.name.as_str() {
+                "vertex" => {
+                    let value = |index: Option<usize>| index.map_or(0.0, |i| scalars[i] as f32);
+
+                    ply.mesh.vertices.push(MeshVertex {
+                        position: position.map(value),
+                        normal: normal.map(value),
+                        uv: uv.map(value),
+                    });
+
+                    if let Some(colors) = &mut ply.colors {
+                        colors.push(color.map(|index| match index {
+                            Some(i) => match &element.properties[i].1 {
+                                Property::Scalar(ply_type) => ply_type.normalize(scalars[i]),
+                                Property::List(..) => 1.0,
+                            },
+                            None => 1.0,
+                        }));
+                    }
+                }
+                "face" => {
+                    let corners = face.map(|i| lists[i].as_slice()).unwrap_or_default();
+                    for i in 1..corners.len().saturating_sub(1) {
+                        ply.mesh.indices.extend(
+                            [corners[0], corners[i], corners[i + 1]].map(|index| index as u32),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(&index) = ply
+        .mesh
+        .indices
+        .iter()
+        .find(|&&index| index as usize >= ply.mesh.vertices.len())
+    {
+        return Err(invalid(format!("face index {index} is out of range")));
+    }
+
+    if !has_normals && !ply.is_point_cloud() {
+        ply.mesh.compute_smooth_normals();
+    }
+
+    Ok(ply)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_ply(path: impl AsRef<std::path::Path>) -> Result<Ply, FormatError> {
+    parse_ply(&std::fs::read(path)?)
+}
+
+enum Value {
+    F32(f32),
+    U8(u8),
+    U32(u32),
+}
+
+fn write_value(output: &mut Vec<u8>, format: PlyFormat, value: Value) {
+    match format {
+        PlyFormat::Ascii => {
+            let text = match value {
+                Value::F32(value) => value.to_string(),
+                Value::U8(value) => value.to_string(),
+                Value::U32(value) => value.to_string(),
+            };
+            output.extend(text.as_bytes());
+            output.push(b' ');
+        }
+        PlyFormat::BinaryLittleEndian => match value {
+            Value::F32(value) => output.extend(value.to_le_bytes()),
+            Value::U8(value) => output.push(value),
+            Value::U32(value) => output.extend(value.to_le_bytes()),
+        },
+        PlyFormat::BinaryBigEndian => match value {
+            Value::F32(value) => output.extend(value.to_be_bytes()),
+            Value::U8(value) => output.push(value),
+            Value::U32(value) => output.extend(value.to_be_bytes()),
+        },
+    }
+}
+
+// Positions, and normals, UVs and colors when the entity has them at the
+// `MeshVertex` locations, with the entity's triangles as faces
+pub fn write_ply(entity: &Entity, format: PlyFormat) -> Vec<u8> {
+    write(
+        format,
+        &positions(entity),
+        entity.read_attribute(2).as_deref(),
+        entity.read_attribute(3).as_deref(),
+        entity.read_attribute(5).as_deref(),
+        &entity.triangle_indices(),
+    )
+}
+
+fn write(
+    format: PlyFormat,
+    positions: &[Vec3],
+    normals: Option<&[[f32; 4]]>,
+    uvs: Option<&[[f32; 4]]>,
+    colors: Option<&[[f32; 4]]>,
+    indices: &[u32],
+) -> Vec<u8> {
+    let format_name = match format {
+        PlyFormat::Ascii => "ascii",
+        PlyFormat::BinaryLittleEndian => "binary_little_endian",
+        PlyFormat::BinaryBigEndian => "binary_big_endian",
+    };
+
+    let mut header = format!(
+        "ply\nformat {format_name} 1.0\ncomment reindeer\nelement vertex {}\n",
+        positions.len()
+    );
+    header += "property float x\nproperty float y\nproperty float z\n";
+    if normals.is_some() {
+        header += "property float nx\nproperty float ny\nproperty float nz\n";
+    }
+    if uvs.is_some() {
+        header += "property float u\nproperty float v\n";
+    }
+    if colors.is_some() {
+        header +=
+            "property uchar red\nproperty uchar green\nproperty uchar blue\nproperty uchar alpha\n";
+    }
+    header += &format!(
+        "element face {}\nproperty list uchar uint vertex_indices\nend_header\n",
+        indices.len() / 3
+    );
+
+    let mut output = header.into_bytes();
+    let end_line = |output: &mut Vec<u8>| {
+        if format == PlyFormat::Ascii {
+            output.pop();
+            output.push(b'\n');
+        }
+    };
+
+    for (i, position) in positions.iter().enumerate() {
+        let mut floats = position.to_vec();
+        if let Some(normals) = normals {
+            floats.extend(&normals[i][..3]);
+        }
+        if let Some(uvs) = uvs {
+            floats.extend(&uvs[i][..2]);
+        }
+        for value in floats {
+            write_value(&mut output, format, Value::F32(value));
+        }
+
+        if let Some(colors) = colors {
+            for component in colors[i] {
+                let component = (component.clamp(0.0, 1.0) * 255.0).round() as u8;
+                write_value(&mut output, format, Value::U8(component));
+            }
+        }
+        end_line(&mut output);
+    }
+
+    for triangle in indices.chunks_exact(3) {
+        write_value(&mut output, format, Value::U8(3));
+        for &index in triangle {
+            write_value(&mut output, format, Value::U32(index));
+        }
+        end_line(&mut output);
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POSITIONS: [Vec3; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.5],
+        [0.0, 1.0, 0.25],
+    ];
+    const NORMALS: [[f32; 4]; 4] = [[0.0, 0.0, 1.0, 0.0]; 4];
+    const UVS: [[f32; 4]; 4] = [
+        [0.0, 0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+    ];
+    const COLORS: [[f32; 4]; 4] = [
+        [1.0, 0.0, 0.0, 1.0],
+        [0.0, 1.0, 0.0, 1.0],
+        [0.0, 0.0, 1.0, 1.0],
+        [1.0, 1.0, 1.0, 0.0],
+    ];
+    const INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+    #[test]
+    fn round_trip() {
+        for format in [
+            PlyFormat::Ascii,
+            PlyFormat::BinaryLittleEndian,
+            PlyFormat::BinaryBigEndian,
+        ] {
+            let bytes = write(
+                format,
+                &POSITIONS,
+                Some(&NORMALS),
+                Some(&UVS),
+                Some(&COLORS),
+                &INDICES,
+            );
+            let ply = parse_ply(&bytes).unwrap();
+
+            assert_eq!(ply.mesh.indices, INDICES);
+            assert_eq!(ply.colors.as_deref(), Some(&COLORS[..]));
+            for (i, vertex) in ply.mesh.vertices.iter().enumerate() {
+                assert_eq!(vertex.position, POSITIONS[i]);
+                assert_eq!(vertex.normal, NORMALS[i][..3]);
+                assert_eq!(vertex.uv, UVS[i][..2]);
+            }
+        }
+    }
+
+    #[test]
+    fn point_cloud() {
+        let bytes = write(PlyFormat::Ascii, &POSITIONS, None, None, None, &[]);
+        let ply = parse_ply(&bytes).unwrap();
+
+        assert!(ply.is_point_cloud());
+        assert_eq!(ply.mesh.vertices.len(), POSITIONS.len());
+        assert!(ply.colors.is_none());
+    }
+
+    #[test]
+    fn counts_past_the_end() {
+        let header = "ply\nformat binary_little_endian 1.0\nelement vertex 4000000000\n\
+            property float x\nproperty float y\nproperty float z\nend_header\n";
+        let mut bytes = header.as_bytes().to_vec();
+        bytes.extend([0; 12]);
+        assert!(parse_ply(&bytes).is_err());
+
+        let header = "ply\nformat ascii 1.0\nelement vertex 1000000\nend_header\n";
+        assert!(parse_ply(header.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn face_index_out_of_range() {
+        let bytes = write(PlyFormat::Ascii, &POSITIONS, None, None, None, &[0, 1, 4]);
+        assert!(parse_ply(&bytes).is_err());
+    }
+}
@@ -0,0 +1,431 @@
+use super::Mesh;
+use crate::{
+    common::Vec3,
+    math::{vec3_add, vec3_normalize, vec3_scale},
+};
+use std::{
+    collections::HashMap,
+    f32::consts::{FRAC_PI_2, PI, TAU},
+};
+
+// A point of a profile that is revolved around the Y axis: distance from the
+// axis, height and the normal in the same plane
+struct ProfilePoint {
+    radius: f32,
+    y: f32,
+    normal: [f32; 2],
+}
+
+// Profiles go from the top to the bottom along the outside of the surface.
+// Rings on the axis (radius exactly 0) are only used by the triangles that
+// aren't degenerate.
+fn revolve(mesh: &mut Mesh, profile: &[ProfilePoint], segments: u32) {
+    let first = mesh.vertices.len() as u32;
+    let ring_size = segments + 1;
+
+    for (i, point) in profile.iter().enumerate() {
+        let v = 1.0 - i as f32 / (profile.len() - 1) as f32;
+
+        for j in 0..ring_size {
+            let u = j as f32 / segments as f32;
+            let (sin, cos) = (u * TAU).sin_cos();
+
+            mesh.push_vertex(
+                [point.radius * sin, point.y, point.radius * cos],
+                vec3_normalize([
+                    point.normal[0] * sin,
+                    point.normal[1],
+                    point.normal[0] * cos,
+                ]),
+                [u, v],
+            );
+        }
+    }
+
+    for i in 0..profile.len() as u32 - 1 {
+        for j in 0..segments {
+            let top = first + i * ring_size + j;
+            let bottom = top + ring_size;
+
+            if profile[i as usize].radius != 0.0 {
+                mesh.indices.extend([top, bottom, top + 1]);
+            }
+            if profile[i as usize + 1].radius != 0.0 {
+                mesh.indices.extend([top + 1, bottom, bottom + 1]);
+            }
+        }
+    }
+}
+
+// A disc facing up or down, closing a cylinder or cone
+fn cap(mesh: &mut Mesh, radius: f32, y: f32, up: bool, segments: u32) {
+    let normal = [0.0, if up { 1.0 } else { -1.0 }, 0.0];
+    let center = mesh.push_vertex([0.0, y, 0.0], normal, [0.5, 0.5]);
+
+    for j in 0..=segments {
+        let (sin, cos) = (j as f32 / segments as f32 * TAU).sin_cos();
+        mesh.push_vertex(
+            [radius * sin, y, radius * cos],
+            normal,
+            [0.5 + 0.5 * sin, 0.5 + 0.5 * cos],
+        );
+    }
+
+    for j in 0..segments {
+        let (current, next) = (center + 1 + j, center + 2 + j);
+        if up {
+            mesh.indices.extend([center, current, next]);
+        } else {
+            mesh.indices.extend([center, next, current]);
+        }
+    }
+}
+
+// Axis aligned cube centered on the origin, every face has its own vertices
+pub fn cube(size: f32) -> Mesh {
+    // Normal, then the face's u and v directions, u x v = normal
+    let faces: [[Vec3; 3]; 6] = [
+        [[1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]],
+        [[-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]],
+        [[0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]],
+        [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+        [[0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+        [[0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+    ];
+    let half = size / 2.0;
+    let mut mesh = Mesh::default();
+
+    for [normal, u, v] in faces {
+        let first = mesh.vertices.len() as u32;
+        let center = vec3_scale(normal, half);
+
+        for uv in [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]] {
+            let position = vec3_add(
+                center,
+                vec3_add(
+                    vec3_scale(u, (uv[0] * 2.0 - 1.0) * half),
+                    vec3_scale(v, (uv[1] * 2.0 - 1.0) * half),
+                ),
+            );
+            mesh.push_vertex(position, normal, uv);
+        }
+
+        mesh.indices
+            .extend([first, first + 1, first + 2, first + 2, first + 3, first]);
+    }
+
+    mesh
+}
+
+// Plane on the XZ axes facing up, split into `subdivisions` x `subdivisions`
+// quads
+pub fn plane(width: f32, depth: f32, subdivisions: u32) -> Mesh {
+    let subdivisions = subdivisions.max(1);
+    let row_size = subdivisions + 1;
+    let mut mesh = Mesh::default();
+
+    for row in 0..row_size {
+        let v = row as f32 / subdivisions as f32;
+
+        for column in 0..row_size {
+            let u = column as f32 / subdivisions as f32;
+            mesh.push_vertex(
+                [(u - 0.5) * width, 0.0, (v - 0.5) * depth],
+                [0.0, 1.0, 0.0],
+                [u, 1.0 - v],
+            );
+        }
+    }
+
+    for row in 0..subdivisions {
+        for column in 0..subdivisions {
+            let top = row * row_size + column;
+            let bottom = top + row_size;
+            mesh.indices
+                .extend([top, bottom, top + 1, top + 1, bottom, bottom + 1]);
+        }
+    }
+
+    mesh
+}
+
+// `sectors` around the Y axis, `stacks` from pole to pole
+pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> Mesh {
+    let stacks = stacks.max(2);
+    let profile = (0..=stacks)
+        .map(|i| {
+            let (sin, cos) = (i as f32 / stacks as f32 * PI).sin_cos();
+            let sin = if i == 0 || i == stacks { 0.0 } else { sin };
+            ProfilePoint {
+                radius: radius * sin,
+                y: radius * cos,
+                normal: [sin, cos],
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut mesh = Mesh::default();
+    revolve(&mut mesh, &profile, sectors.max(3));
+    mesh
+}
+
+// Subdivided icosahedron with spherical UVs. Vertices on the seam are split
+// so no triangle wraps from u = 1 back to 0.
+pub fn icosphere(radius: f32, subdivisions: u32) -> Mesh {
+    let t = (1.0 + 5f32.sqrt()) / 2.0;
+    let mut positions: Vec<Vec3> = [
+        [-1.0, t, 0.0],
+        [1.0, t, 0.0],
+        [-1.0, -t, 0.0],
+        [1.0, -t, 0.0],
+        [0.0, -1.0, t],
+        [0.0, 1.0, t],
+        [0.0, -1.0, -t],
+        [0.0, 1.0, -t],
+        [t, 0.0, -1.0],
+        [t, 0.0, 1.0],
+        [-t, 0.0, -1.0],
+        [-t, 0.0, 1.0],
+    ]
+    .into_iter()
+    .map(vec3_normalize)
+    .collect();
+
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints = HashMap::<(u32, u32), u32>::new();
+        let mut midpoint = |a: u32, b: u32| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let (a, b) = (positions[a as usize], positions[b as usize]);
+                positions.push(vec3_normalize(vec3_add(a, b)));
+                (positions.len() - 1) as u32
+            })
+        };
+
+        triangles = triangles
+            .into_iter()
+            .flat_map(|[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let mut mesh = Mesh::default();
+    for &normal in &positions {
+        let uv = [
+            (normal[0].atan2(normal[2]) / TAU).rem_euclid(1.0),
+            0.5 + normal[1].asin() / PI,
+        ];
+        mesh.push_vertex(vec3_scale(normal, radius), normal, uv);
+    }
+
+    // Triangles crossing the seam get copies of the corners near u = 0 moved
+    // to u + 1, and corners on a pole take the u of the rest of the triangle
+    let on_pole = |i: u32| {
+        let [x, _, z] = positions[i as usize];
+        x.abs() < 1e-6 && z.abs() < 1e-6
+    };
+    let mut copies = HashMap::<(u32, u32), u32>::new();
+    for triangle in triangles {
+        let mut us = triangle.map(|i| mesh.vertices[i as usize].uv[0]);
+        let (min, max) = triangle
+            .iter()
+            .zip(us)
+            .filter(|&(&i, _)| !on_pole(i))
+            .fold((f32::MAX, f32::MIN), |(min, max), (_, u)| {
+                (min.min(u), max.max(u))
+            });
+        if max - min > 0.5 {
+            for u in &mut us {
+                if *u < 0.5 {
+                    *u += 1.0;
+                }
+            }
+        }
+        for corner in 0..3 {
+            if on_pole(triangle[corner]) {
+                let others = (0..3).filter(|&other| !on_pole(triangle[other]));
+                us[corner] = others.clone().map(|other| us[other]).sum::<f32>()
+                    / others.count().max(1) as f32;
+            }
+        }
+
+        for (i, u) in triangle.into_iter().zip(us) {
+            let vertex = mesh.vertices[i as usize];
+            let index = if u == vertex.uv[0] {
+                i
+            } else {
+                *copies.entry((i, u.to_bits())).or_insert_with(|| {
+                    mesh.push_vertex(vertex.position, vertex.normal, [u, vertex.uv[1]])
+                })
+            };
+            mesh.indices.push(index);
+        }
+    }
+
+    mesh
+}
+
+// Closed cylinder along the Y axis, centered on the origin
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> Mesh {
+    let segments = segments.max(3);
+    let half = height / 2.0;
+    let mut mesh = Mesh::default();
+
+    revolve(
+        &mut mesh,
+        &[
+            ProfilePoint {
+                radius,
+                y: half,
+                normal: [1.0, 0.0],
+            },
+            ProfilePoint {
+                radius,
+                y: -half,
+                normal: [1.0, 0.0],
+            },
+        ],
+        segments,
+    );
+    cap(&mut mesh, radius, half, true, segments);
+    cap(&mut mesh, radius, -half, false, segments);
+
+    mesh
+}
+
+// Cone along the Y axis with the apex on top, centered on the origin
+pub fn cone(radius: f32, height: f32, segments: u32) -> Mesh {
+    let segments = segments.max(3);
+    let half = height / 2.0;
+    let slope = vec3_normalize([height, radius, 0.0]);
+    let normal = [slope[0], slope[1]];
+    let mut mesh = Mesh::default();
+
+    revolve(
+        &mut mesh,
+        &[
+            ProfilePoint {
+                radius: 0.0,
+                y: half,
+                normal,
+            },
+            ProfilePoint {
+                radius,
+                y: -half,
+                normal,
+            },
+        ],
+        segments,
+    );
+    cap(&mut mesh, radius, -half, false, segments);
+
+    mesh
+}
+
+// Torus around the Y axis. `major_segments` go around the axis,
+// `minor_segments` around the tube.
+pub fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: u32,
+    minor_segments: u32,
+) -> Mesh {
+    let minor_segments = minor_segments.max(3);
+    let profile = (0..=minor_segments)
+        .map(|i| {
+            let (sin, cos) = (i as f32 / minor_segments as f32 * TAU).sin_cos();
+            ProfilePoint {
+                radius: major_radius + minor_radius * cos,
+                y: -minor_radius * sin,
+                normal: [cos, -sin],
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut mesh = Mesh::default();
+    revolve(&mut mesh, &profile, major_segments.max(3));
+    mesh
+}
+
+// Cylinder of `height` along the Y axis with hemispheres of `radius` on both
+// ends. `rings` is the number of rings per hemisphere.
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Mesh {
+    let rings = rings.max(1);
+    let half = height / 2.0;
+
+    let hemisphere = |center: f32, from: f32| {
+        (0..=rings).map(move |i| {
+            let angle = from + i as f32 / rings as f32 * FRAC_PI_2;
+            let (sin, cos) = angle.sin_cos();
+            // sin(PI) isn't exactly 0
+            let sin = if angle == 0.0 || angle >= PI {
+                0.0
+            } else {
+                sin
+            };
+            ProfilePoint {
+                radius: radius * sin,
+                y: center + radius * cos,
+                normal: [sin, cos],
+            }
+        })
+    };
+    let profile = hemisphere(half, 0.0)
+        .chain(hemisphere(-half, FRAC_PI_2))
+        .collect::<Vec<_>>();
+
+    let mut mesh = Mesh::default();
+    revolve(&mut mesh, &profile, segments.max(3));
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::vec3_length;
+
+    #[test]
+    fn icosphere_seam() {
+        for subdivisions in 0..4 {
+            let mesh = icosphere(1.0, subdivisions);
+
+            for triangle in mesh.indices.chunks_exact(3) {
+                let us = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize].uv[0]);
+                let span = us.iter().copied().fold(f32::MIN, f32::max)
+                    - us.iter().copied().fold(f32::MAX, f32::min);
+                assert!(span <= 0.5, "{us:?}");
+            }
+
+            // Copies on the seam share the position and normal of the vertex
+            // they were split from
+            for vertex in &mesh.vertices {
+                assert!((vec3_length(vertex.position) - 1.0).abs() < 1e-5);
+                assert_eq!(vertex.position, vertex.normal);
+            }
+        }
+    }
+}
@@ -0,0 +1,561 @@
+use super::{Mesh, MeshVertex};
+use crate::{
+    common::{Vec3, VertexAttribute},
+    entity::{Entity, VertexBuffer, VertexData},
+    math::{vec3_add, vec3_cross, vec3_dot, vec3_normalize, vec3_scale, vec3_sub},
+    types::{PrimitiveTopology, ScalarType},
+};
+use std::collections::HashMap;
+
+// Merges vertices whose bytes are identical and returns the unique vertices
+// with one index per input vertex, turning a triangle soup into an indexed
+// mesh
+pub fn weld<T: VertexData>(vertices: &[T]) -> (Vec<T>, Vec<u32>) {
+    let mut unique = Vec::new();
+    let mut lookup = HashMap::<Vec<u8>, u32>::new();
+
+    let indices = vertices
+        .iter()
+        .map(|vertex| {
+            let mut key = Vec::new();
+            vertex.write_bytes(&mut key);
+
+            *lookup.entry(key).or_insert_with(|| {
+                unique.push(*vertex);
+                (unique.len() - 1) as u32
+            })
+        })
+        .collect();
+
+    (unique, indices)
+}
+
+const CACHE_SIZE: usize = 32;
+
+fn vertex_score(cache_position: Option<usize>, remaining_triangles: usize) -> f32 {
+    if remaining_triangles == 0 {
+        return -1.0;
+    }
+
+    let cache_score = match cache_position {
+        None => 0.0,
+        // The last triangle's vertices get a fixed score so that strips
+        // don't keep going in the same direction
+        Some(position) if position < 3 => 0.75,
+        Some(position) => (1.0 - (position - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(1.5),
+    };
+
+    cache_score + 2.0 * (remaining_triangles as f32).powf(-0.5)
+}
+
+// Reorders triangles for the post-transform vertex cache, using Tom Forsyth's
+// linear-speed vertex cache optimisation. The triangles themselves, and their
+// winding, stay the same.
+pub fn optimize_vertex_cache(indices: &mut [u32], vertex_count: usize) {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return;
+    }
+
+    let mut vertex_triangles = vec![Vec::new(); vertex_count];
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        for &vertex in corners {
+            vertex_triangles[vertex as usize].push(triangle);
+        }
+    }
+
+    let mut remaining: Vec<usize> = vertex_triangles.iter().map(Vec::len).collect();
+    let mut cache_position: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut scores: Vec<f32> = remaining
+        .iter()
+        .map(|&remaining| vertex_score(None, remaining))
+        .collect();
+
+    let triangle_score = |scores: &[f32], triangle: usize| -> f32 {
+        indices[triangle * 3..triangle * 3 + 3]
+            .iter()
+            .map(|&vertex| scores[vertex as usize])
+            .sum()
+    };
+
+    let mut emitted = vec![false; triangle_count];
+    let mut order = Vec::with_capacity(triangle_count);
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut next_unemitted = 0;
+    let mut best = None;
+
+    while order.len() < triangle_count {
+        // Nothing in the cache is connected to a remaining triangle, so fall
+        // back to the first one that hasn't been drawn yet
+        let triangle = match best {
+            Some(triangle) => triangle,
+            None => {
+                while emitted[next_unemitted] {
+                    next_unemitted += 1;
+                }
+                next_unemitted
+            }
+        };
+
+        emitted[triangle] = true;
+        order.push(triangle);
+
+        let corners = [
+            indices[triangle * 3],
+            indices[triangle * 3 + 1],
+            indices[triangle * 3 + 2],
+        ];
+        for vertex in corners {
+            remaining[vertex as usize] -= 1;
+            cache.retain(|&cached| cached != vertex);
+        }
+        cache.splice(0..0, corners);
+
+        for (position, &vertex) in cache.iter().enumerate() {
+            cache_position[vertex as usize] = (position < CACHE_SIZE).then_some(position);
+            scores[vertex as usize] =
+                vertex_score(cache_position[vertex as usize], remaining[vertex as usize]);
+        }
+        cache.truncate(CACHE_SIZE);
+
+        best = cache
+            .iter()
+            .flat_map(|&vertex| vertex_triangles[vertex as usize].iter().copied())
+            .filter(|&triangle| !emitted[triangle])
+            .map(|triangle| (triangle, triangle_score(&scores, triangle)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(triangle, _)| triangle);
+    }
+
+    let reordered: Vec<u32> = order
+        .into_iter()
+        .flat_map(|triangle| indices[triangle * 3..triangle * 3 + 3].to_vec())
+        .collect();
+    indices[..reordered.len()].copy_from_slice(&reordered);
+}
+
+fn face_normal(a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+    vec3_cross(vec3_sub(b, a), vec3_sub(c, a))
+}
+
+pub(super) fn position_key(position: Vec3) -> [u32; 3] {
+    // 0.0 and -0.0 are the same position
+    position.map(|component| (component + 0.0).to_bits())
+}
+
+// Averages the normals of the triangles around every position, weighted by
+// their angle at that corner. Vertices that are only split because of their
+// other attributes get the same normal, vertices outside of every triangle
+// none.
+fn smooth_normals(positions: &[Vec3], indices: &[u32]) -> Vec<Option<Vec3>> {
+    let mut normals = HashMap::<[u32; 3], Vec3>::new();
+
+    for corners in indices.chunks_exact(3) {
+        let corners = [0, 1, 2].map(|i| positions[corners[i] as usize]);
+        let normal = vec3_normalize(face_normal(corners[0], corners[1], corners[2]));
+
+        for i in 0..3 {
+            let position = corners[i];
+            let to_next = vec3_normalize(vec3_sub(corners[(i + 1) % 3], position));
+            let to_previous = vec3_normalize(vec3_sub(corners[(i + 2) % 3], position));
+            let angle = vec3_dot(to_next, to_previous).clamp(-1.0, 1.0).acos();
+
+            let sum = normals.entry(position_key(position)).or_insert([0.0; 3]);
+            *sum = vec3_add(*sum, vec3_scale(normal, angle));
+        }
+    }
+
+    positions
+        .iter()
+        .map(|&position| {
+            normals
+                .get(&position_key(position))
+                .map(|&normal| vec3_normalize(normal))
+        })
+        .collect()
+}
+
+// MikkTSpace tangents, one per index
+fn corner_tangents(
+    positions: &[Vec3],
+    normals: &[Vec3],
+    uvs: &[[f32; 2]],
+    indices: &[u32],
+) -> Vec<[f32; 4]> {
+    let mut geometry = TangentGeometry {
+        positions,
+        normals,
+        uvs,
+        indices,
+        tangents: vec![[1.0, 0.0, 0.0, 1.0]; indices.len()],
+    };
+    bevy_mikktspace::generate_tangents(&mut geometry);
+    geometry.tangents
+}
+
+impl Mesh {
+    // Non-indexed triangles, three vertices per triangle
+    pub fn from_triangles(vertices: Vec<MeshVertex>) -> Mesh {
+        let indices = (0..vertices.len() as u32).collect();
+        Mesh { vertices, indices }
+    }
+
+    pub fn weld(&mut self) {
+        let (vertices, remap) = weld(&self.vertices);
+        self.vertices = vertices;
+        for index in &mut self.indices {
+            *index = remap[*index as usize];
+        }
+    }
+
+    pub fn optimize_vertex_cache(&mut self) {
+        optimize_vertex_cache(&mut self.indices, self.vertices.len());
+    }
+
+    // Gives every triangle its own vertices with the triangle's normal
+    pub fn compute_flat_normals(&mut self) {
+        let mut vertices = Vec::with_capacity(self.indices.len());
+
+        for corners in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| self.vertices[corners[i] as usize]);
+            let normal = vec3_normalize(face_normal(a.position, b.position, c.position));
+
+            vertices.extend([a, b, c].map(|vertex| MeshVertex { normal, ..vertex }));
+        }
+
+        *self = Mesh::from_triangles(vertices);
+    }
+
+    // Averages the normals of the triangles around every position, weighted by
+    // their angle at that corner. Vertices that are only split because of
+    // their UVs get the same normal.
+    pub fn compute_smooth_normals(&mut self) {
+        let positions: Vec<Vec3> = self.vertices.iter().map(|vertex| vertex.position).collect();
+        let normals = smooth_normals(&positions, &self.indices);
+
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            if let Some(normal) = normal {
+                vertex.normal = normal;
+            }
+        }
+    }
+
+    // MikkTSpace tangents, xyz is the tangent and w the sign of the bitangent.
+    // Vertices shared by triangles with different tangents are split, the
+    // returned tangents line up with the vertices afterwards and can be added
+    // to an entity with `Entity::push_vertex_attribute`. Needs normals and
+    // UVs.
+    pub fn generate_tangents(&mut self) -> Vec<[f32; 4]> {
+        let positions: Vec<Vec3> = self.vertices.iter().map(|vertex| vertex.position).collect();
+        let normals: Vec<Vec3> = self.vertices.iter().map(|vertex| vertex.normal).collect();
+        let uvs: Vec<[f32; 2]> = self.vertices.iter().map(|vertex| vertex.uv).collect();
+        let tangents = corner_tangents(&positions, &normals, &uvs, &self.indices);
+
+        #[derive(Clone, Copy)]
+        struct TangentVertex(MeshVertex, [f32; 4]);
+
+        impl VertexData for TangentVertex {
+            fn write_bytes(&self, bytes: &mut Vec<u8>) {
+                self.0.write_bytes(bytes);
+                self.1.write_bytes(bytes);
+            }
+        }
+
+        let corners: Vec<TangentVertex> = self
+            .indices
+            .iter()
+            .zip(tangents)
+            .map(|(&index, tangent)| TangentVertex(self.vertices[index as usize], tangent))
+            .collect();
+        let (vertices, indices) = weld(&corners);
+
+        self.vertices = vertices.iter().map(|vertex| vertex.0).collect();
+        self.indices = indices;
+        vertices.into_iter().map(|vertex| vertex.1).collect()
+    }
+}
+
+// The same operations on the vertex buffers of an entity, e.g. a triangle
+// soup loaded with `Entity::with_buffers`. Attributes are where `MeshVertex`
+// puts them: positions at location 0, normals at 2 and UVs at 3, tangents
+// go to location 4 like glTF's. Points and lines are left alone.
+impl Entity {
+    // Gives every triangle corner its own vertex in all buffers, strips and
+    // fans become lists
+    pub fn unindex(&mut self) {
+        if !self.has_triangles() {
+            return;
+        }
+
+        let indices = self.triangle_indices();
+
+        for buffer in 0..self.buffers.len() {
+            let stride = self.buffers[buffer].stride() as usize;
+            let data: Vec<u8> = indices
+                .iter()
+                .flat_map(|&index| {
+                    let start = index as usize * stride;
+                    self.buffers[buffer].data[start..start + stride]
+                        .iter()
+                        .copied()
+                })
+                .collect();
+            self.update_vertices(buffer, &data);
+        }
+
+        self.indices = None;
+        self.topology = PrimitiveTopology::Triangles;
+    }
+
+    pub fn compute_flat_normals(&mut self) {
+        if !self.has_triangles() {
+            return;
+        }
+        self.unindex();
+
+        let positions = self.positions();
+        let normals: Vec<[f32; 4]> = positions
+            .chunks_exact(3)
+            .flat_map(|corners| {
+                let [x, y, z] = vec3_normalize(face_normal(corners[0], corners[1], corners[2]));
+                [[x, y, z, 0.0]; 3]
+            })
+            .collect();
+
+        self.set_attribute(2, 3, &normals);
+    }
+
+    // See `Mesh::compute_smooth_normals`, vertices outside of every triangle
+    // keep their normal
+    pub fn compute_smooth_normals(&mut self) {
+        if !self.has_triangles() {
+            return;
+        }
+        let positions = self.positions();
+        let previous = self.read_attribute(2).unwrap_or_default();
+
+        let normals: Vec<[f32; 4]> = smooth_normals(&positions, &self.triangle_indices())
+            .into_iter()
+            .enumerate()
+            .map(|(vertex, normal)| match normal {
+                Some([x, y, z]) => [x, y, z, 0.0],
+                None => previous.get(vertex).copied().unwrap_or([0.0; 4]),
+            })
+            .collect();
+
+        self.set_attribute(2, 3, &normals);
+    }
+
+    // MikkTSpace tangents at location 4, see `Mesh::generate_tangents`.
+    // The entity is unindexed first, so every corner can have its own
+    // tangent. Needs normals and UVs.
+    pub fn generate_tangents(&mut self) {
+        if !self.has_triangles() {
+            return;
+        }
+        self.unindex();
+
+        let positions = self.positions();
+        let normals: Vec<Vec3> = self
+            .read_attribute(2)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|[x, y, z, _]| [x, y, z])
+            .collect();
+        let uvs: Vec<[f32; 2]> = self
+            .read_attribute(3)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|[u, v, _, _]| [u, v])
+            .collect();
+        assert!(
+            normals.len() == positions.len() && uvs.len() == positions.len(),
+            "generate_tangents needs normals at location 2 and UVs at location 3"
+        );
+
+        let indices: Vec<u32> = (0..positions.len() as u32).collect();
+        let tangents = corner_tangents(&positions, &normals, &uvs, &indices);
+
+        self.set_attribute(4, 4, &tangents);
+    }
+
+    fn has_triangles(&self) -> bool {
+        matches!(
+            self.topology,
+            PrimitiveTopology::Triangles
+                | PrimitiveTopology::TriangleStrip
+                | PrimitiveTopology::TriangleFan
+        )
+    }
+
+    fn positions(&self) -> Vec<Vec3> {
+        self.read_attribute(0)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|[x, y, z, _]| [x, y, z])
+            .collect()
+    }
+
+    // Writes the attribute when the entity has one at the location, otherwise
+    // adds a buffer of floats with that many components there
+    fn set_attribute(&mut self, location: u32, count: u32, values: &[[f32; 4]]) {
+        if self.write_attribute(location, values) {
+            return;
+        }
+
+        let locations: u32 = self
+            .buffers
+            .iter()
+            .map(|buffer| buffer.attributes.len() as u32)
+            .sum();
+        assert!(
+            location >= locations,
+            "location {location} is skipped inside a vertex buffer"
+        );
+
+        let mut attributes = vec![None; (location - locations) as usize];
+        attributes.push(Some(VertexAttribute::new(count, ScalarType::F32, false)));
+        let data: Vec<f32> = values
+            .iter()
+            .flat_map(|value| value[..count as usize].to_vec())
+            .collect();
+
+        self.push_vertex_buffer(VertexBuffer::new(&data, attributes));
+    }
+}
+
+struct TangentGeometry<'a> {
+    positions: &'a [Vec3],
+    normals: &'a [Vec3],
+    uvs: &'a [[f32; 2]],
+    indices: &'a [u32],
+    tangents: Vec<[f32; 4]>,
+}
+
+impl TangentGeometry<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> usize {
+        self.indices[face * 3 + vert] as usize
+    }
+}
+
+impl bevy_mikktspace::Geometry for TangentGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.positions[self.vertex(face, vert)]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.normals[self.vertex(face, vert)]
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.uvs[self.vertex(face, vert)]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = tangent;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::plane;
+
+    // Vertices transformed with a FIFO cache of 16 entries, per triangle
+    fn cache_misses(indices: &[u32]) -> f32 {
+        let mut cache = std::collections::VecDeque::new();
+        let mut misses = 0;
+
+        for &index in indices {
+            if !cache.contains(&index) {
+                misses += 1;
+                cache.push_back(index);
+                if cache.len() > 16 {
+                    cache.pop_front();
+                }
+            }
+        }
+        misses as f32 / (indices.len() / 3) as f32
+    }
+
+    fn sorted_triangles(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect();
+        triangles.sort();
+        triangles
+    }
+
+    #[test]
+    fn weld_merges_identical_vertices() {
+        let indexed = plane(1.0, 1.0, 2);
+        let mut mesh = Mesh::from_triangles(
+            indexed
+                .indices
+                .iter()
+                .map(|&index| indexed.vertices[index as usize])
+                .collect(),
+        );
+
+        mesh.weld();
+        assert_eq!(mesh.vertices.len(), indexed.vertices.len());
+        for (&welded, &index) in mesh.indices.iter().zip(&indexed.indices) {
+            assert_eq!(
+                mesh.vertices[welded as usize],
+                indexed.vertices[index as usize]
+            );
+        }
+    }
+
+    #[test]
+    fn vertex_cache_order() {
+        let mut mesh = plane(1.0, 1.0, 32);
+
+        // Every other row first, so neighbouring triangles are far apart
+        let rows: Vec<&[u32]> = mesh.indices.chunks(32 * 6).collect();
+        let mut indices: Vec<u32> = rows.iter().step_by(2).copied().flatten().copied().collect();
+        indices.extend(rows.iter().skip(1).step_by(2).copied().flatten());
+        mesh.indices = indices.clone();
+
+        mesh.optimize_vertex_cache();
+
+        assert_eq!(sorted_triangles(&mesh.indices), sorted_triangles(&indices));
+        assert!(cache_misses(&mesh.indices) < cache_misses(&indices));
+        assert!(cache_misses(&mesh.indices) < 0.8);
+    }
+
+    #[test]
+    fn smooth_normals_across_uv_seams() {
+        let mut mesh = plane(1.0, 1.0, 2);
+        for vertex in &mut mesh.vertices {
+            vertex.normal = [0.0; 3];
+        }
+        mesh.compute_smooth_normals();
+
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|vertex| vertex.normal == [0.0, 1.0, 0.0]));
+    }
+
+    #[test]
+    fn tangents_follow_u() {
+        let mut mesh = plane(1.0, 1.0, 2);
+        let tangents = mesh.generate_tangents();
+
+        assert_eq!(tangents.len(), mesh.vertices.len());
+        for [x, y, z, w] in tangents {
+            assert!((x - 1.0).abs() < 1e-5 && y.abs() < 1e-5 && z.abs() < 1e-5);
+            assert_eq!(w.abs(), 1.0);
+        }
+    }
+}
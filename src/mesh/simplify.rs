@@ -0,0 +1,505 @@
+use super::{processing::position_key, Mesh};
+use crate::{
+    common::Vec3,
+    math::{vec3_add, vec3_cross, vec3_dot, vec3_length, vec3_normalize, vec3_scale, vec3_sub},
+};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+// Open edges and attribute seams are kept in place by planes through them,
+// weighted this much more than the surface around them
+const BORDER_WEIGHT: f64 = 10.0;
+
+// A symmetric 4x4 matrix, the upper triangle row by row. f64, sums of many
+// nearly parallel planes lose too much precision in f32.
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    // The squared distance to the plane dot(normal, p) + d = 0, times weight
+    fn plane(normal: Vec3, d: f32, weight: f64) -> Quadric {
+        let [a, b, c] = normal.map(f64::from);
+        let d = f64::from(d);
+
+        Quadric(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|value| value * weight),
+        )
+    }
+
+    fn add(self, other: Quadric) -> Quadric {
+        Quadric(std::array::from_fn(|i| self.0[i] + other.0[i]))
+    }
+
+    fn error(&self, point: Vec3) -> f64 {
+        let [x, y, z] = point.map(f64::from);
+        let q = &self.0;
+
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+
+    // The point with the smallest error, `None` when the planes don't pin one
+    // down, e.g. on a flat or cylindrical patch
+    fn minimum(&self) -> Option<Vec3> {
+        let q = &self.0;
+        let matrix = [[q[0], q[1], q[2]], [q[1], q[4], q[5]], [q[2], q[5], q[7]]];
+        let rhs = [-q[3], -q[6], -q[8]];
+
+        let denominator = determinant(matrix);
+        if denominator.abs() <= 1e-9 * (q[0] * q[4] * q[7]).abs() || denominator == 0.0 {
+            return None;
+        }
+
+        // Cramer's rule, the columns replaced by the right hand side in turn
+        Some(std::array::from_fn(|column| {
+            let mut replaced = matrix;
+            for row in 0..3 {
+                replaced[row][column] = rhs[row];
+            }
+            (determinant(replaced) / denominator) as f32
+        }))
+    }
+}
+
+fn determinant(m: [[f64; 3]; 3]) -> f64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+// Merging `remove` into `keep` at `position`. The stamps are those of the two
+// nodes when the cost was computed, the entry is stale once either changed.
+struct Collapse {
+    cost: f64,
+    keep: usize,
+    remove: usize,
+    position: Vec3,
+    stamps: [u32; 2],
+}
+
+// Reversed, so the binary heap pops the cheapest collapse first
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+// Vertices at the same position (split by their normals or UVs) form a node,
+// edges between nodes are collapsed
+struct Simplifier {
+    mesh: Mesh,
+    triangles: Vec<[u32; 3]>,
+    removed: Vec<bool>,
+    node_of: Vec<usize>,
+    positions: Vec<Vec3>,
+    node_vertices: Vec<Vec<u32>>,
+    node_triangles: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+    stamps: Vec<u32>,
+}
+
+impl Simplifier {
+    fn new(mesh: &Mesh) -> Simplifier {
+        let mut lookup = HashMap::<[u32; 3], usize>::new();
+        let mut positions = Vec::new();
+        let mut node_vertices = Vec::new();
+
+        let node_of: Vec<usize> = mesh
+            .vertices
+            .iter()
+            .enumerate()
+            .map(|(index, vertex)| {
+                let node = *lookup
+                    .entry(position_key(vertex.position))
+                    .or_insert_with(|| {
+                        positions.push(vertex.position);
+                        node_vertices.push(Vec::new());
+                        positions.len() - 1
+                    });
+                node_vertices[node].push(index as u32);
+                node
+            })
+            .collect();
+
+        // Triangles that are already degenerate would only get in the way
+        let triangles: Vec<[u32; 3]> = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|corners| [corners[0], corners[1], corners[2]])
+            .filter(|corners| {
+                let [a, b, c] = corners.map(|vertex| node_of[vertex as usize]);
+                a != b && b != c && c != a
+            })
+            .collect();
+
+        let mut simplifier = Simplifier {
+            mesh: mesh.clone(),
+            removed: vec![false; triangles.len()],
+            node_triangles: vec![Vec::new(); positions.len()],
+            quadrics: vec![Quadric::default(); positions.len()],
+            stamps: vec![0; positions.len()],
+            triangles,
+            node_of,
+            positions,
+            node_vertices,
+        };
+        simplifier.compute_quadrics();
+        simplifier
+    }
+
+    fn nodes(&self, triangle: usize) -> [usize; 3] {
+        self.triangles[triangle].map(|vertex| self.node_of[vertex as usize])
+    }
+
+    fn compute_quadrics(&mut self) {
+        // Edges of the vertices (not the nodes) that only one triangle uses
+        // are open edges or seams
+        let mut edge_count = HashMap::<(u32, u32), u32>::new();
+        for corners in &self.triangles {
+            for i in 0..3 {
+                let (a, b) = (corners[i], corners[(i + 1) % 3]);
+                *edge_count.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+        }
+
+        for triangle in 0..self.triangles.len() {
+            let nodes = self.nodes(triangle);
+            for node in nodes {
+                self.node_triangles[node].push(triangle);
+            }
+
+            let [a, b, c] = nodes.map(|node| self.positions[node]);
+            let cross = vec3_cross(vec3_sub(b, a), vec3_sub(c, a));
+            let double_area = vec3_length(cross);
+            if double_area == 0.0 {
+                continue;
+            }
+            let normal = vec3_scale(cross, 1.0 / double_area);
+
+            let quadric =
+                Quadric::plane(normal, -vec3_dot(normal, a), f64::from(double_area) / 2.0);
+            for node in nodes {
+                self.quadrics[node] = self.quadrics[node].add(quadric);
+            }
+
+            let corners = self.triangles[triangle];
+            for i in 0..3 {
+                let (first, second) = (corners[i], corners[(i + 1) % 3]);
+                if edge_count[&(first.min(second), first.max(second))] != 1 {
+                    continue;
+                }
+
+                let (start, end) = (nodes[i], nodes[(i + 1) % 3]);
+                let edge = vec3_sub(self.positions[end], self.positions[start]);
+                let border_normal = vec3_normalize(vec3_cross(edge, normal));
+                let quadric = Quadric::plane(
+                    border_normal,
+                    -vec3_dot(border_normal, self.positions[start]),
+                    BORDER_WEIGHT * f64::from(vec3_dot(edge, edge)),
+                );
+
+                for node in [start, end] {
+                    self.quadrics[node] = self.quadrics[node].add(quadric);
+                }
+            }
+        }
+    }
+
+    fn neighbours(&self, node: usize) -> HashSet<usize> {
+        self.node_triangles[node]
+            .iter()
+            .flat_map(|&triangle| self.nodes(triangle))
+            .filter(|&other| other != node)
+            .collect()
+    }
+
+    fn collapse(&self, keep: usize, remove: usize) -> Collapse {
+        let quadric = self.quadrics[keep].add(self.quadrics[remove]);
+        let (a, b) = (self.positions[keep], self.positions[remove]);
+        let midpoint = vec3_scale(vec3_add(a, b), 0.5);
+
+        // Far away minimums come from nearly singular quadrics, the ends and
+        // the midpoint are safer there
+        let position = quadric
+            .minimum()
+            .filter(|minimum| {
+                vec3_length(vec3_sub(*minimum, midpoint)) <= vec3_length(vec3_sub(b, a))
+            })
+            .unwrap_or_else(|| {
+                [a, b, midpoint]
+                    .into_iter()
+                    .min_by(|p, q| quadric.error(*p).total_cmp(&quadric.error(*q)))
+                    .unwrap()
+            });
+
+        Collapse {
+            cost: quadric.error(position).max(0.0),
+            keep,
+            remove,
+            position,
+            stamps: [self.stamps[keep], self.stamps[remove]],
+        }
+    }
+
+    // Rejects collapses that would fold a triangle over or pinch the surface
+    // into a non-manifold edge
+    fn is_valid(&self, collapse: &Collapse) -> bool {
+        let Collapse { keep, remove, .. } = *collapse;
+
+        let shared = self.node_triangles[remove]
+            .iter()
+            .filter(|&&triangle| self.nodes(triangle).contains(&keep))
+            .count();
+        let common = self
+            .neighbours(keep)
+            .intersection(&self.neighbours(remove))
+            .count();
+        if common > shared {
+            return false;
+        }
+
+        self.node_triangles[keep]
+            .iter()
+            .chain(&self.node_triangles[remove])
+            .all(|&triangle| {
+                let nodes = self.nodes(triangle);
+                if nodes.contains(&keep) && nodes.contains(&remove) {
+                    return true;
+                }
+
+                let before = nodes.map(|node| self.positions[node]);
+                let after = nodes.map(|node| {
+                    if node == keep || node == remove {
+                        collapse.position
+                    } else {
+                        self.positions[node]
+                    }
+                });
+                let normal = |[a, b, c]: [Vec3; 3]| vec3_cross(vec3_sub(b, a), vec3_sub(c, a));
+
+                vec3_dot(normal(before), normal(after)) > 0.0
+            })
+    }
+
+    fn apply(&mut self, collapse: &Collapse) -> usize {
+        let Collapse { keep, remove, .. } = *collapse;
+        let mut removed_triangles = 0;
+
+        // The vertices of the collapsed triangles pair up, the removed
+        // node's vertex is replaced by the kept one's
+        let mut replacements = HashMap::<u32, u32>::new();
+        for triangle in std::mem::take(&mut self.node_triangles[remove]) {
+            let nodes = self.nodes(triangle);
+
+            if nodes.contains(&keep) {
+                let corners = self.triangles[triangle];
+                let kept = corners[nodes.iter().position(|&node| node == keep).unwrap()];
+                let replaced = corners[nodes.iter().position(|&node| node == remove).unwrap()];
+                replacements.entry(replaced).or_insert(kept);
+
+                self.removed[triangle] = true;
+                removed_triangles += 1;
+
+                let removed = &self.removed;
+                for node in nodes.into_iter().filter(|&node| node != remove) {
+                    self.node_triangles[node].retain(|&triangle| !removed[triangle]);
+                }
+            } else {
+                self.node_triangles[keep].push(triangle);
+            }
+        }
+
+        for &triangle in &self.node_triangles[keep] {
+            for corner in &mut self.triangles[triangle] {
+                if let Some(&kept) = replacements.get(corner) {
+                    *corner = kept;
+                }
+            }
+        }
+
+        // Vertices without a partner keep their normals and UVs, they just
+        // move to the kept node
+        for vertex in std::mem::take(&mut self.node_vertices[remove]) {
+            if !replacements.contains_key(&vertex) {
+                self.node_of[vertex as usize] = keep;
+                self.node_vertices[keep].push(vertex);
+            }
+        }
+
+        self.positions[keep] = collapse.position;
+        for &vertex in &self.node_vertices[keep] {
+            self.mesh.vertices[vertex as usize].position = collapse.position;
+        }
+        self.quadrics[keep] = self.quadrics[keep].add(self.quadrics[remove]);
+        self.stamps[keep] += 1;
+        self.stamps[remove] += 1;
+
+        removed_triangles
+    }
+
+    fn finish(mut self) -> Mesh {
+        let mut remap = HashMap::<u32, u32>::new();
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        for (corners, _) in self
+            .triangles
+            .iter()
+            .zip(&self.removed)
+            .filter(|(_, removed)| !**removed)
+        {
+            for &vertex in corners {
+                indices.push(*remap.entry(vertex).or_insert_with(|| {
+                    vertices.push(self.mesh.vertices[vertex as usize]);
+                    (vertices.len() - 1) as u32
+                }));
+            }
+        }
+
+        self.mesh = Mesh { vertices, indices };
+        self.mesh.weld();
+        self.mesh
+    }
+}
+
+impl Mesh {
+    // Collapses edges in order of their quadric error (Garland and Heckbert)
+    // until at most `target_triangles` are left, or no collapse is possible
+    // without flipping a triangle. Open edges and UV or normal seams are
+    // held in place, the vertices keep their normals and UVs.
+    pub fn simplify(&self, target_triangles: usize) -> Mesh {
+        let mut simplifier = Simplifier::new(self);
+        let mut triangle_count = simplifier.triangles.len();
+
+        let mut edges = HashSet::new();
+        for triangle in 0..simplifier.triangles.len() {
+            let nodes = simplifier.nodes(triangle);
+            for i in 0..3 {
+                let (a, b) = (nodes[i], nodes[(i + 1) % 3]);
+                edges.insert((a.min(b), a.max(b)));
+            }
+        }
+        let mut heap: BinaryHeap<Collapse> = edges
+            .into_iter()
+            .map(|(a, b)| simplifier.collapse(a, b))
+            .collect();
+
+        while triangle_count > target_triangles {
+            let Some(collapse) = heap.pop() else {
+                break;
+            };
+
+            if collapse.stamps
+                != [
+                    simplifier.stamps[collapse.keep],
+                    simplifier.stamps[collapse.remove],
+                ]
+                || !simplifier.is_valid(&collapse)
+            {
+                continue;
+            }
+
+            triangle_count -= simplifier.apply(&collapse);
+
+            for neighbour in simplifier.neighbours(collapse.keep) {
+                heap.push(simplifier.collapse(collapse.keep, neighbour));
+            }
+        }
+
+        simplifier.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::{icosphere, plane};
+
+    fn face_normals(mesh: &Mesh) -> Vec<(Vec3, Vec3)> {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize].position);
+                let center = vec3_scale(vec3_add(vec3_add(a, b), c), 1.0 / 3.0);
+                (center, vec3_cross(vec3_sub(b, a), vec3_sub(c, a)))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plane_keeps_facing_up() {
+        let mesh = plane(2.0, 2.0, 16);
+        let simplified = mesh.simplify(128);
+
+        let normals = face_normals(&simplified);
+        assert!(!normals.is_empty() && normals.len() <= 128);
+        assert!(normals.iter().all(|&(_, normal)| normal[1] > 0.0));
+
+        // The corners are on two open edges, they can't move
+        for corner in [
+            [-1.0, 0.0, -1.0],
+            [1.0, 0.0, -1.0],
+            [-1.0, 0.0, 1.0],
+            [1.0, 0.0, 1.0],
+        ] {
+            assert!(simplified
+                .vertices
+                .iter()
+                .any(|vertex| vertex.position == corner));
+        }
+    }
+
+    #[test]
+    fn sphere_keeps_facing_out() {
+        let mesh = icosphere(1.0, 3);
+        let simplified = mesh.simplify(200);
+
+        let normals = face_normals(&simplified);
+        assert!(!normals.is_empty() && normals.len() <= 200);
+        assert!(normals
+            .iter()
+            .all(|&(center, normal)| vec3_dot(center, normal) > 0.0));
+    }
+
+    #[test]
+    fn target_above_the_triangle_count() {
+        let mesh = plane(1.0, 1.0, 4);
+        assert_eq!(mesh.simplify(1000).indices.len(), mesh.indices.len());
+    }
+}
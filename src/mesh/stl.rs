@@ -0,0 +1,229 @@
+use super::{
+    format::{invalid, positions, FormatError},
+    Mesh, MeshVertex,
+};
+use crate::{
+    common::Vec3,
+    entity::Entity,
+    math::{vec3_cross, vec3_length, vec3_normalize, vec3_sub},
+};
+
+fn face_normal([a, b, c]: [Vec3; 3]) -> Vec3 {
+    vec3_normalize(vec3_cross(vec3_sub(b, a), vec3_sub(c, a)))
+}
+
+fn push_facet(vertices: &mut Vec<MeshVertex>, normal: Vec3, corners: [Vec3; 3]) {
+    // Zero normals are common, they have to be calculated from the corners
+    let normal = if vec3_length(normal) > 0.0 {
+        vec3_normalize(normal)
+    } else {
+        face_normal(corners)
+    };
+
+    vertices.extend(corners.map(|position| MeshVertex {
+        position,
+        normal,
+        uv: [0.0; 2],
+    }));
+}
+
+fn parse_binary(bytes: &[u8]) -> Mesh {
+    let mut vertices = Vec::new();
+
+    // An 80 byte header and the triangle count, then 50 bytes per triangle:
+    // normal, 3 corners and an unused attribute word
+    for facet in bytes[84..].chunks_exact(50) {
+        let vector = |offset: usize| -> Vec3 {
+            std::array::from_fn(|i| {
+                let start = offset + i * 4;
+                f32::from_le_bytes(facet[start..start + 4].try_into().unwrap())
+            })
+        };
+
+        push_facet(
+            &mut vertices,
+            vector(0),
+            [vector(12), vector(24), vector(36)],
+        );
+    }
+
+    Mesh::from_triangles(vertices)
+}
+
+fn parse_ascii(source: &str) -> Result<Mesh, FormatError> {
+    let mut vertices = Vec::new();
+    let mut tokens = source.split_whitespace();
+    let mut normal = [0.0; 3];
+    let mut corners: Vec<Vec3> = Vec::new();
+
+    let read_vector = |tokens: &mut std::str::SplitWhitespace| -> Result<Vec3, FormatError> {
+        let mut vector = [0.0; 3];
+        for component in &mut vector {
+            let token = tokens
+                .next()
+                .ok_or_else(|| invalid("unexpected end of the STL file"))?;
+            *component = token
+                .parse()
+                .map_err(|_| invalid(format!("invalid number `{token}` in the STL file")))?;
+        }
+        Ok(vector)
+    };
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "normal" => normal = read_vector(&mut tokens)?,
+            "vertex" => corners.push(read_vector(&mut tokens)?),
+            // Facets with more than 3 corners are fanned out
+            "endfacet" => {
+                for i in 1..corners.len().saturating_sub(1) {
+                    push_facet(
+                        &mut vertices,
+                        normal,
+                        [corners[0], corners[i], corners[i + 1]],
+                    );
+                }
+                corners.clear();
+                normal = [0.0; 3];
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Mesh::from_triangles(vertices))
+}
+
+// Binary or ASCII STL, every triangle gets its own vertices with the facet's
+// normal
+pub fn parse_stl(bytes: &[u8]) -> Result<Mesh, FormatError> {
+    // Binary files may start with "solid" too, the size tells them apart
+    if bytes.len() >= 84 {
+        let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+        let size = count.checked_mul(50).and_then(|size| size.checked_add(84));
+        if size == Some(bytes.len()) {
+            return Ok(parse_binary(bytes));
+        }
+    }
+
+    if bytes.starts_with(b"solid") {
+        let source =
+            std::str::from_utf8(bytes).map_err(|_| invalid("the ASCII STL file isn't UTF-8"))?;
+        return parse_ascii(source);
+    }
+
+    Err(invalid("the STL file is truncated"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_stl(path: impl AsRef<std::path::Path>) -> Result<Mesh, FormatError> {
+    parse_stl(&std::fs::read(path)?)
+}
+
+fn triangles(entity: &Entity) -> Vec<[Vec3; 3]> {
+    let positions = positions(entity);
+    entity
+        .triangle_indices()
+        .chunks_exact(3)
+        .map(|triangle| [0, 1, 2].map(|i| positions[triangle[i] as usize]))
+        .collect()
+}
+
+// Binary STL of the entity's triangles, the normals are calculated from the
+// corners
+pub fn write_stl(entity: &Entity) -> Vec<u8> {
+    write_binary(&triangles(entity))
+}
+
+fn write_binary(triangles: &[[Vec3; 3]]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(84 + triangles.len() * 50);
+
+    let mut header = [0u8; 80];
+    header[..8].copy_from_slice(b"reindeer");
+    bytes.extend(header);
+    bytes.extend((triangles.len() as u32).to_le_bytes());
+
+    for &corners in triangles {
+        for vector in [face_normal(corners), corners[0], corners[1], corners[2]] {
+            bytes.extend(vector.iter().flat_map(|component| component.to_le_bytes()));
+        }
+        bytes.extend([0, 0]);
+    }
+
+    bytes
+}
+
+pub fn write_stl_ascii(entity: &Entity, name: &str) -> String {
+    let mut output = format!("solid {name}\n");
+
+    for corners in triangles(entity) {
+        let [nx, ny, nz] = face_normal(corners);
+        output += &format!("  facet normal {nx:e} {ny:e} {nz:e}\n    outer loop\n");
+        for [x, y, z] in corners {
+            output += &format!("      vertex {x:e} {y:e} {z:e}\n");
+        }
+        output += "    endloop\n  endfacet\n";
+    }
+
+    output + &format!("endsolid {name}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLES: [[Vec3; 3]; 2] = [
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+        [[0.0, 0.0, 1.0], [0.0, 1.0, 1.0], [1.0, 0.0, 1.0]],
+    ];
+
+    #[test]
+    fn binary_round_trip() {
+        let mesh = parse_stl(&write_binary(&TRIANGLES)).unwrap();
+
+        let positions: Vec<Vec3> = mesh
+            .indices
+            .iter()
+            .map(|&index| mesh.vertices[index as usize].position)
+            .collect();
+        assert_eq!(positions, TRIANGLES.concat());
+
+        let normals: Vec<Vec3> = mesh
+            .indices
+            .iter()
+            .map(|&index| mesh.vertices[index as usize].normal)
+            .collect();
+        assert_eq!(normals[..3], [[0.0, 0.0, 1.0]; 3]);
+        assert_eq!(normals[3..], [[0.0, 0.0, -1.0]; 3]);
+    }
+
+    #[test]
+    fn ascii_facets() {
+        let source = "solid test
+            facet normal 0 0 0
+              outer loop
+                vertex 0 0 0
+                vertex 1 0 0
+                vertex 1 1 0
+                vertex 0 1 0
+              endloop
+            endfacet
+            endsolid test";
+        let mesh = parse_stl(source.as_bytes()).unwrap();
+
+        assert_eq!(mesh.indices.len(), 6);
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|vertex| vertex.normal == [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn triangle_count_past_the_end() {
+        let mut bytes = write_binary(&TRIANGLES);
+        bytes[80..84].copy_from_slice(&3u32.to_le_bytes());
+        assert!(parse_stl(&bytes).is_err());
+
+        // 84 + count * 50 overflows on 32 bit targets
+        bytes[80..84].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse_stl(&bytes).is_err());
+    }
+}
@@ -1,10 +1,19 @@
 use crate::BACKEND;
 
-use super::{backend::Backend, WINDOW_HEIGHT, WINDOW_WIDTH};
-use gl::types::{GLfloat, GLsizeiptr};
+use super::{
+    backend::Backend,
+    common::{Capabilities, QueryResult},
+    types::{BufferTarget, BufferUsage, IndexType, PolygonMode, PrimitiveTopology, ScalarType},
+    WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use gl::types::{GLchar, GLenum, GLintptr, GLsizei, GLsizeiptr, GLuint};
 use glfw::{Context as GLFWContext, WindowEvent};
-use std::mem;
-use std::{ffi::c_void, sync::mpsc::Receiver};
+use std::{
+    ffi::{c_void, CStr},
+    ptr,
+    sync::mpsc::Receiver,
+    time::Duration,
+};
 
 pub mod shader;
 
@@ -21,6 +30,8 @@ impl Backend for Context {
     type Program = u32;
     type Shader = u32;
     type Vao = u32;
+    type Query = u32;
+    type UniformLocation = i32;
 
     fn new() -> Self::Context {
         let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).expect("Failed to initialize window.");
@@ -35,6 +46,9 @@ impl Backend for Context {
             glfw::OpenGlProfileHint::Core,
         ));
         glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
+        if crate::debug() {
+            glfw.window_hint(glfw::WindowHint::OpenGlDebugContext(true));
+        }
 
         let (mut window, events) = glfw
             .create_window(
@@ -52,12 +66,17 @@ impl Backend for Context {
 
         gl::load_with(|s| window.get_proc_address(s) as *const _);
 
+        if crate::debug() {
+            enable_debug_output();
+        }
+
         let (fb_width, fb_height) = window.get_framebuffer_size();
 
         unsafe {
             gl::Viewport(0, 0, fb_width, fb_height);
             gl::Enable(gl::DEPTH_TEST);
-            // gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+            // Point sizes come from gl_PointSize, like in WebGL
+            gl::Enable(gl::PROGRAM_POINT_SIZE);
         };
 
         Context {
@@ -67,23 +86,62 @@ impl Backend for Context {
         }
     }
 
+    fn capabilities(&self) -> Capabilities {
+        let extension_count = get_integer(gl::NUM_EXTENSIONS);
+
+        Capabilities {
+            version: get_string(gl::VERSION),
+            renderer: get_string(gl::RENDERER),
+            vendor: get_string(gl::VENDOR),
+            max_texture_size: get_integer(gl::MAX_TEXTURE_SIZE),
+            max_vertex_attributes: get_integer(gl::MAX_VERTEX_ATTRIBS),
+            max_uniform_block_size: get_integer(gl::MAX_UNIFORM_BLOCK_SIZE),
+            max_samples: get_integer(gl::MAX_SAMPLES),
+            timer_query: true,
+            polygon_mode: true,
+            max_point_size: {
+                let mut range = [0.0; 2];
+                unsafe { gl::GetFloatv(gl::POINT_SIZE_RANGE, range.as_mut_ptr()) }
+                range[1]
+            },
+            extensions: (0..extension_count)
+                .map(|index| unsafe {
+                    CStr::from_ptr(gl::GetStringi(gl::EXTENSIONS, index) as *const _)
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect(),
+        }
+    }
+
     fn create_buffer(&self) -> Self::Buffer {
         let mut buf: u32 = 0;
         unsafe { gl::GenBuffers(1, &mut buf) }
         buf
     }
 
-    fn bind_buffer(&self, buffer_type: u32, buffer: &Self::Buffer) {
-        unsafe { gl::BindBuffer(buffer_type, *buffer) }
+    fn bind_buffer(&self, target: BufferTarget, buffer: &Self::Buffer) {
+        unsafe { gl::BindBuffer(buffer_target(target), *buffer) }
     }
 
-    fn buffer_data(&self, vertices: &[f32], buffer_type: u32, usage_hint: u32) {
+    fn buffer_data(&self, data: &[u8], target: BufferTarget, usage: BufferUsage) {
         unsafe {
             gl::BufferData(
-                buffer_type,
-                (vertices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
-                &vertices[0] as *const f32 as *const std::ffi::c_void,
-                usage_hint,
+                buffer_target(target),
+                data.len() as GLsizeiptr,
+                data.as_ptr() as *const c_void,
+                buffer_usage(usage),
+            )
+        }
+    }
+
+    fn buffer_sub_data(&self, data: &[u8], target: BufferTarget, offset: i32) {
+        unsafe {
+            gl::BufferSubData(
+                buffer_target(target),
+                offset as GLintptr,
+                data.len() as GLsizeiptr,
+                data.as_ptr() as *const c_void,
             )
         }
     }
@@ -100,20 +158,122 @@ impl Backend for Context {
         unsafe { gl::ClearColor(red, green, blue, alpha) }
     }
 
+    // Lines are pulled towards the camera, so they win against the filled
+    // triangles they are drawn over
+    fn set_polygon_mode(&self, mode: PolygonMode) {
+        unsafe {
+            match mode {
+                PolygonMode::Fill => {
+                    gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+                    gl::Disable(gl::POLYGON_OFFSET_LINE);
+                }
+                PolygonMode::Line => {
+                    gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
+                    gl::Enable(gl::POLYGON_OFFSET_LINE);
+                    gl::PolygonOffset(-1.0, -1.0);
+                }
+            }
+        }
+    }
+
     fn create_vertex_array(&self) -> Self::Vao {
         let mut vao: u32 = 0;
         unsafe { gl::GenVertexArrays(1, &mut vao) }
         vao
     }
 
-    fn draw_arrays(&self, mode: u32, first: i32, vertex_count: i32) {
-        unsafe { gl::DrawArrays(mode, first, vertex_count) }
+    fn draw_arrays(&self, topology: PrimitiveTopology, first: i32, vertex_count: i32) {
+        unsafe { gl::DrawArrays(primitive_topology(topology), first, vertex_count) }
+    }
+
+    fn draw_elements(
+        &self,
+        topology: PrimitiveTopology,
+        index_count: i32,
+        index_type: IndexType,
+        offset: i32,
+    ) {
+        unsafe {
+            gl::DrawElements(
+                primitive_topology(topology),
+                index_count,
+                index_type_enum(index_type),
+                offset as *const c_void,
+            )
+        }
+    }
+
+    fn draw_arrays_instanced(
+        &self,
+        topology: PrimitiveTopology,
+        first: i32,
+        vertex_count: i32,
+        instance_count: i32,
+    ) {
+        unsafe {
+            gl::DrawArraysInstanced(
+                primitive_topology(topology),
+                first,
+                vertex_count,
+                instance_count,
+            )
+        }
+    }
+
+    fn draw_elements_instanced(
+        &self,
+        topology: PrimitiveTopology,
+        index_count: i32,
+        index_type: IndexType,
+        offset: i32,
+        instance_count: i32,
+    ) {
+        unsafe {
+            gl::DrawElementsInstanced(
+                primitive_topology(topology),
+                index_count,
+                index_type_enum(index_type),
+                offset as *const c_void,
+                instance_count,
+            )
+        }
+    }
+
+    fn create_timer_query(&self) -> Self::Query {
+        let mut query: u32 = 0;
+        unsafe { gl::GenQueries(1, &mut query) }
+        query
+    }
+
+    fn begin_timer_query(&self, query: &Self::Query) {
+        unsafe { gl::BeginQuery(gl::TIME_ELAPSED, *query) }
+    }
+
+    fn end_timer_query(&self, _query: &Self::Query) {
+        unsafe { gl::EndQuery(gl::TIME_ELAPSED) }
+    }
+
+    fn timer_query_result(&self, query: &Self::Query) -> QueryResult {
+        let mut available = 0;
+        unsafe { gl::GetQueryObjectiv(*query, gl::QUERY_RESULT_AVAILABLE, &mut available) }
+
+        if available == 0 {
+            return QueryResult::Pending;
+        }
+
+        let mut elapsed: u64 = 0;
+        unsafe { gl::GetQueryObjectui64v(*query, gl::QUERY_RESULT, &mut elapsed) }
+        QueryResult::Ready(Duration::from_nanos(elapsed))
     }
 
     fn enable_vertex_attrib_array(&self, index: u32) {
         unsafe { gl::EnableVertexAttribArray(index) }
     }
 
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        unsafe { gl::VertexAttribDivisor(index, divisor) }
+    }
+
     fn use_program(&self, program: &Self::Program) {
         unsafe { gl::UseProgram(*program) }
     }
@@ -122,7 +282,7 @@ impl Backend for Context {
         &self,
         index: u32,
         size: i32,
-        type_: u32,
+        type_: ScalarType,
         normalized: bool,
         stride: i32,
         offset: i32,
@@ -131,7 +291,7 @@ impl Backend for Context {
             gl::VertexAttribPointer(
                 index,
                 size,
-                type_,
+                scalar_type(type_),
                 u8::from(normalized),
                 stride,
                 offset as *const c_void,
@@ -156,3 +316,133 @@ impl Backend for Context {
         }
     }
 }
+
+// KHR_debug is core since 4.3, macOS stops at 4.1
+fn enable_debug_output() {
+    if !gl::DebugMessageCallback::is_loaded() {
+        log::warn!("GL debug output is not supported by the context");
+        return;
+    }
+
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(Some(debug_message), ptr::null());
+    }
+}
+
+extern "system" fn debug_message(
+    source: GLenum,
+    type_: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    _length: GLsizei,
+    message: *const GLchar,
+    _user_param: *mut c_void,
+) {
+    let level = match severity {
+        gl::DEBUG_SEVERITY_HIGH => log::Level::Error,
+        gl::DEBUG_SEVERITY_MEDIUM => log::Level::Warn,
+        gl::DEBUG_SEVERITY_LOW => log::Level::Info,
+        _ => log::Level::Debug,
+    };
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+
+    log::log!(
+        level,
+        "GL {} {} {}: {}",
+        debug_source(source),
+        debug_type(type_),
+        id,
+        message
+    );
+}
+
+fn debug_source(source: GLenum) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API => "api",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    }
+}
+
+fn debug_type(type_: GLenum) -> &'static str {
+    match type_ {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        gl::DEBUG_TYPE_PUSH_GROUP => "push group",
+        gl::DEBUG_TYPE_POP_GROUP => "pop group",
+        _ => "other",
+    }
+}
+
+fn get_string(name: GLenum) -> String {
+    unsafe {
+        let value = gl::GetString(name);
+        if value.is_null() {
+            return String::new();
+        }
+        CStr::from_ptr(value as *const _)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+fn get_integer(name: GLenum) -> u32 {
+    let mut value = 0;
+    unsafe { gl::GetIntegerv(name, &mut value) }
+    value as u32
+}
+
+fn buffer_target(target: BufferTarget) -> GLenum {
+    match target {
+        BufferTarget::Array => gl::ARRAY_BUFFER,
+        BufferTarget::ElementArray => gl::ELEMENT_ARRAY_BUFFER,
+    }
+}
+
+fn buffer_usage(usage: BufferUsage) -> GLenum {
+    match usage {
+        BufferUsage::Static => gl::STATIC_DRAW,
+        BufferUsage::Dynamic => gl::DYNAMIC_DRAW,
+        BufferUsage::Stream => gl::STREAM_DRAW,
+    }
+}
+
+fn primitive_topology(topology: PrimitiveTopology) -> GLenum {
+    match topology {
+        PrimitiveTopology::Points => gl::POINTS,
+        PrimitiveTopology::Lines => gl::LINES,
+        PrimitiveTopology::LineStrip => gl::LINE_STRIP,
+        PrimitiveTopology::Triangles => gl::TRIANGLES,
+        PrimitiveTopology::TriangleStrip => gl::TRIANGLE_STRIP,
+        PrimitiveTopology::TriangleFan => gl::TRIANGLE_FAN,
+    }
+}
+
+fn index_type_enum(index_type: IndexType) -> GLenum {
+    match index_type {
+        IndexType::U16 => gl::UNSIGNED_SHORT,
+        IndexType::U32 => gl::UNSIGNED_INT,
+    }
+}
+
+fn scalar_type(type_: ScalarType) -> GLenum {
+    match type_ {
+        ScalarType::I8 => gl::BYTE,
+        ScalarType::U8 => gl::UNSIGNED_BYTE,
+        ScalarType::I16 => gl::SHORT,
+        ScalarType::U16 => gl::UNSIGNED_SHORT,
+        ScalarType::I32 => gl::INT,
+        ScalarType::U32 => gl::UNSIGNED_INT,
+        ScalarType::F16 => gl::HALF_FLOAT,
+        ScalarType::F32 => gl::FLOAT,
+    }
+}
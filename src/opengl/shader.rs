@@ -1,58 +1,180 @@
+use super::Context;
 use crate::{
-    backend::Backend,
-    common::{Shader, ShaderProgram, ShaderUtils},
+    common::{ShaderUtils, TextureUnit, Uniform, UniformInfo, UniformType},
     SHADERS,
 };
-use gl::types::{GLchar, GLint};
+use gl::types::{GLchar, GLenum, GLint, GLsizei};
 use std::{ffi::CString, ptr, str};
 
-impl Shader<[f32; 3]> for ShaderProgram {
-    fn set_uniform(&self, name: &str, input: [f32; 3]) {
-        let loc: i32;
+const SHADER_HEADER: &str = "#version 330 core\n";
 
-        unsafe {
-            let c_str = CString::new(name.as_bytes()).unwrap();
-            loc = gl::GetUniformLocation(self.id, c_str.as_ptr());
-            gl::UseProgram(self.id);
-            gl::Uniform3f(loc, input[0], input[1], input[2]);
-        };
-    }
+// glUniform* writes to the current program. Slices are uploaded with the
+// count of elements, matrices are row-major and uploaded without transpose.
+macro_rules! uniform {
+    ($($input:ty => |$location:ident, $value:ident| $body:expr;)*) => {
+        $(
+            impl Uniform<$input> for Context {
+                fn set_uniform(
+                    &self,
+                    program: &Self::Program,
+                    location: &Self::UniformLocation,
+                    input: $input,
+                ) {
+                    let ($location, $value) = (*location, input);
+
+                    unsafe {
+                        gl::UseProgram(*program);
+                        $body;
+                    }
+                }
+            }
+        )*
+    };
 }
 
-impl Shader<[[f32; 3]; 3]> for ShaderProgram {
-    fn set_uniform(&self, name: &str, input: [[f32; 3]; 3]) {
-        let loc: i32;
+uniform! {
+    f32 => |location, input| gl::Uniform1f(location, input);
+    [f32; 2] => |location, input| gl::Uniform2f(location, input[0], input[1]);
+    [f32; 3] => |location, input| gl::Uniform3f(location, input[0], input[1], input[2]);
+    [f32; 4] => |location, input| gl::Uniform4f(location, input[0], input[1], input[2], input[3]);
+    i32 => |location, input| gl::Uniform1i(location, input);
+    [i32; 2] => |location, input| gl::Uniform2i(location, input[0], input[1]);
+    [i32; 3] => |location, input| gl::Uniform3i(location, input[0], input[1], input[2]);
+    [i32; 4] => |location, input| gl::Uniform4i(location, input[0], input[1], input[2], input[3]);
+    u32 => |location, input| gl::Uniform1ui(location, input);
+    [u32; 2] => |location, input| gl::Uniform2ui(location, input[0], input[1]);
+    [u32; 3] => |location, input| gl::Uniform3ui(location, input[0], input[1], input[2]);
+    [u32; 4] => |location, input| gl::Uniform4ui(location, input[0], input[1], input[2], input[3]);
+    bool => |location, input| gl::Uniform1i(location, input as GLint);
+    [[f32; 2]; 2] => |location, input| gl::UniformMatrix2fv(location, 1, 0, &input[0][0]);
+    [[f32; 3]; 3] => |location, input| gl::UniformMatrix3fv(location, 1, 0, &input[0][0]);
+    [[f32; 4]; 4] => |location, input| gl::UniformMatrix4fv(location, 1, 0, &input[0][0]);
+    TextureUnit => |location, input| gl::Uniform1i(location, input.0 as GLint);
+    &[f32] => |location, input| gl::Uniform1fv(location, count(input), input.as_ptr());
+    &[[f32; 2]] => |location, input| gl::Uniform2fv(location, count(input), input.as_ptr().cast());
+    &[[f32; 3]] => |location, input| gl::Uniform3fv(location, count(input), input.as_ptr().cast());
+    &[[f32; 4]] => |location, input| gl::Uniform4fv(location, count(input), input.as_ptr().cast());
+    &[i32] => |location, input| gl::Uniform1iv(location, count(input), input.as_ptr());
+    &[[i32; 2]] => |location, input| gl::Uniform2iv(location, count(input), input.as_ptr().cast());
+    &[[i32; 3]] => |location, input| gl::Uniform3iv(location, count(input), input.as_ptr().cast());
+    &[[i32; 4]] => |location, input| gl::Uniform4iv(location, count(input), input.as_ptr().cast());
+    &[u32] => |location, input| gl::Uniform1uiv(location, count(input), input.as_ptr());
+    &[[u32; 2]] => |location, input| gl::Uniform2uiv(location, count(input), input.as_ptr().cast());
+    &[[u32; 3]] => |location, input| gl::Uniform3uiv(location, count(input), input.as_ptr().cast());
+    &[[u32; 4]] => |location, input| gl::Uniform4uiv(location, count(input), input.as_ptr().cast());
+    &[bool] => |location, input| {
+        let input: Vec<GLint> = input.iter().map(|&value| value as GLint).collect();
+        gl::Uniform1iv(location, count(&input), input.as_ptr())
+    };
+    &[[[f32; 2]; 2]] => |location, input| {
+        gl::UniformMatrix2fv(location, count(input), 0, input.as_ptr().cast())
+    };
+    &[[[f32; 3]; 3]] => |location, input| {
+        gl::UniformMatrix3fv(location, count(input), 0, input.as_ptr().cast())
+    };
+    &[[[f32; 4]; 4]] => |location, input| {
+        gl::UniformMatrix4fv(location, count(input), 0, input.as_ptr().cast())
+    };
+    &[TextureUnit] => |location, input| {
+        let input: Vec<GLint> = input.iter().map(|unit| unit.0 as GLint).collect();
+        gl::Uniform1iv(location, count(&input), input.as_ptr())
+    };
+}
 
-        unsafe {
-            let c_str = CString::new(name.as_bytes()).unwrap();
-            loc = gl::GetUniformLocation(self.id, c_str.as_ptr());
-            gl::UseProgram(self.id);
-            gl::UniformMatrix3fv(loc, 1, 0, &input[0][0]);
-        };
-    }
+fn count<T>(input: &[T]) -> GLsizei {
+    input.len() as GLsizei
 }
 
-impl Shader<[[f32; 4]; 4]> for ShaderProgram {
-    fn set_uniform(&self, name: &str, input: [[f32; 4]; 4]) {
-        let loc: i32;
+impl ShaderUtils for Context {
+    fn compile_program(&self, name: &str) -> Self::Program {
+        let fragment_shader = compile_shader(gl::FRAGMENT_SHADER, name).unwrap();
+        let vertex_shader = compile_shader(gl::VERTEX_SHADER, name).unwrap();
+
+        link_program(vertex_shader, fragment_shader)
+    }
+
+    fn active_uniforms(&self, program: &Self::Program) -> Vec<UniformInfo> {
+        let mut count = 0;
+        let mut max_length = 0;
 
         unsafe {
-            let c_str = CString::new(name.as_bytes()).unwrap();
-            loc = gl::GetUniformLocation(self.id, c_str.as_ptr());
-            gl::UseProgram(self.id);
-            gl::UniformMatrix4fv(loc, 1, 0, &input[0][0]);
-        };
+            gl::GetProgramiv(*program, gl::ACTIVE_UNIFORMS, &mut count);
+            gl::GetProgramiv(*program, gl::ACTIVE_UNIFORM_MAX_LENGTH, &mut max_length);
+        }
+
+        (0..count as u32)
+            .map(|index| {
+                let mut name = vec![0u8; max_length as usize];
+                let mut length = 0;
+                let mut size = 0;
+                let mut uniform_type = 0;
+
+                unsafe {
+                    gl::GetActiveUniform(
+                        *program,
+                        index,
+                        max_length,
+                        &mut length,
+                        &mut size,
+                        &mut uniform_type,
+                        name.as_mut_ptr() as *mut GLchar,
+                    );
+                }
+                name.truncate(length as usize);
+                let name = String::from_utf8_lossy(&name);
+
+                // Arrays are listed as their first element
+                UniformInfo {
+                    name: String::from(name.strip_suffix("[0]").unwrap_or(&name)),
+                    uniform_type: to_uniform_type(uniform_type),
+                    array_size: size as u32,
+                }
+            })
+            .collect()
     }
-}
 
-pub struct ShaderLib;
+    fn uniform_location(&self, program: &Self::Program, name: &str) -> Self::UniformLocation {
+        let c_str = CString::new(name.as_bytes()).unwrap();
 
-impl ShaderUtils for ShaderLib {
-    fn compile_program(name: &str) -> <super::Context as Backend>::Program {
-        let fragment_shader = compile_shader(gl::FRAGMENT_SHADER, name).unwrap();
-        let vertex_shader = compile_shader(gl::VERTEX_SHADER, name).unwrap();
+        unsafe { gl::GetUniformLocation(*program, c_str.as_ptr()) }
+    }
+}
 
-        link_program(vertex_shader, fragment_shader)
+fn to_uniform_type(uniform_type: GLenum) -> UniformType {
+    match uniform_type {
+        gl::FLOAT => UniformType::Float,
+        gl::FLOAT_VEC2 => UniformType::Vec2,
+        gl::FLOAT_VEC3 => UniformType::Vec3,
+        gl::FLOAT_VEC4 => UniformType::Vec4,
+        gl::INT => UniformType::Int,
+        gl::INT_VEC2 => UniformType::IVec2,
+        gl::INT_VEC3 => UniformType::IVec3,
+        gl::INT_VEC4 => UniformType::IVec4,
+        gl::UNSIGNED_INT => UniformType::UInt,
+        gl::UNSIGNED_INT_VEC2 => UniformType::UVec2,
+        gl::UNSIGNED_INT_VEC3 => UniformType::UVec3,
+        gl::UNSIGNED_INT_VEC4 => UniformType::UVec4,
+        gl::BOOL => UniformType::Bool,
+        gl::BOOL_VEC2 => UniformType::BVec2,
+        gl::BOOL_VEC3 => UniformType::BVec3,
+        gl::BOOL_VEC4 => UniformType::BVec4,
+        gl::FLOAT_MAT2 => UniformType::Mat2,
+        gl::FLOAT_MAT3 => UniformType::Mat3,
+        gl::FLOAT_MAT4 => UniformType::Mat4,
+        gl::SAMPLER_2D
+        | gl::INT_SAMPLER_2D
+        | gl::UNSIGNED_INT_SAMPLER_2D
+        | gl::SAMPLER_2D_SHADOW => UniformType::Sampler2D,
+        gl::SAMPLER_3D | gl::INT_SAMPLER_3D | gl::UNSIGNED_INT_SAMPLER_3D => UniformType::Sampler3D,
+        gl::SAMPLER_CUBE
+        | gl::INT_SAMPLER_CUBE
+        | gl::UNSIGNED_INT_SAMPLER_CUBE
+        | gl::SAMPLER_CUBE_SHADOW => UniformType::SamplerCube,
+        gl::SAMPLER_2D_ARRAY
+        | gl::INT_SAMPLER_2D_ARRAY
+        | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY
+        | gl::SAMPLER_2D_ARRAY_SHADOW => UniformType::Sampler2DArray,
+        _ => UniformType::Other,
     }
 }
 
@@ -69,6 +191,7 @@ fn compile_shader(shader_type: u32, name: &str) -> Result<u32, String> {
         }
     )
     .as_str()];
+    let shader_source = format!("{}{}", SHADER_HEADER, shader_source);
 
     unsafe {
         let c_str = CString::new(shader_source.as_bytes()).unwrap();
@@ -0,0 +1,261 @@
+use crate::{backend::Backend, common::QueryResult, lib::Query, BACKEND};
+use std::{collections::VecDeque, time::Duration};
+
+const HISTORY: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct ScopeTiming {
+    pub name: String,
+    pub depth: usize,
+    pub cpu: Duration,
+    pub gpu: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameTimings {
+    pub frame: u64,
+    pub scopes: Vec<ScopeTiming>,
+}
+
+struct Scope {
+    name: String,
+    depth: usize,
+    cpu_start: Duration,
+    cpu: Duration,
+    query: Option<Query>,
+}
+
+struct Frame {
+    frame: u64,
+    scopes: Vec<Scope>,
+}
+
+// Timer query results are read back a few frames later, once the GPU got to
+// them, so collecting them never stalls. Frames are reported in order.
+pub struct Profiler {
+    gpu_timing: bool,
+    frame: u64,
+    current: Option<Frame>,
+    open: Vec<usize>,
+    pending: VecDeque<Frame>,
+    free_queries: Vec<Query>,
+    completed: VecDeque<FrameTimings>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::with_gpu_timing(BACKEND.lock().unwrap().capabilities().timer_query)
+    }
+
+    fn with_gpu_timing(gpu_timing: bool) -> Profiler {
+        Profiler {
+            gpu_timing,
+            frame: 0,
+            current: None,
+            open: Vec::new(),
+            pending: VecDeque::new(),
+            free_queries: Vec::new(),
+            completed: VecDeque::new(),
+        }
+    }
+
+    pub fn begin_frame(&mut self) {
+        assert!(self.current.is_none(), "begin_frame called twice");
+
+        self.current = Some(Frame {
+            frame: self.frame,
+            scopes: Vec::new(),
+        });
+    }
+
+    pub fn end_frame(&mut self) {
+        assert!(self.open.is_empty(), "end_frame called with open scopes");
+
+        let frame = self
+            .current
+            .take()
+            .expect("end_frame called without begin_frame");
+        self.pending.push_back(frame);
+        self.frame += 1;
+
+        self.collect();
+    }
+
+    pub fn begin_scope(&mut self, name: &str) {
+        let on_gpu = self.next_scope_on_gpu();
+        let frame = self
+            .current
+            .as_mut()
+            .expect("begin_scope called outside of a frame");
+
+        let query = if on_gpu {
+            let backend = BACKEND.lock().unwrap();
+            let query = self
+                .free_queries
+                .pop()
+                .unwrap_or_else(|| backend.create_timer_query());
+            backend.begin_timer_query(&query);
+            Some(query)
+        } else {
+            None
+        };
+
+        self.open.push(frame.scopes.len());
+        frame.scopes.push(Scope {
+            name: String::from(name),
+            depth: self.open.len() - 1,
+            cpu_start: now(),
+            cpu: Duration::ZERO,
+            query,
+        });
+    }
+
+    pub fn end_scope(&mut self) {
+        let index = self.open.pop().expect("end_scope called without a scope");
+        let scope = &mut self.current.as_mut().unwrap().scopes[index];
+
+        scope.cpu = now().saturating_sub(scope.cpu_start);
+        if let Some(query) = &scope.query {
+            BACKEND.lock().unwrap().end_timer_query(query);
+        }
+    }
+
+    // Timer queries can't be nested, only the outermost scopes are measured
+    // on the GPU
+    fn next_scope_on_gpu(&self) -> bool {
+        self.gpu_timing && self.open.is_empty()
+    }
+
+    pub fn scope<T>(&mut self, name: &str, f: impl FnOnce(&mut Profiler) -> T) -> T {
+        self.begin_scope(name);
+        let result = f(self);
+        self.end_scope();
+        result
+    }
+
+    pub fn latest(&self) -> Option<&FrameTimings> {
+        self.completed.back()
+    }
+
+    pub fn drain(&mut self) -> Vec<FrameTimings> {
+        self.completed.drain(..).collect()
+    }
+
+    fn collect(&mut self) {
+        while let Some(frame) = self.pending.front() {
+            // Without queries the backend isn't needed at all
+            let results = frame
+                .scopes
+                .iter()
+                .map(|scope| {
+                    scope
+                        .query
+                        .as_ref()
+                        .map(|query| BACKEND.lock().unwrap().timer_query_result(query))
+                })
+                .collect::<Vec<_>>();
+
+            if results.contains(&Some(QueryResult::Pending)) {
+                return;
+            }
+
+            let frame = self.pending.pop_front().unwrap();
+            let scopes = frame
+                .scopes
+                .into_iter()
+                .zip(results)
+                .map(|(scope, result)| {
+                    if let Some(query) = scope.query {
+                        self.free_queries.push(query);
+                    }
+
+                    ScopeTiming {
+                        name: scope.name,
+                        depth: scope.depth,
+                        cpu: scope.cpu,
+                        gpu: match result {
+                            Some(QueryResult::Ready(elapsed)) => Some(elapsed),
+                            _ => None,
+                        },
+                    }
+                })
+                .collect();
+
+            self.completed.push_back(FrameTimings {
+                frame: frame.frame,
+                scopes,
+            });
+            if self.completed.len() > HISTORY {
+                self.completed.pop_front();
+            }
+        }
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::new()
+    }
+}
+
+// std::time::Instant is not available in the browser
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn now() -> Duration {
+    use once_cell::sync::Lazy;
+    use std::time::Instant;
+
+    static START: Lazy<Instant> = Lazy::new(Instant::now);
+    START.elapsed()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn now() -> Duration {
+    let milliseconds = web_sys::window()
+        .and_then(|window| window.performance())
+        .map(|performance| performance.now())
+        .unwrap_or_default();
+
+    Duration::from_secs_f64(milliseconds / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_scopes() {
+        let mut profiler = Profiler::with_gpu_timing(false);
+
+        for _ in 0..HISTORY + 4 {
+            profiler.begin_frame();
+            profiler.scope("outer", |profiler| profiler.scope("inner", |_| ()));
+            profiler.scope("other", |_| ());
+            profiler.end_frame();
+        }
+
+        let frames = profiler.drain();
+        assert_eq!(frames.len(), HISTORY);
+        assert_eq!(frames[0].frame, 4);
+        assert_eq!(frames[HISTORY - 1].frame, HISTORY as u64 + 3);
+
+        let scopes: Vec<(&str, usize)> = frames[0]
+            .scopes
+            .iter()
+            .map(|scope| (scope.name.as_str(), scope.depth))
+            .collect();
+        assert_eq!(scopes, [("outer", 0), ("inner", 1), ("other", 0)]);
+        assert!(frames[0].scopes.iter().all(|scope| scope.gpu.is_none()));
+        assert!(profiler.latest().is_none());
+    }
+
+    #[test]
+    fn only_outermost_scopes_on_gpu() {
+        let mut profiler = Profiler::with_gpu_timing(true);
+        assert!(profiler.next_scope_on_gpu());
+
+        profiler.open.push(0);
+        assert!(!profiler.next_scope_on_gpu());
+
+        assert!(!Profiler::with_gpu_timing(false).next_scope_on_gpu());
+    }
+}
@@ -1,15 +1,39 @@
-use crate::BACKEND;
+use crate::{debug, BACKEND};
 
-use super::entity::Entity;
+use super::{
+    entity::{Entity, InstancedEntity},
+    lod::LodGroup,
+};
 
 pub trait Drawable {
     fn draw(&self);
 }
 
+// Drawn in the mode set with `debug::set_render_mode`
 impl Drawable for Vec<Entity> {
     fn draw(&self) {
         for entity in self {
-            entity.draw(&BACKEND.lock().unwrap()); // TODO: This should be BACNEKD.draw(entity) instead....
+            debug::draw_entity(entity);
+        }
+    }
+}
+
+// Always shaded, the debug render modes don't apply to instances
+impl Drawable for Vec<InstancedEntity> {
+    fn draw(&self) {
+        for entity in self {
+            entity.draw(&BACKEND.lock().unwrap());
+        }
+    }
+}
+
+// Every group draws the level picked for the viewer set with
+// `lod::set_viewer`. The levels take the render mode too, but aren't
+// cross-faded outside of `RenderMode::Shaded`.
+impl Drawable for Vec<LodGroup> {
+    fn draw(&self) {
+        for group in self {
+            group.draw();
         }
     }
 }
@@ -0,0 +1,1453 @@
+pub mod shader;
+
+use crate::{
+    types::{BufferTarget, BufferUsage, IndexType, PolygonMode, PrimitiveTopology, ScalarType},
+    BACKEND,
+};
+
+use super::{
+    backend::Backend,
+    common::{Capabilities, QueryResult},
+    WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use ash::vk;
+use shader::{CompiledShader, UniformBlock};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap},
+    ffi::CStr,
+};
+
+const COLOR_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+const DEPTH_FORMAT: vk::Format = vk::Format::D32_SFLOAT;
+
+struct Image {
+    image: vk::Image,
+    #[allow(unused)]
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+}
+
+struct Buffer {
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    size: u64,
+    mapped: *mut u8,
+}
+
+struct StageUniforms {
+    block: UniformBlock,
+    values: Vec<u8>,
+}
+
+pub struct Program {
+    vertex_module: vk::ShaderModule,
+    fragment_module: vk::ShaderModule,
+    uniforms: Vec<StageUniforms>,
+    #[allow(unused)]
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    #[allow(unused)]
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: Option<vk::DescriptorSet>,
+    layout: vk::PipelineLayout,
+}
+
+#[derive(Default)]
+struct VertexArray {
+    attributes: BTreeMap<u32, VertexAttribute>,
+    element_buffer: Option<usize>,
+}
+
+#[derive(Default)]
+struct VertexAttribute {
+    enabled: bool,
+    pointer: Option<VertexPointer>,
+    divisor: u32,
+}
+
+#[derive(Clone, Copy)]
+struct VertexPointer {
+    buffer: usize,
+    format: vk::Format,
+    stride: u32,
+    offset: u32,
+}
+
+struct VertexBinding {
+    buffer: usize,
+    base: u32,
+    stride: u32,
+    input_rate: vk::VertexInputRate,
+    attributes: Vec<(u32, vk::Format, u32)>,
+}
+
+struct Draw {
+    program: usize,
+    vao: usize,
+    topology: PrimitiveTopology,
+    first: u32,
+    count: u32,
+    instances: u32,
+    indices: Option<(usize, IndexType)>,
+    uniform_offsets: Vec<u32>,
+}
+
+pub struct Context {
+    #[allow(unused)]
+    entry: ash::Entry,
+    #[allow(unused)]
+    instance: ash::Instance,
+    device: ash::Device,
+    capabilities: Capabilities,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    uniform_alignment: u64,
+    queue: vk::Queue,
+    #[allow(unused)]
+    command_pool: vk::CommandPool,
+    command_buffer: vk::CommandBuffer,
+    fence: vk::Fence,
+    color: Image,
+    #[allow(unused)]
+    depth: Image,
+    render_pass_clear: vk::RenderPass,
+    render_pass_load: vk::RenderPass,
+    framebuffer: vk::Framebuffer,
+    clear_color: [f32; 4],
+    clear_requested: Cell<bool>,
+    drawn: bool,
+    buffers: RefCell<Vec<Option<Buffer>>>,
+    vaos: RefCell<Vec<VertexArray>>,
+    programs: RefCell<Vec<Program>>,
+    pipelines: RefCell<HashMap<(usize, usize, PrimitiveTopology), vk::Pipeline>>,
+    bound_buffer: Cell<Option<usize>>,
+    bound_vao: Cell<Option<usize>>,
+    bound_program: Cell<Option<usize>>,
+    draws: RefCell<Vec<Draw>>,
+    uniform_data: RefCell<Vec<u8>>,
+    uniform_buffer: Buffer,
+}
+
+impl Backend for Context {
+    type Context = Context;
+    type Buffer = usize;
+    type Program = usize;
+    type Shader = vk::ShaderModule;
+    type Vao = usize;
+    type Query = ();
+    // The offsets in the uniform blocks of the program's stages
+    type UniformLocation = [Option<u32>; 2];
+
+    fn new() -> Self::Context {
+        unsafe {
+            let entry = ash::Entry::load().expect("Failed to load the Vulkan library.");
+
+            let application_info = vk::ApplicationInfo::default()
+                .application_name(c"reindeer")
+                .engine_name(c"reindeer")
+                .api_version(vk::API_VERSION_1_0);
+            let instance = entry
+                .create_instance(
+                    &vk::InstanceCreateInfo::default().application_info(&application_info),
+                    None,
+                )
+                .expect("Failed to create Vulkan instance.");
+
+            let (physical_device, queue_family) = pick_physical_device(&instance);
+            let properties = instance.get_physical_device_properties(physical_device);
+            let capabilities = capabilities(&instance, physical_device, &properties);
+            let memory_properties = instance.get_physical_device_memory_properties(physical_device);
+
+            // Sizes other than 1 for point lists
+            let features = instance.get_physical_device_features(physical_device);
+            let enabled_features = vk::PhysicalDeviceFeatures::default()
+                .large_points(features.large_points == vk::TRUE);
+
+            let priorities = [1.0];
+            let queue_info = vk::DeviceQueueCreateInfo::default()
+                .queue_family_index(queue_family)
+                .queue_priorities(&priorities);
+            let device = instance
+                .create_device(
+                    physical_device,
+                    &vk::DeviceCreateInfo::default()
+                        .queue_create_infos(&[queue_info])
+                        .enabled_features(&enabled_features),
+                    None,
+                )
+                .expect("Failed to create Vulkan device.");
+            let queue = device.get_device_queue(queue_family, 0);
+
+            let command_pool = device
+                .create_command_pool(
+                    &vk::CommandPoolCreateInfo::default()
+                        .queue_family_index(queue_family)
+                        .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER),
+                    None,
+                )
+                .unwrap();
+            let command_buffer = device
+                .allocate_command_buffers(
+                    &vk::CommandBufferAllocateInfo::default()
+                        .command_pool(command_pool)
+                        .level(vk::CommandBufferLevel::PRIMARY)
+                        .command_buffer_count(1),
+                )
+                .unwrap()[0];
+            let fence = device
+                .create_fence(&vk::FenceCreateInfo::default(), None)
+                .unwrap();
+
+            let color = create_image(
+                &device,
+                &memory_properties,
+                COLOR_FORMAT,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+                vk::ImageAspectFlags::COLOR,
+            );
+            let depth = create_image(
+                &device,
+                &memory_properties,
+                DEPTH_FORMAT,
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                vk::ImageAspectFlags::DEPTH,
+            );
+
+            // Both passes are compatible, so pipelines and the framebuffer
+            // created with one of them work with the other as well.
+            let render_pass_clear = create_render_pass(&device, true);
+            let render_pass_load = create_render_pass(&device, false);
+
+            let attachments = [color.view, depth.view];
+            let framebuffer = device
+                .create_framebuffer(
+                    &vk::FramebufferCreateInfo::default()
+                        .render_pass(render_pass_clear)
+                        .attachments(&attachments)
+                        .width(WINDOW_WIDTH as u32)
+                        .height(WINDOW_HEIGHT as u32)
+                        .layers(1),
+                    None,
+                )
+                .unwrap();
+
+            let uniform_buffer = create_buffer(
+                &device,
+                &memory_properties,
+                1 << 16,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+            );
+
+            Context {
+                entry,
+                instance,
+                device,
+                capabilities,
+                memory_properties,
+                uniform_alignment: properties.limits.min_uniform_buffer_offset_alignment,
+                queue,
+                command_pool,
+                command_buffer,
+                fence,
+                color,
+                depth,
+                render_pass_clear,
+                render_pass_load,
+                framebuffer,
+                clear_color: [0.0, 0.0, 0.0, 0.0],
+                clear_requested: Cell::new(false),
+                drawn: false,
+                buffers: RefCell::new(Vec::new()),
+                vaos: RefCell::new(Vec::new()),
+                programs: RefCell::new(Vec::new()),
+                pipelines: RefCell::new(HashMap::new()),
+                bound_buffer: Cell::new(None),
+                bound_vao: Cell::new(None),
+                bound_program: Cell::new(None),
+                draws: RefCell::new(Vec::new()),
+                uniform_data: RefCell::new(Vec::new()),
+                uniform_buffer,
+            }
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities.clone()
+    }
+
+    fn use_program(&self, program: &Self::Program) {
+        self.bound_program.set(Some(*program));
+    }
+
+    fn create_buffer(&self) -> Self::Buffer {
+        let mut buffers = self.buffers.borrow_mut();
+        buffers.push(None);
+        buffers.len() - 1
+    }
+
+    fn bind_buffer(&self, target: BufferTarget, buffer: &Self::Buffer) {
+        match target {
+            BufferTarget::Array => self.bound_buffer.set(Some(*buffer)),
+            // The element buffer binding is part of the vertex array in GL
+            BufferTarget::ElementArray => {
+                self.with_bound_vao(|vao| vao.element_buffer = Some(*buffer))
+            }
+        }
+    }
+
+    fn buffer_data(&self, data: &[u8], target: BufferTarget, _usage: BufferUsage) {
+        let index = self
+            .bound_buffer_for(target)
+            .expect("buffer_data called without a bound buffer");
+
+        let buffer = create_buffer(
+            &self.device,
+            &self.memory_properties,
+            data.len() as u64,
+            vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::INDEX_BUFFER,
+        );
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), buffer.mapped, data.len());
+        }
+
+        if let Some(old) = self.buffers.borrow_mut()[index].replace(buffer) {
+            destroy_buffer(&self.device, old);
+        }
+    }
+
+    // The previous frame was waited for and this one's draws are replayed in
+    // after_draw, so the mapped memory can be written right away. Every draw
+    // of the frame sees the last update.
+    fn buffer_sub_data(&self, data: &[u8], target: BufferTarget, offset: i32) {
+        let index = self
+            .bound_buffer_for(target)
+            .expect("buffer_sub_data called without a bound buffer");
+        let buffers = self.buffers.borrow();
+        let buffer = buffers[index]
+            .as_ref()
+            .expect("buffer_sub_data called on a buffer without data");
+
+        assert!(
+            offset as u64 + data.len() as u64 <= buffer.size,
+            "buffer_sub_data writes past the end of the buffer"
+        );
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                buffer.mapped.add(offset as usize),
+                data.len(),
+            );
+        }
+    }
+
+    fn create_vertex_array(&self) -> Self::Vao {
+        let mut vaos = self.vaos.borrow_mut();
+        vaos.push(VertexArray::default());
+        vaos.len() - 1
+    }
+
+    fn bind_vertex_array(&self, vao: &Self::Vao) {
+        self.bound_vao.set(Some(*vao));
+    }
+
+    fn vertex_attrib_pointer(
+        &self,
+        index: u32,
+        size: i32,
+        type_: ScalarType,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    ) {
+        let format = vertex_format(size, type_, normalized);
+
+        let pointer = VertexPointer {
+            buffer: self
+                .bound_buffer
+                .get()
+                .expect("vertex_attrib_pointer called without a bound buffer"),
+            format,
+            stride: match stride {
+                0 => type_.size() * size as u32,
+                _ => stride as u32,
+            },
+            offset: offset as u32,
+        };
+
+        self.with_bound_vao(|vao| {
+            vao.attributes.entry(index).or_default().pointer = Some(pointer);
+        });
+    }
+
+    // Draws are recorded and replayed in after_draw, there is nothing to
+    // time while they are issued. `Capabilities::timer_query` is false.
+    fn create_timer_query(&self) -> Self::Query {
+        panic!("Timer queries are not supported by the vulkan backend");
+    }
+
+    fn begin_timer_query(&self, _query: &Self::Query) {
+        panic!("Timer queries are not supported by the vulkan backend");
+    }
+
+    fn end_timer_query(&self, _query: &Self::Query) {
+        panic!("Timer queries are not supported by the vulkan backend");
+    }
+
+    fn timer_query_result(&self, _query: &Self::Query) -> QueryResult {
+        panic!("Timer queries are not supported by the vulkan backend");
+    }
+
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        self.with_bound_vao(|vao| {
+            vao.attributes.entry(index).or_default().enabled = true;
+        });
+    }
+
+    // Attributes either advance per vertex or per instance, there are no
+    // divisors above 1
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        assert!(
+            divisor <= 1,
+            "The vulkan backend only supports vertex attribute divisors of 0 and 1"
+        );
+
+        self.with_bound_vao(|vao| {
+            vao.attributes.entry(index).or_default().divisor = divisor;
+        });
+    }
+
+    fn draw_arrays(&self, topology: PrimitiveTopology, first: i32, vertex_count: i32) {
+        self.draw_arrays_instanced(topology, first, vertex_count, 1);
+    }
+
+    fn draw_elements(
+        &self,
+        topology: PrimitiveTopology,
+        index_count: i32,
+        index_type: IndexType,
+        offset: i32,
+    ) {
+        self.draw_elements_instanced(topology, index_count, index_type, offset, 1);
+    }
+
+    fn draw_arrays_instanced(
+        &self,
+        topology: PrimitiveTopology,
+        first: i32,
+        vertex_count: i32,
+        instance_count: i32,
+    ) {
+        self.record_draw(
+            topology,
+            first as u32,
+            vertex_count as u32,
+            instance_count as u32,
+            None,
+        );
+    }
+
+    fn draw_elements_instanced(
+        &self,
+        topology: PrimitiveTopology,
+        index_count: i32,
+        index_type: IndexType,
+        offset: i32,
+        instance_count: i32,
+    ) {
+        let element_buffer = self
+            .bound_vao
+            .get()
+            .and_then(|vao| self.vaos.borrow()[vao].element_buffer)
+            .expect("draw_elements called without a bound element buffer");
+
+        self.record_draw(
+            topology,
+            offset as u32 / index_type.size(),
+            index_count as u32,
+            instance_count as u32,
+            Some((element_buffer, index_type)),
+        );
+    }
+
+    fn set_clear_color(&mut self, red: f32, green: f32, blue: f32, alpha: f32) {
+        self.clear_color = [red, green, blue, alpha];
+    }
+
+    fn clear(&self) {
+        self.clear_requested.set(true);
+    }
+
+    // Polygon modes are pipeline state that needs fillModeNonSolid,
+    // `Capabilities::polygon_mode` is false
+    fn set_polygon_mode(&self, _mode: PolygonMode) {}
+
+    fn before_draw(&mut self) {
+        self.draws.get_mut().clear();
+        self.uniform_data.get_mut().clear();
+        self.clear();
+    }
+
+    fn after_draw(&mut self) {
+        let draws = std::mem::take(self.draws.get_mut());
+
+        self.create_pipelines(&draws);
+        self.upload_uniforms();
+
+        // Nothing was rendered into the attachments yet, so there is nothing to load
+        let clear = self.clear_requested.replace(false) || !self.drawn;
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: self.clear_color,
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+
+        let pipelines = self.pipelines.borrow();
+        let programs = self.programs.borrow();
+        let buffers = self.buffers.borrow();
+        let vaos = self.vaos.borrow();
+
+        unsafe {
+            let device = &self.device;
+            let command_buffer = self.command_buffer;
+
+            device
+                .begin_command_buffer(
+                    command_buffer,
+                    &vk::CommandBufferBeginInfo::default()
+                        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                )
+                .unwrap();
+
+            device.cmd_begin_render_pass(
+                command_buffer,
+                &vk::RenderPassBeginInfo::default()
+                    .render_pass(if clear {
+                        self.render_pass_clear
+                    } else {
+                        self.render_pass_load
+                    })
+                    .framebuffer(self.framebuffer)
+                    .render_area(window_rect())
+                    .clear_values(&clear_values),
+                vk::SubpassContents::INLINE,
+            );
+
+            device.cmd_set_viewport(
+                command_buffer,
+                0,
+                &[vk::Viewport {
+                    x: 0.0,
+                    y: 0.0,
+                    width: WINDOW_WIDTH as f32,
+                    height: WINDOW_HEIGHT as f32,
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                }],
+            );
+            device.cmd_set_scissor(command_buffer, 0, &[window_rect()]);
+
+            for draw in &draws {
+                let program = &programs[draw.program];
+
+                device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipelines[&(draw.program, draw.vao, draw.topology)],
+                );
+
+                if let Some(descriptor_set) = program.descriptor_set {
+                    device.cmd_bind_descriptor_sets(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        program.layout,
+                        0,
+                        &[descriptor_set],
+                        &draw.uniform_offsets,
+                    );
+                }
+
+                for (binding, layout) in vertex_bindings(&vaos[draw.vao]).iter().enumerate() {
+                    let buffer = buffers[layout.buffer]
+                        .as_ref()
+                        .expect("Vertex attribute points to a buffer without data");
+                    device.cmd_bind_vertex_buffers(
+                        command_buffer,
+                        binding as u32,
+                        &[buffer.buffer],
+                        &[layout.base as u64],
+                    );
+                }
+
+                match draw.indices {
+                    Some((buffer, index_type)) => {
+                        let buffer = buffers[buffer]
+                            .as_ref()
+                            .expect("Element buffer has no data");
+                        device.cmd_bind_index_buffer(
+                            command_buffer,
+                            buffer.buffer,
+                            0,
+                            vk_index_type(index_type),
+                        );
+                        device.cmd_draw_indexed(
+                            command_buffer,
+                            draw.count,
+                            draw.instances,
+                            draw.first,
+                            0,
+                            0,
+                        );
+                    }
+                    None => {
+                        device.cmd_draw(command_buffer, draw.count, draw.instances, draw.first, 0)
+                    }
+                }
+            }
+
+            device.cmd_end_render_pass(command_buffer);
+            device.end_command_buffer(command_buffer).unwrap();
+        }
+
+        self.submit_and_wait();
+        self.drawn = true;
+    }
+
+    fn draw_loop(mut draw_frame: impl FnMut() + 'static) {
+        loop {
+            BACKEND.lock().unwrap().before_draw();
+            draw_frame();
+            BACKEND.lock().unwrap().after_draw();
+        }
+    }
+}
+
+impl Context {
+    pub(crate) fn create_program(
+        &self,
+        vertex_shader: CompiledShader,
+        fragment_shader: CompiledShader,
+    ) -> usize {
+        let device = &self.device;
+
+        let uniforms: Vec<StageUniforms> = [vertex_shader.uniforms, fragment_shader.uniforms]
+            .into_iter()
+            .flatten()
+            .map(|block| StageUniforms {
+                values: vec![0; block.size as usize],
+                block,
+            })
+            .collect();
+
+        unsafe {
+            let vertex_module = device
+                .create_shader_module(
+                    &vk::ShaderModuleCreateInfo::default().code(&vertex_shader.spirv),
+                    None,
+                )
+                .unwrap();
+            let fragment_module = device
+                .create_shader_module(
+                    &vk::ShaderModuleCreateInfo::default().code(&fragment_shader.spirv),
+                    None,
+                )
+                .unwrap();
+
+            let bindings: Vec<vk::DescriptorSetLayoutBinding> = uniforms
+                .iter()
+                .map(|stage| {
+                    vk::DescriptorSetLayoutBinding::default()
+                        .binding(stage.block.binding)
+                        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+                        .descriptor_count(1)
+                        .stage_flags(vk::ShaderStageFlags::ALL_GRAPHICS)
+                })
+                .collect();
+
+            let descriptor_set_layout = device
+                .create_descriptor_set_layout(
+                    &vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings),
+                    None,
+                )
+                .unwrap();
+
+            let (descriptor_pool, descriptor_set) = if uniforms.is_empty() {
+                (vk::DescriptorPool::null(), None)
+            } else {
+                let pool_sizes = [vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+                    descriptor_count: uniforms.len() as u32,
+                }];
+                let pool = device
+                    .create_descriptor_pool(
+                        &vk::DescriptorPoolCreateInfo::default()
+                            .max_sets(1)
+                            .pool_sizes(&pool_sizes),
+                        None,
+                    )
+                    .unwrap();
+                let set = device
+                    .allocate_descriptor_sets(
+                        &vk::DescriptorSetAllocateInfo::default()
+                            .descriptor_pool(pool)
+                            .set_layouts(&[descriptor_set_layout]),
+                    )
+                    .unwrap()[0];
+                (pool, Some(set))
+            };
+
+            let layout = device
+                .create_pipeline_layout(
+                    &vk::PipelineLayoutCreateInfo::default().set_layouts(&[descriptor_set_layout]),
+                    None,
+                )
+                .unwrap();
+
+            let program = Program {
+                vertex_module,
+                fragment_module,
+                uniforms,
+                descriptor_set_layout,
+                descriptor_pool,
+                descriptor_set,
+                layout,
+            };
+            write_descriptor_set(device, &program, &self.uniform_buffer);
+
+            let mut programs = self.programs.borrow_mut();
+            programs.push(program);
+            programs.len() - 1
+        }
+    }
+
+    // A uniform declared in both stages is written to both blocks
+    pub(crate) fn write_uniform(&self, program: &usize, location: &[Option<u32>; 2], bytes: &[u8]) {
+        let mut programs = self.programs.borrow_mut();
+
+        for (stage, offset) in programs[*program].uniforms.iter_mut().zip(location) {
+            if let Some(offset) = offset {
+                let offset = *offset as usize;
+                stage.values[offset..offset + bytes.len()].copy_from_slice(bytes);
+            }
+        }
+    }
+
+    pub fn read_pixels(&self) -> Vec<u8> {
+        assert!(self.drawn, "read_pixels called before the first frame");
+
+        let size = WINDOW_WIDTH as u64 * WINDOW_HEIGHT as u64 * 4;
+        let readback = create_buffer(
+            &self.device,
+            &self.memory_properties,
+            size,
+            vk::BufferUsageFlags::TRANSFER_DST,
+        );
+
+        unsafe {
+            let device = &self.device;
+            device
+                .begin_command_buffer(
+                    self.command_buffer,
+                    &vk::CommandBufferBeginInfo::default()
+                        .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                )
+                .unwrap();
+            device.cmd_copy_image_to_buffer(
+                self.command_buffer,
+                self.color.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                readback.buffer,
+                &[vk::BufferImageCopy::default()
+                    .image_subresource(
+                        vk::ImageSubresourceLayers::default()
+                            .aspect_mask(vk::ImageAspectFlags::COLOR)
+                            .layer_count(1),
+                    )
+                    .image_extent(window_extent())],
+            );
+            device.end_command_buffer(self.command_buffer).unwrap();
+        }
+
+        self.submit_and_wait();
+
+        let pixels = unsafe { std::slice::from_raw_parts(readback.mapped, size as usize) }.to_vec();
+        destroy_buffer(&self.device, readback);
+
+        pixels
+    }
+
+    fn submit_and_wait(&self) {
+        let command_buffers = [self.command_buffer];
+        unsafe {
+            self.device
+                .queue_submit(
+                    self.queue,
+                    &[vk::SubmitInfo::default().command_buffers(&command_buffers)],
+                    self.fence,
+                )
+                .expect("Failed to submit Vulkan command buffer.");
+            self.device
+                .wait_for_fences(&[self.fence], true, u64::MAX)
+                .unwrap();
+            self.device.reset_fences(&[self.fence]).unwrap();
+        }
+    }
+
+    fn record_draw(
+        &self,
+        topology: PrimitiveTopology,
+        first: u32,
+        count: u32,
+        instances: u32,
+        indices: Option<(usize, IndexType)>,
+    ) {
+        let program = self
+            .bound_program
+            .get()
+            .expect("Draw called without a program in use");
+        let vao = self
+            .bound_vao
+            .get()
+            .expect("Draw called without a bound vertex array");
+
+        // Uniforms are program state in GL, so every draw gets a snapshot of
+        // the values the program has at the time of the call.
+        let programs = self.programs.borrow();
+        let mut data = self.uniform_data.borrow_mut();
+        let alignment = self.uniform_alignment as usize;
+
+        let uniform_offsets = programs[program]
+            .uniforms
+            .iter()
+            .map(|stage| {
+                let offset = data.len().div_ceil(alignment) * alignment;
+                data.resize(offset, 0);
+                data.extend_from_slice(&stage.values);
+                offset as u32
+            })
+            .collect();
+
+        self.draws.borrow_mut().push(Draw {
+            program,
+            vao,
+            topology,
+            first,
+            count,
+            instances,
+            indices,
+            uniform_offsets,
+        });
+    }
+
+    // The vertex layout is baked into the pipelines, they are created again
+    // after it changes. The previous frame was waited for, so none of them are
+    // in use.
+    fn with_bound_vao(&self, f: impl FnOnce(&mut VertexArray)) {
+        let vao = self.bound_vao.get().expect("No vertex array is bound");
+        f(&mut self.vaos.borrow_mut()[vao]);
+        self.pipelines
+            .borrow_mut()
+            .retain(|(_, pipeline_vao, _), pipeline| {
+                if *pipeline_vao == vao {
+                    unsafe { self.device.destroy_pipeline(*pipeline, None) };
+                }
+                *pipeline_vao != vao
+            });
+    }
+
+    fn bound_buffer_for(&self, target: BufferTarget) -> Option<usize> {
+        match target {
+            BufferTarget::Array => self.bound_buffer.get(),
+            BufferTarget::ElementArray => self
+                .bound_vao
+                .get()
+                .and_then(|vao| self.vaos.borrow()[vao].element_buffer),
+        }
+    }
+
+    fn create_pipelines(&self, draws: &[Draw]) {
+        let mut pipelines = self.pipelines.borrow_mut();
+        let programs = self.programs.borrow();
+        let vaos = self.vaos.borrow();
+
+        for draw in draws {
+            let key = (draw.program, draw.vao, draw.topology);
+            if pipelines.contains_key(&key) {
+                continue;
+            }
+
+            let program = &programs[draw.program];
+            let bindings = vertex_bindings(&vaos[draw.vao]);
+
+            let binding_descriptions: Vec<vk::VertexInputBindingDescription> = bindings
+                .iter()
+                .enumerate()
+                .map(|(index, binding)| vk::VertexInputBindingDescription {
+                    binding: index as u32,
+                    stride: binding.stride,
+                    input_rate: binding.input_rate,
+                })
+                .collect();
+            let attribute_descriptions: Vec<vk::VertexInputAttributeDescription> = bindings
+                .iter()
+                .enumerate()
+                .flat_map(|(index, binding)| {
+                    binding
+                        .attributes
+                        .iter()
+                        .map(move |(location, format, offset)| {
+                            vk::VertexInputAttributeDescription {
+                                location: *location,
+                                binding: index as u32,
+                                format: *format,
+                                offset: *offset,
+                            }
+                        })
+                })
+                .collect();
+
+            let stages = [
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::VERTEX)
+                    .module(program.vertex_module)
+                    .name(c"main"),
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::FRAGMENT)
+                    .module(program.fragment_module)
+                    .name(c"main"),
+            ];
+            let vertex_input = vk::PipelineVertexInputStateCreateInfo::default()
+                .vertex_binding_descriptions(&binding_descriptions)
+                .vertex_attribute_descriptions(&attribute_descriptions);
+            let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
+                .topology(primitive_topology(draw.topology));
+            let viewport = vk::PipelineViewportStateCreateInfo::default()
+                .viewport_count(1)
+                .scissor_count(1);
+            let rasterization = vk::PipelineRasterizationStateCreateInfo::default()
+                .polygon_mode(vk::PolygonMode::FILL)
+                .cull_mode(vk::CullModeFlags::NONE)
+                .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+                .line_width(1.0);
+            let multisample = vk::PipelineMultisampleStateCreateInfo::default()
+                .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+            let depth_stencil = vk::PipelineDepthStencilStateCreateInfo::default()
+                .depth_test_enable(true)
+                .depth_write_enable(true)
+                .depth_compare_op(vk::CompareOp::LESS);
+            let blend_attachments = [vk::PipelineColorBlendAttachmentState::default()
+                .color_write_mask(vk::ColorComponentFlags::RGBA)];
+            let color_blend =
+                vk::PipelineColorBlendStateCreateInfo::default().attachments(&blend_attachments);
+            let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+            let dynamic_state =
+                vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+
+            let create_info = vk::GraphicsPipelineCreateInfo::default()
+                .stages(&stages)
+                .vertex_input_state(&vertex_input)
+                .input_assembly_state(&input_assembly)
+                .viewport_state(&viewport)
+                .rasterization_state(&rasterization)
+                .multisample_state(&multisample)
+                .depth_stencil_state(&depth_stencil)
+                .color_blend_state(&color_blend)
+                .dynamic_state(&dynamic_state)
+                .layout(program.layout)
+                .render_pass(self.render_pass_clear);
+
+            let pipeline = unsafe {
+                self.device
+                    .create_graphics_pipelines(vk::PipelineCache::null(), &[create_info], None)
+                    .map_err(|(_, err)| err)
+                    .expect("Failed to create Vulkan pipeline.")[0]
+            };
+
+            pipelines.insert(key, pipeline);
+        }
+    }
+
+    fn upload_uniforms(&mut self) {
+        let data = self.uniform_data.get_mut();
+        if data.is_empty() {
+            return;
+        }
+
+        if data.len() as u64 > self.uniform_buffer.size {
+            let buffer = create_buffer(
+                &self.device,
+                &self.memory_properties,
+                (data.len() as u64).next_power_of_two(),
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+            );
+            // Every frame is waited for, so the old buffer is no longer in use
+            destroy_buffer(
+                &self.device,
+                std::mem::replace(&mut self.uniform_buffer, buffer),
+            );
+
+            for program in self.programs.get_mut().iter() {
+                write_descriptor_set(&self.device, program, &self.uniform_buffer);
+            }
+        }
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), self.uniform_buffer.mapped, data.len());
+        }
+    }
+}
+
+fn pick_physical_device(instance: &ash::Instance) -> (vk::PhysicalDevice, u32) {
+    // REINDEER_VULKAN_DEVICE=llvmpipe picks lavapipe when there are several devices
+    let requested = std::env::var("REINDEER_VULKAN_DEVICE").ok();
+
+    unsafe {
+        instance
+            .enumerate_physical_devices()
+            .expect("Failed to enumerate Vulkan devices.")
+            .into_iter()
+            .filter(|physical_device| {
+                let properties = instance.get_physical_device_properties(*physical_device);
+                let name = properties
+                    .device_name_as_c_str()
+                    .map(CStr::to_string_lossy)
+                    .unwrap_or_default();
+
+                requested
+                    .as_ref()
+                    .is_none_or(|requested| name.contains(requested.as_str()))
+            })
+            .find_map(|physical_device| {
+                instance
+                    .get_physical_device_queue_family_properties(physical_device)
+                    .iter()
+                    .position(|family| family.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+                    .map(|family| (physical_device, family as u32))
+            })
+            .expect("Failed to find a Vulkan device with a graphics queue.")
+    }
+}
+
+fn capabilities(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    properties: &vk::PhysicalDeviceProperties,
+) -> Capabilities {
+    let limits = &properties.limits;
+    let features = unsafe { instance.get_physical_device_features(physical_device) };
+    let sample_counts =
+        limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+
+    let extensions = unsafe { instance.enumerate_device_extension_properties(physical_device) }
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|extension| extension.extension_name_as_c_str().ok())
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect();
+
+    Capabilities {
+        version: format!(
+            "Vulkan {}.{}.{}",
+            vk::api_version_major(properties.api_version),
+            vk::api_version_minor(properties.api_version),
+            vk::api_version_patch(properties.api_version)
+        ),
+        renderer: properties
+            .device_name_as_c_str()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        vendor: format!("{:#06x}", properties.vendor_id),
+        max_texture_size: limits.max_image_dimension2_d,
+        max_vertex_attributes: limits.max_vertex_input_attributes,
+        max_uniform_block_size: limits.max_uniform_buffer_range,
+        max_samples: 1 << (31 - sample_counts.as_raw().max(1).leading_zeros()),
+        timer_query: false,
+        polygon_mode: false,
+        // Without the largePoints feature every point is 1 pixel
+        max_point_size: if features.large_points == vk::TRUE {
+            limits.point_size_range[1]
+        } else {
+            1.0
+        },
+        extensions,
+    }
+}
+
+fn find_memory_type(
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    type_bits: u32,
+    flags: vk::MemoryPropertyFlags,
+) -> u32 {
+    (0..memory_properties.memory_type_count)
+        .find(|index| {
+            type_bits & (1 << index) != 0
+                && memory_properties.memory_types[*index as usize]
+                    .property_flags
+                    .contains(flags)
+        })
+        .expect("Failed to find a suitable Vulkan memory type.")
+}
+
+fn create_buffer(
+    device: &ash::Device,
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    size: u64,
+    usage: vk::BufferUsageFlags,
+) -> Buffer {
+    unsafe {
+        let buffer = device
+            .create_buffer(
+                &vk::BufferCreateInfo::default()
+                    .size(size)
+                    .usage(usage)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE),
+                None,
+            )
+            .unwrap();
+
+        let requirements = device.get_buffer_memory_requirements(buffer);
+        let memory = device
+            .allocate_memory(
+                &vk::MemoryAllocateInfo::default()
+                    .allocation_size(requirements.size)
+                    .memory_type_index(find_memory_type(
+                        memory_properties,
+                        requirements.memory_type_bits,
+                        vk::MemoryPropertyFlags::HOST_VISIBLE
+                            | vk::MemoryPropertyFlags::HOST_COHERENT,
+                    )),
+                None,
+            )
+            .expect("Failed to allocate Vulkan buffer memory.");
+        device.bind_buffer_memory(buffer, memory, 0).unwrap();
+
+        let mapped = device
+            .map_memory(memory, 0, size, vk::MemoryMapFlags::empty())
+            .unwrap() as *mut u8;
+
+        Buffer {
+            buffer,
+            memory,
+            size,
+            mapped,
+        }
+    }
+}
+
+fn destroy_buffer(device: &ash::Device, buffer: Buffer) {
+    unsafe {
+        device.unmap_memory(buffer.memory);
+        device.destroy_buffer(buffer.buffer, None);
+        device.free_memory(buffer.memory, None);
+    }
+}
+
+fn create_image(
+    device: &ash::Device,
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    format: vk::Format,
+    usage: vk::ImageUsageFlags,
+    aspect: vk::ImageAspectFlags,
+) -> Image {
+    unsafe {
+        let image = device
+            .create_image(
+                &vk::ImageCreateInfo::default()
+                    .image_type(vk::ImageType::TYPE_2D)
+                    .format(format)
+                    .extent(window_extent())
+                    .mip_levels(1)
+                    .array_layers(1)
+                    .samples(vk::SampleCountFlags::TYPE_1)
+                    .tiling(vk::ImageTiling::OPTIMAL)
+                    .usage(usage)
+                    .initial_layout(vk::ImageLayout::UNDEFINED),
+                None,
+            )
+            .unwrap();
+
+        let requirements = device.get_image_memory_requirements(image);
+        let memory = device
+            .allocate_memory(
+                &vk::MemoryAllocateInfo::default()
+                    .allocation_size(requirements.size)
+                    .memory_type_index(find_memory_type(
+                        memory_properties,
+                        requirements.memory_type_bits,
+                        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                    )),
+                None,
+            )
+            .expect("Failed to allocate Vulkan image memory.");
+        device.bind_image_memory(image, memory, 0).unwrap();
+
+        let view = device
+            .create_image_view(
+                &vk::ImageViewCreateInfo::default()
+                    .image(image)
+                    .view_type(vk::ImageViewType::TYPE_2D)
+                    .format(format)
+                    .subresource_range(
+                        vk::ImageSubresourceRange::default()
+                            .aspect_mask(aspect)
+                            .level_count(1)
+                            .layer_count(1),
+                    ),
+                None,
+            )
+            .unwrap();
+
+        Image {
+            image,
+            memory,
+            view,
+        }
+    }
+}
+
+fn create_render_pass(device: &ash::Device, clear: bool) -> vk::RenderPass {
+    let (load_op, color_layout, depth_layout) = if clear {
+        (
+            vk::AttachmentLoadOp::CLEAR,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::UNDEFINED,
+        )
+    } else {
+        (
+            vk::AttachmentLoadOp::LOAD,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        )
+    };
+
+    let attachments = [
+        vk::AttachmentDescription::default()
+            .format(COLOR_FORMAT)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(load_op)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .initial_layout(color_layout)
+            .final_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL),
+        vk::AttachmentDescription::default()
+            .format(DEPTH_FORMAT)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(load_op)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .initial_layout(depth_layout)
+            .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL),
+    ];
+
+    let color_reference = [vk::AttachmentReference {
+        attachment: 0,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    }];
+    let depth_reference = vk::AttachmentReference {
+        attachment: 1,
+        layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+    };
+    let subpasses = [vk::SubpassDescription::default()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .color_attachments(&color_reference)
+        .depth_stencil_attachment(&depth_reference)];
+
+    let dependencies = [
+        vk::SubpassDependency::default()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(
+                vk::PipelineStageFlags::TRANSFER
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            )
+            .dst_stage_mask(
+                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            )
+            .src_access_mask(
+                vk::AccessFlags::TRANSFER_READ
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+                    | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            )
+            .dst_access_mask(
+                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+                    | vk::AccessFlags::COLOR_ATTACHMENT_READ
+                    | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            ),
+        vk::SubpassDependency::default()
+            .src_subpass(0)
+            .dst_subpass(vk::SUBPASS_EXTERNAL)
+            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .dst_stage_mask(vk::PipelineStageFlags::TRANSFER)
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ),
+    ];
+
+    unsafe {
+        device
+            .create_render_pass(
+                &vk::RenderPassCreateInfo::default()
+                    .attachments(&attachments)
+                    .subpasses(&subpasses)
+                    .dependencies(&dependencies),
+                None,
+            )
+            .unwrap()
+    }
+}
+
+fn write_descriptor_set(device: &ash::Device, program: &Program, uniform_buffer: &Buffer) {
+    let Some(descriptor_set) = program.descriptor_set else {
+        return;
+    };
+
+    let infos: Vec<[vk::DescriptorBufferInfo; 1]> = program
+        .uniforms
+        .iter()
+        .map(|stage| {
+            [vk::DescriptorBufferInfo {
+                buffer: uniform_buffer.buffer,
+                offset: 0,
+                range: stage.block.size as u64,
+            }]
+        })
+        .collect();
+
+    let writes: Vec<vk::WriteDescriptorSet> = program
+        .uniforms
+        .iter()
+        .zip(infos.iter())
+        .map(|(stage, info)| {
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(stage.block.binding)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+                .buffer_info(info)
+        })
+        .collect();
+
+    unsafe { device.update_descriptor_sets(&writes, &[]) }
+}
+
+// Attributes sourced from the same buffer with the same stride share a vertex
+// binding. GL offsets past the stride become the binding's buffer offset.
+fn vertex_bindings(vao: &VertexArray) -> Vec<VertexBinding> {
+    let mut bindings: Vec<VertexBinding> = Vec::new();
+
+    for (location, attribute) in &vao.attributes {
+        let pointer = match (attribute.enabled, attribute.pointer) {
+            (true, Some(pointer)) => pointer,
+            _ => continue,
+        };
+
+        let base = pointer.offset - pointer.offset % pointer.stride;
+        let input_rate = match attribute.divisor {
+            0 => vk::VertexInputRate::VERTEX,
+            _ => vk::VertexInputRate::INSTANCE,
+        };
+        let vertex_attribute = (*location, pointer.format, pointer.offset - base);
+
+        match bindings.iter_mut().find(|binding| {
+            binding.buffer == pointer.buffer
+                && binding.stride == pointer.stride
+                && binding.base == base
+                && binding.input_rate == input_rate
+        }) {
+            Some(binding) => binding.attributes.push(vertex_attribute),
+            None => bindings.push(VertexBinding {
+                buffer: pointer.buffer,
+                base,
+                stride: pointer.stride,
+                input_rate,
+                attributes: vec![vertex_attribute],
+            }),
+        }
+    }
+
+    bindings
+}
+
+// Integer attributes that aren't normalized use the scaled formats, the GLSL
+// shaders read them as floats just like glVertexAttribPointer does
+fn vertex_format(size: i32, type_: ScalarType, normalized: bool) -> vk::Format {
+    match (type_, size, normalized) {
+        (ScalarType::F32, 1, _) => vk::Format::R32_SFLOAT,
+        (ScalarType::F32, 2, _) => vk::Format::R32G32_SFLOAT,
+        (ScalarType::F32, 3, _) => vk::Format::R32G32B32_SFLOAT,
+        (ScalarType::F32, 4, _) => vk::Format::R32G32B32A32_SFLOAT,
+        (ScalarType::F16, 1, _) => vk::Format::R16_SFLOAT,
+        (ScalarType::F16, 2, _) => vk::Format::R16G16_SFLOAT,
+        (ScalarType::F16, 4, _) => vk::Format::R16G16B16A16_SFLOAT,
+        (ScalarType::U8, 1, true) => vk::Format::R8_UNORM,
+        (ScalarType::U8, 2, true) => vk::Format::R8G8_UNORM,
+        (ScalarType::U8, 4, true) => vk::Format::R8G8B8A8_UNORM,
+        (ScalarType::U8, 1, false) => vk::Format::R8_USCALED,
+        (ScalarType::U8, 2, false) => vk::Format::R8G8_USCALED,
+        (ScalarType::U8, 4, false) => vk::Format::R8G8B8A8_USCALED,
+        (ScalarType::I8, 1, true) => vk::Format::R8_SNORM,
+        (ScalarType::I8, 2, true) => vk::Format::R8G8_SNORM,
+        (ScalarType::I8, 4, true) => vk::Format::R8G8B8A8_SNORM,
+        (ScalarType::I8, 1, false) => vk::Format::R8_SSCALED,
+        (ScalarType::I8, 2, false) => vk::Format::R8G8_SSCALED,
+        (ScalarType::I8, 4, false) => vk::Format::R8G8B8A8_SSCALED,
+        (ScalarType::U16, 1, true) => vk::Format::R16_UNORM,
+        (ScalarType::U16, 2, true) => vk::Format::R16G16_UNORM,
+        (ScalarType::U16, 4, true) => vk::Format::R16G16B16A16_UNORM,
+        (ScalarType::U16, 1, false) => vk::Format::R16_USCALED,
+        (ScalarType::U16, 2, false) => vk::Format::R16G16_USCALED,
+        (ScalarType::U16, 4, false) => vk::Format::R16G16B16A16_USCALED,
+        (ScalarType::I16, 1, true) => vk::Format::R16_SNORM,
+        (ScalarType::I16, 2, true) => vk::Format::R16G16_SNORM,
+        (ScalarType::I16, 4, true) => vk::Format::R16G16B16A16_SNORM,
+        (ScalarType::I16, 1, false) => vk::Format::R16_SSCALED,
+        (ScalarType::I16, 2, false) => vk::Format::R16G16_SSCALED,
+        (ScalarType::I16, 4, false) => vk::Format::R16G16B16A16_SSCALED,
+        _ => panic!(
+            "Unsupported vertex attribute format: {} x {:?} (normalized: {})",
+            size, type_, normalized
+        ),
+    }
+}
+
+fn vk_index_type(index_type: IndexType) -> vk::IndexType {
+    match index_type {
+        IndexType::U16 => vk::IndexType::UINT16,
+        IndexType::U32 => vk::IndexType::UINT32,
+    }
+}
+
+fn primitive_topology(topology: PrimitiveTopology) -> vk::PrimitiveTopology {
+    match topology {
+        PrimitiveTopology::Points => vk::PrimitiveTopology::POINT_LIST,
+        PrimitiveTopology::Lines => vk::PrimitiveTopology::LINE_LIST,
+        PrimitiveTopology::LineStrip => vk::PrimitiveTopology::LINE_STRIP,
+        PrimitiveTopology::Triangles => vk::PrimitiveTopology::TRIANGLE_LIST,
+        PrimitiveTopology::TriangleStrip => vk::PrimitiveTopology::TRIANGLE_STRIP,
+        PrimitiveTopology::TriangleFan => vk::PrimitiveTopology::TRIANGLE_FAN,
+    }
+}
+
+fn window_extent() -> vk::Extent3D {
+    vk::Extent3D {
+        width: WINDOW_WIDTH as u32,
+        height: WINDOW_HEIGHT as u32,
+        depth: 1,
+    }
+}
+
+fn window_rect() -> vk::Rect2D {
+    vk::Rect2D {
+        offset: vk::Offset2D { x: 0, y: 0 },
+        extent: vk::Extent2D {
+            width: WINDOW_WIDTH as u32,
+            height: WINDOW_HEIGHT as u32,
+        },
+    }
+}
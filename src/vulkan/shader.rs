@@ -0,0 +1,461 @@
+use super::Context;
+use crate::{
+    common::{ShaderUtils, TextureUnit, Uniform, UniformInfo, UniformType},
+    SHADERS,
+};
+use naga::{back::spv, front::glsl, valid, ShaderStage};
+use std::collections::HashMap;
+
+// How a value is laid out in the uniform block
+trait UniformBytes {
+    fn write_bytes(&self, bytes: &mut Vec<u8>);
+}
+
+macro_rules! uniform_bytes {
+    ($($scalar:ty),*) => {
+        $(
+            impl UniformBytes for $scalar {
+                fn write_bytes(&self, bytes: &mut Vec<u8>) {
+                    bytes.extend_from_slice(&self.to_ne_bytes());
+                }
+            }
+
+            // Vectors are packed, a vec3 takes 12 bytes
+            impl<const N: usize> UniformBytes for [$scalar; N] {
+                fn write_bytes(&self, bytes: &mut Vec<u8>) {
+                    self.iter().for_each(|value| value.write_bytes(bytes));
+                }
+            }
+        )*
+    };
+}
+
+uniform_bytes!(f32, i32, u32);
+
+impl UniformBytes for bool {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        (*self as u32).write_bytes(bytes);
+    }
+}
+
+impl UniformBytes for [[f32; 2]; 2] {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        // std140 pads every matrix column to a vec4
+        for column in self {
+            column.write_bytes(bytes);
+            bytes.resize(bytes.len().next_multiple_of(16), 0);
+        }
+    }
+}
+
+impl UniformBytes for [[f32; 3]; 3] {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        // std140 pads every mat3 column to a vec4
+        for column in self {
+            column.write_bytes(bytes);
+            bytes.resize(bytes.len().next_multiple_of(16), 0);
+        }
+    }
+}
+
+impl UniformBytes for [[f32; 4]; 4] {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        self.iter().for_each(|column| column.write_bytes(bytes));
+    }
+}
+
+impl<Input: UniformBytes> Uniform<Input> for Context {
+    fn set_uniform(&self, program: &Self::Program, location: &Self::UniformLocation, input: Input) {
+        let mut bytes = Vec::new();
+        input.write_bytes(&mut bytes);
+        self.write_uniform(program, location, &bytes);
+    }
+}
+
+impl<Input: UniformBytes> Uniform<&[Input]> for Context {
+    fn set_uniform(
+        &self,
+        program: &Self::Program,
+        location: &Self::UniformLocation,
+        input: &[Input],
+    ) {
+        // std140 rounds the array stride up to a vec4
+        let mut bytes = Vec::new();
+        for element in input {
+            element.write_bytes(&mut bytes);
+            bytes.resize(bytes.len().next_multiple_of(16), 0);
+        }
+        self.write_uniform(program, location, &bytes);
+    }
+}
+
+// Samplers are opaque and can't go into the std140 block the uniforms are
+// gathered in, `to_vulkan_glsl` rejects them, so none is ever reflected
+impl Uniform<TextureUnit> for Context {
+    fn set_uniform(&self, _: &Self::Program, _: &Self::UniformLocation, _: TextureUnit) {}
+}
+
+impl Uniform<&[TextureUnit]> for Context {
+    fn set_uniform(&self, _: &Self::Program, _: &Self::UniformLocation, _: &[TextureUnit]) {}
+}
+
+pub struct UniformBlock {
+    pub binding: u32,
+    pub fields: HashMap<String, u32>,
+    pub uniforms: Vec<UniformInfo>,
+    pub size: u32,
+}
+
+pub struct CompiledShader {
+    pub spirv: Vec<u32>,
+    pub uniforms: Option<UniformBlock>,
+}
+
+impl ShaderUtils for Context {
+    fn compile_program(&self, name: &str) -> Self::Program {
+        let mut varyings = HashMap::new();
+
+        let vertex_shader = compile_shader(ShaderStage::Vertex, name, &mut varyings).unwrap();
+        let fragment_shader = compile_shader(ShaderStage::Fragment, name, &mut varyings).unwrap();
+
+        self.create_program(vertex_shader, fragment_shader)
+    }
+
+    // A uniform used by both stages is in both blocks
+    fn active_uniforms(&self, program: &Self::Program) -> Vec<UniformInfo> {
+        let mut uniforms: Vec<UniformInfo> = Vec::new();
+
+        for stage in &self.programs.borrow()[*program].uniforms {
+            for uniform in &stage.block.uniforms {
+                if !uniforms.iter().any(|known| known.name == uniform.name) {
+                    uniforms.push(uniform.clone());
+                }
+            }
+        }
+
+        uniforms
+    }
+
+    fn uniform_location(&self, program: &Self::Program, name: &str) -> Self::UniformLocation {
+        let programs = self.programs.borrow();
+        let stages = &programs[*program].uniforms;
+
+        std::array::from_fn(|stage| {
+            stages
+                .get(stage)
+                .and_then(|stage| stage.block.fields.get(name).copied())
+        })
+    }
+}
+
+fn compile_shader(
+    stage: ShaderStage,
+    name: &str,
+    varyings: &mut HashMap<String, u32>,
+) -> Result<CompiledShader, String> {
+    let shader_source = SHADERS[format!(
+        "{}.{}.glsl",
+        name,
+        if stage == ShaderStage::Fragment {
+            "frag"
+        } else {
+            "vert"
+        }
+    )
+    .as_str()];
+
+    let source = to_vulkan_glsl(shader_source, stage, varyings)?;
+
+    let module = glsl::Frontend::default()
+        .parse(&glsl::Options::from(stage), &source)
+        .map_err(|err| err.emit_to_string(&source))?;
+
+    let info = valid::Validator::new(
+        valid::ValidationFlags::all(),
+        valid::Capabilities::default(),
+    )
+    .validate(&module)
+    .map_err(|err| err.emit_to_string(&source))?;
+
+    // Point lists need a point size, shaders that don't write gl_PointSize
+    // get 1.0
+    let options = spv::Options {
+        flags: spv::Options::default().flags | spv::WriterFlags::FORCE_POINT_SIZE,
+        ..spv::Options::default()
+    };
+
+    let spirv = spv::write_vec(
+        &module,
+        &info,
+        &options,
+        Some(&spv::PipelineOptions {
+            shader_stage: stage,
+            entry_point: String::from("main"),
+        }),
+    )
+    .map_err(|err| err.to_string())?;
+
+    Ok(CompiledShader {
+        spirv,
+        uniforms: reflect_uniforms(&module, uniform_binding(stage))?,
+    })
+}
+
+fn uniform_binding(stage: ShaderStage) -> u32 {
+    match stage {
+        ShaderStage::Fragment => 1,
+        _ => 0,
+    }
+}
+
+// The shaders are written for GL. Vulkan wants version 450, uniforms in
+// blocks and explicit varying locations, so the loose uniforms of a stage are
+// gathered into one std140 block, varyings are numbered by name across the
+// stages and the vertex stage remaps depth from [-1, 1] to [0, 1].
+fn to_vulkan_glsl(
+    source: &str,
+    stage: ShaderStage,
+    varyings: &mut HashMap<String, u32>,
+) -> Result<String, String> {
+    let mut lines: Vec<String> = vec![String::from("#version 450")];
+    let mut uniforms: Vec<&str> = Vec::new();
+    let mut uniforms_at = None;
+    let mut outputs = 0;
+
+    for line in source.lines() {
+        let trimmed = line.trim();
+
+        if let Some(declaration) = trimmed.strip_prefix("uniform ") {
+            // Opaque types can't be members of a block, and there are no
+            // textures on vulkan yet to bind them to
+            if is_opaque(declaration) {
+                return Err(format!(
+                    "Samplers and images aren't supported on vulkan: {}",
+                    trimmed
+                ));
+            }
+            uniforms_at.get_or_insert(lines.len());
+            uniforms.push(declaration);
+        } else if let Some(declaration) = trimmed.strip_prefix("out ") {
+            let location = if stage == ShaderStage::Vertex {
+                let next = varyings.len() as u32;
+                *varyings
+                    .entry(declaration_name(declaration))
+                    .or_insert(next)
+            } else {
+                outputs += 1;
+                outputs - 1
+            };
+            lines.push(format!(
+                "layout(location = {}) out {}",
+                location, declaration
+            ));
+        } else if let Some(declaration) = trimmed.strip_prefix("in ") {
+            if stage == ShaderStage::Vertex {
+                return Err(format!(
+                    "Vertex input needs an explicit layout(location = n): {}",
+                    trimmed
+                ));
+            }
+            let name = declaration_name(declaration);
+            let location = varyings
+                .get(&name)
+                .ok_or_else(|| format!("No vertex shader output matches input {}", name))?;
+            lines.push(format!(
+                "layout(location = {}) in {}",
+                location, declaration
+            ));
+        } else if stage == ShaderStage::Vertex && trimmed.starts_with("void main(") {
+            lines.push(line.replacen("main(", "reindeer_main(", 1));
+        } else {
+            lines.push(String::from(line));
+        }
+    }
+
+    if let Some(index) = uniforms_at {
+        lines.insert(
+            index,
+            format!(
+                "layout(std140, set = 0, binding = {}) uniform Uniforms {{\n  {}\n}};",
+                uniform_binding(stage),
+                uniforms.join("\n  ")
+            ),
+        );
+    }
+
+    if stage == ShaderStage::Vertex {
+        lines.push(String::from(
+            "void main() {\n  reindeer_main();\n  gl_Position.z = 0.5 * (gl_Position.z + gl_Position.w);\n}",
+        ));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+fn is_opaque(declaration: &str) -> bool {
+    let ty = declaration
+        .split_whitespace()
+        .find(|word| !matches!(*word, "lowp" | "mediump" | "highp"))
+        .unwrap_or_default();
+
+    [
+        "sampler", "isampler", "usampler", "image", "iimage", "uimage",
+    ]
+    .iter()
+    .any(|prefix| ty.starts_with(prefix))
+}
+
+fn declaration_name(declaration: &str) -> String {
+    let name = declaration
+        .trim_end_matches(';')
+        .split_whitespace()
+        .last()
+        .unwrap_or_default();
+
+    String::from(name.split('[').next().unwrap_or(name))
+}
+
+fn reflect_uniforms(module: &naga::Module, binding: u32) -> Result<Option<UniformBlock>, String> {
+    let uniforms = module.global_variables.iter().find(|(_, variable)| {
+        variable.space == naga::AddressSpace::Uniform
+            && variable.binding == Some(naga::ResourceBinding { group: 0, binding })
+    });
+
+    let Some((_, uniforms)) = uniforms else {
+        return Ok(None);
+    };
+
+    match &module.types[uniforms.ty].inner {
+        naga::TypeInner::Struct { members, span } => Ok(Some(UniformBlock {
+            binding,
+            fields: members
+                .iter()
+                .filter_map(|member| member.name.clone().map(|name| (name, member.offset)))
+                .collect(),
+            uniforms: members
+                .iter()
+                .filter_map(|member| {
+                    member
+                        .name
+                        .clone()
+                        .map(|name| uniform_info(module, name, member.ty))
+                })
+                .collect(),
+            size: *span,
+        })),
+        _ => Err(String::from("Uniform block has to be a struct")),
+    }
+}
+
+fn uniform_info(module: &naga::Module, name: String, ty: naga::Handle<naga::Type>) -> UniformInfo {
+    let (inner, array_size) = match &module.types[ty].inner {
+        naga::TypeInner::Array {
+            base,
+            size: naga::ArraySize::Constant(size),
+            ..
+        } => (&module.types[*base].inner, size.get()),
+        inner => (inner, 1),
+    };
+
+    UniformInfo {
+        name,
+        uniform_type: to_uniform_type(inner),
+        array_size,
+    }
+}
+
+fn to_uniform_type(inner: &naga::TypeInner) -> UniformType {
+    use naga::{ScalarKind, TypeInner, VectorSize};
+
+    match *inner {
+        TypeInner::Scalar(scalar) => match scalar.kind {
+            ScalarKind::Float => UniformType::Float,
+            ScalarKind::Sint => UniformType::Int,
+            ScalarKind::Uint => UniformType::UInt,
+            ScalarKind::Bool => UniformType::Bool,
+            _ => UniformType::Other,
+        },
+        TypeInner::Vector { size, scalar } => match (scalar.kind, size) {
+            (ScalarKind::Float, VectorSize::Bi) => UniformType::Vec2,
+            (ScalarKind::Float, VectorSize::Tri) => UniformType::Vec3,
+            (ScalarKind::Float, VectorSize::Quad) => UniformType::Vec4,
+            (ScalarKind::Sint, VectorSize::Bi) => UniformType::IVec2,
+            (ScalarKind::Sint, VectorSize::Tri) => UniformType::IVec3,
+            (ScalarKind::Sint, VectorSize::Quad) => UniformType::IVec4,
+            (ScalarKind::Uint, VectorSize::Bi) => UniformType::UVec2,
+            (ScalarKind::Uint, VectorSize::Tri) => UniformType::UVec3,
+            (ScalarKind::Uint, VectorSize::Quad) => UniformType::UVec4,
+            (ScalarKind::Bool, VectorSize::Bi) => UniformType::BVec2,
+            (ScalarKind::Bool, VectorSize::Tri) => UniformType::BVec3,
+            (ScalarKind::Bool, VectorSize::Quad) => UniformType::BVec4,
+            _ => UniformType::Other,
+        },
+        TypeInner::Matrix { columns, rows, .. } => match (columns, rows) {
+            (VectorSize::Bi, VectorSize::Bi) => UniformType::Mat2,
+            (VectorSize::Tri, VectorSize::Tri) => UniformType::Mat3,
+            (VectorSize::Quad, VectorSize::Quad) => UniformType::Mat4,
+            _ => UniformType::Other,
+        },
+        _ => UniformType::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(name: &str) -> [CompiledShader; 2] {
+        let mut varyings = HashMap::new();
+        [ShaderStage::Vertex, ShaderStage::Fragment].map(|stage| {
+            compile_shader(stage, name, &mut varyings)
+                .unwrap_or_else(|err| panic!("{name} {stage:?}: {err}"))
+        })
+    }
+
+    #[test]
+    fn bundled_shaders() {
+        for name in SHADERS
+            .keys()
+            .filter_map(|name| name.strip_suffix(".vert.glsl"))
+        {
+            compile(name);
+        }
+    }
+
+    #[test]
+    fn loose_uniforms_become_blocks() {
+        let [vertex, fragment] = compile("phong_light_object");
+        let vertex = vertex.uniforms.unwrap();
+        let fragment = fragment.uniforms.unwrap();
+
+        assert_eq!(vertex.binding, 0);
+        assert_eq!(fragment.binding, 1);
+        assert!(vertex.uniforms.contains(&UniformInfo {
+            name: String::from("u_model"),
+            uniform_type: UniformType::Mat4,
+            array_size: 1,
+        }));
+
+        // std140 puts every vec3 on a 16 byte boundary
+        let mut offsets: Vec<u32> = ["u_color", "u_light_color", "u_light_position"]
+            .map(|name| fragment.fields[name])
+            .to_vec();
+        offsets.sort();
+        assert_eq!(offsets, [0, 16, 32]);
+    }
+
+    #[test]
+    fn samplers_are_rejected() {
+        let mut varyings = HashMap::new();
+        let source = "uniform highp sampler2D u_texture;\nvoid main() {}";
+        let error = to_vulkan_glsl(source, ShaderStage::Fragment, &mut varyings).unwrap_err();
+        assert!(error.contains("u_texture"), "{error}");
+
+        let source = "uniform usampler2D u_ids;\nvoid main() {}";
+        assert!(to_vulkan_glsl(source, ShaderStage::Fragment, &mut varyings).is_err());
+
+        // Only the type counts, not the name
+        let source = "uniform float image_scale;\nvoid main() {}";
+        assert!(to_vulkan_glsl(source, ShaderStage::Fragment, &mut varyings).is_ok());
+    }
+}
@@ -2,18 +2,29 @@ pub mod shader;
 
 use crate::BACKEND;
 
-use super::{backend::Backend, WINDOW_HEIGHT, WINDOW_WIDTH};
-use std::{cell::RefCell, rc::Rc};
+use super::{
+    backend::Backend,
+    common::{Capabilities, QueryResult},
+    types::{BufferTarget, BufferUsage, IndexType, PolygonMode, PrimitiveTopology, ScalarType},
+    WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use std::{cell::RefCell, rc::Rc, time::Duration};
 use wasm_bindgen::{prelude::Closure, JsCast};
 use web_sys::{
-    window, WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlShader, WebGlVertexArrayObject,
-    Window,
+    window, WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlQuery, WebGlShader,
+    WebGlUniformLocation, WebGlVertexArrayObject, Window,
 };
 
+// EXT_disjoint_timer_query_webgl2
+const TIME_ELAPSED_EXT: u32 = 0x88BF;
+const GPU_DISJOINT_EXT: u32 = 0x8FBB;
+
 pub struct Context {
     context: WebGl2RenderingContext,
     #[allow(unused)]
     window: Window,
+    debug: bool,
+    timer_query: bool,
 }
 
 impl Backend for Context {
@@ -22,6 +33,8 @@ impl Backend for Context {
     type Program = WebGlProgram;
     type Shader = WebGlShader;
     type Vao = WebGlVertexArrayObject;
+    type Query = WebGlQuery;
+    type UniformLocation = WebGlUniformLocation;
 
     fn new() -> Context {
         let window = web_sys::window().unwrap();
@@ -47,72 +60,235 @@ impl Backend for Context {
 
         context.enable(WebGl2RenderingContext::DEPTH_TEST);
 
-        Context { context, window }
+        let timer_query = context
+            .get_extension("EXT_disjoint_timer_query_webgl2")
+            .ok()
+            .flatten()
+            .is_some();
+
+        Context {
+            context,
+            window,
+            debug: crate::debug(),
+            timer_query,
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            version: self.get_string(WebGl2RenderingContext::VERSION),
+            renderer: self.get_string(WebGl2RenderingContext::RENDERER),
+            vendor: self.get_string(WebGl2RenderingContext::VENDOR),
+            max_texture_size: self.get_integer(WebGl2RenderingContext::MAX_TEXTURE_SIZE),
+            max_vertex_attributes: self.get_integer(WebGl2RenderingContext::MAX_VERTEX_ATTRIBS),
+            max_uniform_block_size: self
+                .get_integer(WebGl2RenderingContext::MAX_UNIFORM_BLOCK_SIZE),
+            max_samples: self.get_integer(WebGl2RenderingContext::MAX_SAMPLES),
+            timer_query: self.timer_query,
+            polygon_mode: false,
+            max_point_size: self
+                .context
+                .get_parameter(WebGl2RenderingContext::ALIASED_POINT_SIZE_RANGE)
+                .ok()
+                .map(|range| js_sys::Float32Array::from(range).to_vec()[1])
+                .unwrap_or(1.0),
+            extensions: self
+                .context
+                .get_supported_extensions()
+                .map(|extensions| {
+                    extensions
+                        .iter()
+                        .filter_map(|extension| extension.as_string())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
     }
 
     fn use_program(&self, program: &WebGlProgram) {
-        self.context.use_program(Some(program))
+        self.context.use_program(Some(program));
+        self.check_error("use_program");
     }
 
     fn create_buffer(&self) -> WebGlBuffer {
-        self.context.create_buffer().unwrap()
+        let buffer = self.context.create_buffer().unwrap();
+        self.check_error("create_buffer");
+        buffer
     }
 
-    fn bind_buffer(&self, buffer_type: u32, buffer: &WebGlBuffer) {
-        self.context.bind_buffer(buffer_type, Some(buffer));
+    fn bind_buffer(&self, target: BufferTarget, buffer: &WebGlBuffer) {
+        self.context
+            .bind_buffer(buffer_target(target), Some(buffer));
+        self.check_error("bind_buffer");
     }
 
-    fn buffer_data(&self, vertices: &[f32], buffer_type: u32, usage_hint: u32) {
-        unsafe {
-            let positions_array_buf_view = js_sys::Float32Array::view(vertices);
+    fn buffer_data(&self, data: &[u8], target: BufferTarget, usage: BufferUsage) {
+        self.context
+            .buffer_data_with_u8_array(buffer_target(target), data, buffer_usage(usage));
+        self.check_error("buffer_data");
+    }
 
-            self.context.buffer_data_with_array_buffer_view(
-                buffer_type,
-                &positions_array_buf_view,
-                usage_hint,
-            );
-        }
+    fn buffer_sub_data(&self, data: &[u8], target: BufferTarget, offset: i32) {
+        self.context
+            .buffer_sub_data_with_i32_and_u8_array(buffer_target(target), offset, data);
+        self.check_error("buffer_sub_data");
     }
 
     fn create_vertex_array(&self) -> WebGlVertexArrayObject {
-        self.context.create_vertex_array().unwrap()
+        let vao = self.context.create_vertex_array().unwrap();
+        self.check_error("create_vertex_array");
+        vao
     }
 
     fn bind_vertex_array(&self, vao: &WebGlVertexArrayObject) {
-        self.context.bind_vertex_array(Some(vao))
+        self.context.bind_vertex_array(Some(vao));
+        self.check_error("bind_vertex_array");
     }
 
     fn vertex_attrib_pointer(
         &self,
         index: u32,
         size: i32,
-        type_: u32,
+        type_: ScalarType,
         normalized: bool,
         stride: i32,
         offset: i32,
     ) {
-        self.context
-            .vertex_attrib_pointer_with_i32(index, size, type_, normalized, stride, offset);
+        self.context.vertex_attrib_pointer_with_i32(
+            index,
+            size,
+            scalar_type(type_),
+            normalized,
+            stride,
+            offset,
+        );
+        self.check_error("vertex_attrib_pointer");
+    }
+
+    fn draw_elements(
+        &self,
+        topology: PrimitiveTopology,
+        index_count: i32,
+        index_type: IndexType,
+        offset: i32,
+    ) {
+        self.context.draw_elements_with_i32(
+            primitive_topology(topology),
+            index_count,
+            index_type_enum(index_type),
+            offset,
+        );
+        self.check_error("draw_elements");
+    }
+
+    fn draw_arrays_instanced(
+        &self,
+        topology: PrimitiveTopology,
+        first: i32,
+        vertex_count: i32,
+        instance_count: i32,
+    ) {
+        self.context.draw_arrays_instanced(
+            primitive_topology(topology),
+            first,
+            vertex_count,
+            instance_count,
+        );
+        self.check_error("draw_arrays_instanced");
+    }
+
+    fn draw_elements_instanced(
+        &self,
+        topology: PrimitiveTopology,
+        index_count: i32,
+        index_type: IndexType,
+        offset: i32,
+        instance_count: i32,
+    ) {
+        self.context.draw_elements_instanced_with_i32(
+            primitive_topology(topology),
+            index_count,
+            index_type_enum(index_type),
+            offset,
+            instance_count,
+        );
+        self.check_error("draw_elements_instanced");
+    }
+
+    fn create_timer_query(&self) -> WebGlQuery {
+        let query = self.context.create_query().unwrap();
+        self.check_error("create_timer_query");
+        query
+    }
+
+    fn begin_timer_query(&self, query: &WebGlQuery) {
+        self.context.begin_query(TIME_ELAPSED_EXT, query);
+        self.check_error("begin_timer_query");
+    }
+
+    fn end_timer_query(&self, _query: &WebGlQuery) {
+        self.context.end_query(TIME_ELAPSED_EXT);
+        self.check_error("end_timer_query");
+    }
+
+    fn timer_query_result(&self, query: &WebGlQuery) -> QueryResult {
+        let available = self
+            .context
+            .get_query_parameter(query, WebGl2RenderingContext::QUERY_RESULT_AVAILABLE)
+            .as_bool()
+            .unwrap_or(false);
+        let disjoint = self
+            .context
+            .get_parameter(GPU_DISJOINT_EXT)
+            .ok()
+            .and_then(|disjoint| disjoint.as_bool())
+            .unwrap_or(false);
+
+        if !available {
+            QueryResult::Pending
+        } else if disjoint {
+            QueryResult::Discarded
+        } else {
+            let elapsed = self
+                .context
+                .get_query_parameter(query, WebGl2RenderingContext::QUERY_RESULT)
+                .as_f64()
+                .unwrap_or_default();
+            QueryResult::Ready(Duration::from_nanos(elapsed as u64))
+        }
     }
 
     fn enable_vertex_attrib_array(&self, index: u32) {
         self.context.enable_vertex_attrib_array(index);
+        self.check_error("enable_vertex_attrib_array");
     }
 
-    fn draw_arrays(&self, mode: u32, first: i32, vertex_count: i32) {
-        self.context.draw_arrays(mode, first, vertex_count);
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        self.context.vertex_attrib_divisor(index, divisor);
+        self.check_error("vertex_attrib_divisor");
+    }
+
+    fn draw_arrays(&self, topology: PrimitiveTopology, first: i32, vertex_count: i32) {
+        self.context
+            .draw_arrays(primitive_topology(topology), first, vertex_count);
+        self.check_error("draw_arrays");
     }
 
     fn set_clear_color(&mut self, red: f32, green: f32, blue: f32, alpha: f32) {
         self.context.clear_color(red, green, blue, alpha);
+        self.check_error("set_clear_color");
     }
 
     fn clear(&self) {
         self.context.clear(
             WebGl2RenderingContext::COLOR_BUFFER_BIT | WebGl2RenderingContext::DEPTH_BUFFER_BIT,
-        )
+        );
+        self.check_error("clear");
     }
 
+    // WebGL has no polygon modes, `Capabilities::polygon_mode` is false
+    fn set_polygon_mode(&self, _mode: PolygonMode) {}
+
     fn before_draw(&mut self) {
         self.clear();
     }
@@ -136,9 +312,102 @@ impl Backend for Context {
     }
 }
 
+impl Context {
+    // get_error stalls the pipeline, so it is only called in debug mode
+    fn check_error(&self, method: &str) {
+        if !self.debug {
+            return;
+        }
+
+        loop {
+            let error = self.context.get_error();
+            if error == WebGl2RenderingContext::NO_ERROR {
+                break;
+            }
+            log::error!("WebGL error in {}: {}", method, error_name(error));
+        }
+    }
+
+    fn get_string(&self, name: u32) -> String {
+        self.context
+            .get_parameter(name)
+            .ok()
+            .and_then(|value| value.as_string())
+            .unwrap_or_default()
+    }
+
+    fn get_integer(&self, name: u32) -> u32 {
+        self.context
+            .get_parameter(name)
+            .ok()
+            .and_then(|value| value.as_f64())
+            .unwrap_or_default() as u32
+    }
+}
+
 fn request_animation_frame(f: &Closure<dyn FnMut()>) -> i32 {
     window()
         .unwrap()
         .request_animation_frame(f.as_ref().unchecked_ref())
         .expect("should register `requestAnimationFrame` OK")
 }
+
+fn error_name(error: u32) -> String {
+    match error {
+        WebGl2RenderingContext::INVALID_ENUM => String::from("INVALID_ENUM"),
+        WebGl2RenderingContext::INVALID_VALUE => String::from("INVALID_VALUE"),
+        WebGl2RenderingContext::INVALID_OPERATION => String::from("INVALID_OPERATION"),
+        WebGl2RenderingContext::INVALID_FRAMEBUFFER_OPERATION => {
+            String::from("INVALID_FRAMEBUFFER_OPERATION")
+        }
+        WebGl2RenderingContext::OUT_OF_MEMORY => String::from("OUT_OF_MEMORY"),
+        WebGl2RenderingContext::CONTEXT_LOST_WEBGL => String::from("CONTEXT_LOST_WEBGL"),
+        _ => format!("{:#x}", error),
+    }
+}
+
+fn buffer_target(target: BufferTarget) -> u32 {
+    match target {
+        BufferTarget::Array => WebGl2RenderingContext::ARRAY_BUFFER,
+        BufferTarget::ElementArray => WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER,
+    }
+}
+
+fn buffer_usage(usage: BufferUsage) -> u32 {
+    match usage {
+        BufferUsage::Static => WebGl2RenderingContext::STATIC_DRAW,
+        BufferUsage::Dynamic => WebGl2RenderingContext::DYNAMIC_DRAW,
+        BufferUsage::Stream => WebGl2RenderingContext::STREAM_DRAW,
+    }
+}
+
+fn primitive_topology(topology: PrimitiveTopology) -> u32 {
+    match topology {
+        PrimitiveTopology::Points => WebGl2RenderingContext::POINTS,
+        PrimitiveTopology::Lines => WebGl2RenderingContext::LINES,
+        PrimitiveTopology::LineStrip => WebGl2RenderingContext::LINE_STRIP,
+        PrimitiveTopology::Triangles => WebGl2RenderingContext::TRIANGLES,
+        PrimitiveTopology::TriangleStrip => WebGl2RenderingContext::TRIANGLE_STRIP,
+        PrimitiveTopology::TriangleFan => WebGl2RenderingContext::TRIANGLE_FAN,
+    }
+}
+
+fn index_type_enum(index_type: IndexType) -> u32 {
+    match index_type {
+        IndexType::U16 => WebGl2RenderingContext::UNSIGNED_SHORT,
+        IndexType::U32 => WebGl2RenderingContext::UNSIGNED_INT,
+    }
+}
+
+fn scalar_type(type_: ScalarType) -> u32 {
+    match type_ {
+        ScalarType::I8 => WebGl2RenderingContext::BYTE,
+        ScalarType::U8 => WebGl2RenderingContext::UNSIGNED_BYTE,
+        ScalarType::I16 => WebGl2RenderingContext::SHORT,
+        ScalarType::U16 => WebGl2RenderingContext::UNSIGNED_SHORT,
+        ScalarType::I32 => WebGl2RenderingContext::INT,
+        ScalarType::U32 => WebGl2RenderingContext::UNSIGNED_INT,
+        ScalarType::F16 => WebGl2RenderingContext::HALF_FLOAT,
+        ScalarType::F32 => WebGl2RenderingContext::FLOAT,
+    }
+}
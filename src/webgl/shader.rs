@@ -1,107 +1,200 @@
+use super::Context;
 use crate::{
-    backend::Backend,
-    common::{Shader, ShaderProgram, ShaderUtils},
-    BACKEND, SHADERS,
+    common::{ShaderUtils, TextureUnit, Uniform, UniformInfo, UniformType},
+    SHADERS,
 };
 use web_sys::{WebGl2RenderingContext, WebGlProgram, WebGlShader};
 
-impl Shader<[f32; 3]> for ShaderProgram {
-    fn set_uniform(&self, name: &str, input: [f32; 3]) {
-        let loc = BACKEND
-            .lock()
-            .unwrap()
-            .context
-            .get_uniform_location(&self.id, name)
-            .unwrap_or_else(|| panic!("Failed to find uniform location: {}", name));
-        BACKEND.lock().unwrap().context.use_program(Some(&self.id));
-        BACKEND
-            .lock()
-            .unwrap()
-            .context
-            .uniform3f(Some(&loc), input[0], input[1], input[2]);
-    }
+const SHADER_HEADER: &str = "#version 300 es\nprecision highp float;\nprecision highp int;\n";
+
+// Uniforms are written to the current program. Slices are uploaded
+// flattened, matrices are row-major and uploaded without transpose.
+macro_rules! uniform {
+    ($($input:ty => |$context:ident, $location:ident, $value:ident| $body:expr;)*) => {
+        $(
+            impl Uniform<$input> for Context {
+                fn set_uniform(
+                    &self,
+                    program: &Self::Program,
+                    location: &Self::UniformLocation,
+                    input: $input,
+                ) {
+                    self.context.use_program(Some(program));
+
+                    let ($context, $location, $value) = (&self.context, Some(location), input);
+                    $body;
+                }
+            }
+        )*
+    };
 }
 
-impl Shader<[[f32; 3]; 3]> for ShaderProgram {
-    fn set_uniform(&self, name: &str, input: [[f32; 3]; 3]) {
-        let loc = BACKEND
-            .lock()
-            .unwrap()
-            .context
-            .get_uniform_location(&self.id, name)
-            .unwrap_or_else(|| panic!("Failed to find uniform location: {}", name));
-        BACKEND.lock().unwrap().context.use_program(Some(&self.id));
-
-        let input: [f32; 9] = [
-            input[0][0],
-            input[0][1],
-            input[0][2],
-            input[1][0],
-            input[1][1],
-            input[1][2],
-            input[2][0],
-            input[2][1],
-            input[2][2],
-        ];
-        BACKEND
-            .lock()
-            .unwrap()
-            .context
-            .uniform_matrix3fv_with_f32_array(Some(&loc), false, &input);
-    }
+uniform! {
+    f32 => |gl, location, input| gl.uniform1f(location, input);
+    [f32; 2] => |gl, location, input| gl.uniform2f(location, input[0], input[1]);
+    [f32; 3] => |gl, location, input| gl.uniform3f(location, input[0], input[1], input[2]);
+    [f32; 4] => |gl, location, input| {
+        gl.uniform4f(location, input[0], input[1], input[2], input[3])
+    };
+    i32 => |gl, location, input| gl.uniform1i(location, input);
+    [i32; 2] => |gl, location, input| gl.uniform2i(location, input[0], input[1]);
+    [i32; 3] => |gl, location, input| gl.uniform3i(location, input[0], input[1], input[2]);
+    [i32; 4] => |gl, location, input| {
+        gl.uniform4i(location, input[0], input[1], input[2], input[3])
+    };
+    u32 => |gl, location, input| gl.uniform1ui(location, input);
+    [u32; 2] => |gl, location, input| gl.uniform2ui(location, input[0], input[1]);
+    [u32; 3] => |gl, location, input| gl.uniform3ui(location, input[0], input[1], input[2]);
+    [u32; 4] => |gl, location, input| {
+        gl.uniform4ui(location, input[0], input[1], input[2], input[3])
+    };
+    bool => |gl, location, input| gl.uniform1i(location, input as i32);
+    [[f32; 2]; 2] => |gl, location, input| {
+        gl.uniform_matrix2fv_with_f32_array(location, false, input.as_flattened())
+    };
+    [[f32; 3]; 3] => |gl, location, input| {
+        gl.uniform_matrix3fv_with_f32_array(location, false, input.as_flattened())
+    };
+    [[f32; 4]; 4] => |gl, location, input| {
+        gl.uniform_matrix4fv_with_f32_array(location, false, input.as_flattened())
+    };
+    TextureUnit => |gl, location, input| gl.uniform1i(location, input.0 as i32);
+    &[f32] => |gl, location, input| gl.uniform1fv_with_f32_array(location, input);
+    &[[f32; 2]] => |gl, location, input| {
+        gl.uniform2fv_with_f32_array(location, input.as_flattened())
+    };
+    &[[f32; 3]] => |gl, location, input| {
+        gl.uniform3fv_with_f32_array(location, input.as_flattened())
+    };
+    &[[f32; 4]] => |gl, location, input| {
+        gl.uniform4fv_with_f32_array(location, input.as_flattened())
+    };
+    &[i32] => |gl, location, input| gl.uniform1iv_with_i32_array(location, input);
+    &[[i32; 2]] => |gl, location, input| {
+        gl.uniform2iv_with_i32_array(location, input.as_flattened())
+    };
+    &[[i32; 3]] => |gl, location, input| {
+        gl.uniform3iv_with_i32_array(location, input.as_flattened())
+    };
+    &[[i32; 4]] => |gl, location, input| {
+        gl.uniform4iv_with_i32_array(location, input.as_flattened())
+    };
+    &[u32] => |gl, location, input| gl.uniform1uiv_with_u32_array(location, input);
+    &[[u32; 2]] => |gl, location, input| {
+        gl.uniform2uiv_with_u32_array(location, input.as_flattened())
+    };
+    &[[u32; 3]] => |gl, location, input| {
+        gl.uniform3uiv_with_u32_array(location, input.as_flattened())
+    };
+    &[[u32; 4]] => |gl, location, input| {
+        gl.uniform4uiv_with_u32_array(location, input.as_flattened())
+    };
+    &[bool] => |gl, location, input| {
+        let input: Vec<i32> = input.iter().map(|&value| value as i32).collect();
+        gl.uniform1iv_with_i32_array(location, &input)
+    };
+    &[[[f32; 2]; 2]] => |gl, location, input| {
+        gl.uniform_matrix2fv_with_f32_array(location, false, input.as_flattened().as_flattened())
+    };
+    &[[[f32; 3]; 3]] => |gl, location, input| {
+        gl.uniform_matrix3fv_with_f32_array(location, false, input.as_flattened().as_flattened())
+    };
+    &[[[f32; 4]; 4]] => |gl, location, input| {
+        gl.uniform_matrix4fv_with_f32_array(location, false, input.as_flattened().as_flattened())
+    };
+    &[TextureUnit] => |gl, location, input| {
+        let input: Vec<i32> = input.iter().map(|unit| unit.0 as i32).collect();
+        gl.uniform1iv_with_i32_array(location, &input)
+    };
 }
 
-impl Shader<[[f32; 4]; 4]> for ShaderProgram {
-    fn set_uniform(&self, name: &str, input: [[f32; 4]; 4]) {
-        let loc = BACKEND
-            .lock()
-            .unwrap()
-            .context
-            .get_uniform_location(&self.id, name)
-            .unwrap_or_else(|| panic!("Failed to find uniform location: {}", name));
-        BACKEND.lock().unwrap().context.use_program(Some(&self.id));
-
-        let input: [f32; 16] = [
-            input[0][0],
-            input[0][1],
-            input[0][2],
-            input[0][3],
-            input[1][0],
-            input[1][1],
-            input[1][2],
-            input[1][3],
-            input[2][0],
-            input[2][1],
-            input[2][2],
-            input[2][3],
-            input[3][0],
-            input[3][1],
-            input[3][2],
-            input[3][3],
-        ];
-        BACKEND
-            .lock()
-            .unwrap()
-            .context
-            .uniform_matrix4fv_with_f32_array(Some(&loc), false, &input);
-    }
-}
+impl ShaderUtils for Context {
+    fn compile_program(&self, name: &str) -> Self::Program {
+        let fragment_shader =
+            compile_shader(&self.context, WebGl2RenderingContext::FRAGMENT_SHADER, name)
+                .expect("Compiling fragmet shader failed");
 
-pub struct ShaderLib;
+        let vertex_shader =
+            compile_shader(&self.context, WebGl2RenderingContext::VERTEX_SHADER, name)
+                .expect("Compiling vertex shader failed");
 
-impl ShaderUtils for ShaderLib {
-    fn compile_program(name: &str) -> <super::Context as Backend>::Program {
-        let fragment_shader = compile_shader(WebGl2RenderingContext::FRAGMENT_SHADER, name)
-            .expect("Compiling fragmet shader failed");
+        link_program(&self.context, &vertex_shader, &fragment_shader)
+    }
 
-        let vertex_shader = compile_shader(WebGl2RenderingContext::VERTEX_SHADER, name)
-            .expect("Compiling vertex shader failed");
+    fn active_uniforms(&self, program: &Self::Program) -> Vec<UniformInfo> {
+        let count = self
+            .context
+            .get_program_parameter(program, WebGl2RenderingContext::ACTIVE_UNIFORMS)
+            .as_f64()
+            .unwrap_or_default() as u32;
+
+        (0..count)
+            .filter_map(|index| self.context.get_active_uniform(program, index))
+            .map(|info| {
+                let name = info.name();
+
+                // Arrays are listed as their first element
+                UniformInfo {
+                    name: String::from(name.strip_suffix("[0]").unwrap_or(&name)),
+                    uniform_type: to_uniform_type(info.type_()),
+                    array_size: info.size() as u32,
+                }
+            })
+            .collect()
+    }
 
-        link_program(&vertex_shader, &fragment_shader)
+    fn uniform_location(&self, program: &Self::Program, name: &str) -> Self::UniformLocation {
+        self.context
+            .get_uniform_location(program, name)
+            .unwrap_or_else(|| panic!("Failed to find uniform location: {}", name))
     }
 }
 
-fn compile_shader(shader_type: u32, name: &str) -> Result<WebGlShader, String> {
+fn to_uniform_type(uniform_type: u32) -> UniformType {
+    type Gl = WebGl2RenderingContext;
+
+    match uniform_type {
+        Gl::FLOAT => UniformType::Float,
+        Gl::FLOAT_VEC2 => UniformType::Vec2,
+        Gl::FLOAT_VEC3 => UniformType::Vec3,
+        Gl::FLOAT_VEC4 => UniformType::Vec4,
+        Gl::INT => UniformType::Int,
+        Gl::INT_VEC2 => UniformType::IVec2,
+        Gl::INT_VEC3 => UniformType::IVec3,
+        Gl::INT_VEC4 => UniformType::IVec4,
+        Gl::UNSIGNED_INT => UniformType::UInt,
+        Gl::UNSIGNED_INT_VEC2 => UniformType::UVec2,
+        Gl::UNSIGNED_INT_VEC3 => UniformType::UVec3,
+        Gl::UNSIGNED_INT_VEC4 => UniformType::UVec4,
+        Gl::BOOL => UniformType::Bool,
+        Gl::BOOL_VEC2 => UniformType::BVec2,
+        Gl::BOOL_VEC3 => UniformType::BVec3,
+        Gl::BOOL_VEC4 => UniformType::BVec4,
+        Gl::FLOAT_MAT2 => UniformType::Mat2,
+        Gl::FLOAT_MAT3 => UniformType::Mat3,
+        Gl::FLOAT_MAT4 => UniformType::Mat4,
+        Gl::SAMPLER_2D
+        | Gl::INT_SAMPLER_2D
+        | Gl::UNSIGNED_INT_SAMPLER_2D
+        | Gl::SAMPLER_2D_SHADOW => UniformType::Sampler2D,
+        Gl::SAMPLER_3D | Gl::INT_SAMPLER_3D | Gl::UNSIGNED_INT_SAMPLER_3D => UniformType::Sampler3D,
+        Gl::SAMPLER_CUBE
+        | Gl::INT_SAMPLER_CUBE
+        | Gl::UNSIGNED_INT_SAMPLER_CUBE
+        | Gl::SAMPLER_CUBE_SHADOW => UniformType::SamplerCube,
+        Gl::SAMPLER_2D_ARRAY
+        | Gl::INT_SAMPLER_2D_ARRAY
+        | Gl::UNSIGNED_INT_SAMPLER_2D_ARRAY
+        | Gl::SAMPLER_2D_ARRAY_SHADOW => UniformType::Sampler2DArray,
+        _ => UniformType::Other,
+    }
+}
+
+fn compile_shader(
+    context: &WebGl2RenderingContext,
+    shader_type: u32,
+    name: &str,
+) -> Result<WebGlShader, String> {
     let shader_source = SHADERS[format!(
         "{}.{}.glsl",
         name,
@@ -112,60 +205,44 @@ fn compile_shader(shader_type: u32, name: &str) -> Result<WebGlShader, String> {
         }
     )
     .as_str()];
+    let shader_source = format!("{}{}", SHADER_HEADER, shader_source);
 
-    let shader = BACKEND
-        .lock()
-        .unwrap()
-        .context
+    let shader = context
         .create_shader(shader_type)
         .ok_or_else(|| String::from("Unable to create shader object"))?;
 
-    BACKEND
-        .lock()
-        .unwrap()
-        .context
-        .shader_source(&shader, shader_source);
-    BACKEND.lock().unwrap().context.compile_shader(&shader);
-
-    if BACKEND
-        .lock()
-        .unwrap()
-        .context
+    context.shader_source(&shader, &shader_source);
+    context.compile_shader(&shader);
+
+    if context
         .get_shader_parameter(&shader, WebGl2RenderingContext::COMPILE_STATUS)
         .as_bool()
         .unwrap_or(false)
     {
         Ok(shader)
     } else {
-        if let Some(err) = BACKEND.lock().unwrap().context.get_shader_info_log(&shader) {
+        if let Some(err) = context.get_shader_info_log(&shader) {
             let array = js_sys::Array::new();
             array.push(&err.into());
             web_sys::console::log(&array);
         }
 
-        Err(BACKEND
-            .lock()
-            .unwrap()
-            .context
+        Err(context
             .get_shader_info_log(&shader)
             .unwrap_or_else(|| String::from("Unknown error creating shader")))
     }
 }
 
-fn link_program(vertex_shader: &WebGlShader, fragment_shader: &WebGlShader) -> WebGlProgram {
-    let program = BACKEND.lock().unwrap().context.create_program().unwrap();
-
-    BACKEND
-        .lock()
-        .unwrap()
-        .context
-        .attach_shader(&program, vertex_shader);
-    BACKEND
-        .lock()
-        .unwrap()
-        .context
-        .attach_shader(&program, fragment_shader);
-    BACKEND.lock().unwrap().context.link_program(&program);
+fn link_program(
+    context: &WebGl2RenderingContext,
+    vertex_shader: &WebGlShader,
+    fragment_shader: &WebGlShader,
+) -> WebGlProgram {
+    let program = context.create_program().unwrap();
+
+    context.attach_shader(&program, vertex_shader);
+    context.attach_shader(&program, fragment_shader);
+    context.link_program(&program);
 
     program
 }
@@ -0,0 +1,1145 @@
+pub mod shader;
+
+use crate::{
+    entity::triangle_list,
+    types::{BufferTarget, BufferUsage, IndexType, PolygonMode, PrimitiveTopology, ScalarType},
+    BACKEND,
+};
+
+use super::{
+    backend::Backend,
+    common::{Capabilities, QueryResult},
+    WINDOW_HEIGHT, WINDOW_WIDTH,
+};
+use shader::UniformBlock;
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap},
+    num::NonZeroU64,
+};
+use wgpu::util::DeviceExt;
+#[cfg(target_arch = "wasm32")]
+use {
+    std::rc::Rc,
+    wasm_bindgen::{prelude::Closure, JsCast},
+};
+
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+#[cfg(not(target_arch = "wasm32"))]
+const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+pub struct Gpu {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    target: Target,
+    capabilities: Capabilities,
+}
+
+enum Target {
+    #[cfg(target_arch = "wasm32")]
+    Surface(wgpu::Surface<'static>, wgpu::SurfaceConfiguration),
+    #[cfg(not(target_arch = "wasm32"))]
+    Offscreen(wgpu::Texture),
+}
+
+impl Target {
+    fn format(&self) -> wgpu::TextureFormat {
+        match self {
+            #[cfg(target_arch = "wasm32")]
+            Target::Surface(_, config) => config.format,
+            #[cfg(not(target_arch = "wasm32"))]
+            Target::Offscreen(texture) => texture.format(),
+        }
+    }
+}
+
+impl Gpu {
+    async fn request() -> Gpu {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::from_env_or_default());
+
+        #[cfg(target_arch = "wasm32")]
+        let surface = instance
+            .create_surface(wgpu::SurfaceTarget::Canvas(create_canvas()))
+            .expect("Failed to create wgpu surface.");
+        #[cfg(target_arch = "wasm32")]
+        let compatible_surface = Some(&surface);
+        #[cfg(not(target_arch = "wasm32"))]
+        let compatible_surface = None;
+
+        // WGPU_ADAPTER_NAME=llvmpipe selects lavapipe when running without a GPU
+        let adapter =
+            wgpu::util::initialize_adapter_from_env_or_default(&instance, compatible_surface)
+                .await
+                .expect("Failed to find a wgpu adapter.");
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("reindeer"),
+                    required_features: wgpu::Features::empty(),
+                    required_limits: wgpu::Limits::downlevel_defaults()
+                        .using_resolution(adapter.limits()),
+                    memory_hints: wgpu::MemoryHints::default(),
+                },
+                None,
+            )
+            .await
+            .expect("Failed to create wgpu device.");
+
+        #[cfg(target_arch = "wasm32")]
+        let target = {
+            let config = surface
+                .get_default_config(&adapter, WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32)
+                .expect("Surface is not supported by the adapter.");
+            surface.configure(&device, &config);
+            Target::Surface(surface, config)
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let target = Target::Offscreen(device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("reindeer offscreen target"),
+            size: window_extent(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: OFFSCREEN_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        }));
+
+        let capabilities = capabilities(&adapter, &device, target.format());
+
+        Gpu {
+            device,
+            queue,
+            target,
+            capabilities,
+        }
+    }
+}
+
+thread_local! {
+    static PREPARED: RefCell<Option<Gpu>> = const { RefCell::new(None) };
+}
+
+// Adapter and device requests are asynchronous. Natively `Context::new` can
+// block on them, in the browser they have to be awaited before `init`.
+pub async fn prepare() {
+    let gpu = Gpu::request().await;
+    PREPARED.with(|prepared| *prepared.borrow_mut() = Some(gpu));
+}
+
+pub struct Program {
+    module: wgpu::ShaderModule,
+    uniforms: Option<UniformBlock>,
+    values: Vec<u8>,
+    bind_group_layout: Option<wgpu::BindGroupLayout>,
+    bind_group: Option<wgpu::BindGroup>,
+    layout: wgpu::PipelineLayout,
+}
+
+#[derive(Default)]
+struct VertexArray {
+    attributes: BTreeMap<u32, VertexAttribute>,
+    element_buffer: Option<usize>,
+}
+
+#[derive(Default)]
+struct VertexAttribute {
+    enabled: bool,
+    pointer: Option<VertexPointer>,
+    divisor: u32,
+}
+
+#[derive(Clone, Copy)]
+struct VertexPointer {
+    buffer: usize,
+    format: wgpu::VertexFormat,
+    stride: u64,
+    offset: u64,
+}
+
+struct VertexBufferSlot {
+    buffer: usize,
+    base: u64,
+    stride: u64,
+    step_mode: wgpu::VertexStepMode,
+    attributes: Vec<wgpu::VertexAttribute>,
+}
+
+struct Draw {
+    program: usize,
+    vao: usize,
+    topology: PrimitiveTopology,
+    first: u32,
+    count: u32,
+    instances: u32,
+    indices: Option<(usize, IndexType)>,
+    uniform_offset: Option<u32>,
+    // Index into fan_buffers for triangle fans drawn as lists
+    fan: Option<usize>,
+}
+
+pub struct Context {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    target: Target,
+    capabilities: Capabilities,
+    depth: wgpu::TextureView,
+    clear_color: wgpu::Color,
+    clear_requested: Cell<bool>,
+    buffers: RefCell<Vec<Option<wgpu::Buffer>>>,
+    vaos: RefCell<Vec<VertexArray>>,
+    programs: RefCell<Vec<Program>>,
+    pipelines: RefCell<HashMap<(usize, usize, PrimitiveTopology), wgpu::RenderPipeline>>,
+    bound_buffer: Cell<Option<usize>>,
+    bound_vao: Cell<Option<usize>>,
+    bound_program: Cell<Option<usize>>,
+    draws: RefCell<Vec<Draw>>,
+    uniform_data: RefCell<Vec<u8>>,
+    uniform_buffer: wgpu::Buffer,
+    // CPU copies of the buffers. wgpu has no triangle fans, they are turned
+    // into lists on the CPU, and buffer writes have to be whole words, so
+    // unaligned ones are rounded out with the bytes around them.
+    buffer_data: RefCell<HashMap<usize, Vec<u8>>>,
+    fan_buffers: RefCell<Vec<wgpu::Buffer>>,
+}
+
+impl Backend for Context {
+    type Context = Context;
+    type Buffer = usize;
+    type Program = usize;
+    type Shader = wgpu::ShaderModule;
+    type Vao = usize;
+    type Query = ();
+    // The offset in the program's uniform struct
+    type UniformLocation = u32;
+
+    fn new() -> Self::Context {
+        let gpu = PREPARED.with(|prepared| prepared.borrow_mut().take());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let gpu = gpu.unwrap_or_else(|| pollster::block_on(Gpu::request()));
+        #[cfg(target_arch = "wasm32")]
+        let gpu =
+            gpu.expect("reindeer::init_async has to be awaited before using wgpu in the browser.");
+
+        let depth = gpu
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("reindeer depth"),
+                size: window_extent(),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: DEPTH_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let uniform_buffer = create_uniform_buffer(&gpu.device, 1 << 16);
+
+        Context {
+            device: gpu.device,
+            queue: gpu.queue,
+            target: gpu.target,
+            capabilities: gpu.capabilities,
+            depth,
+            clear_color: wgpu::Color::BLACK,
+            clear_requested: Cell::new(false),
+            buffers: RefCell::new(Vec::new()),
+            vaos: RefCell::new(Vec::new()),
+            programs: RefCell::new(Vec::new()),
+            pipelines: RefCell::new(HashMap::new()),
+            bound_buffer: Cell::new(None),
+            bound_vao: Cell::new(None),
+            bound_program: Cell::new(None),
+            draws: RefCell::new(Vec::new()),
+            uniform_data: RefCell::new(Vec::new()),
+            uniform_buffer,
+            buffer_data: RefCell::new(HashMap::new()),
+            fan_buffers: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities.clone()
+    }
+
+    fn use_program(&self, program: &Self::Program) {
+        self.bound_program.set(Some(*program));
+    }
+
+    fn create_buffer(&self) -> Self::Buffer {
+        let mut buffers = self.buffers.borrow_mut();
+        buffers.push(None);
+        buffers.len() - 1
+    }
+
+    fn bind_buffer(&self, target: BufferTarget, buffer: &Self::Buffer) {
+        match target {
+            BufferTarget::Array => self.bound_buffer.set(Some(*buffer)),
+            // The element buffer binding is part of the vertex array in GL
+            BufferTarget::ElementArray => {
+                self.with_bound_vao(|vao| vao.element_buffer = Some(*buffer))
+            }
+        }
+    }
+
+    fn buffer_data(&self, data: &[u8], target: BufferTarget, _usage: BufferUsage) {
+        let buffer = self
+            .bound_buffer_for(target)
+            .expect("buffer_data called without a bound buffer");
+
+        self.buffer_data.borrow_mut().insert(buffer, data.to_vec());
+
+        self.buffers.borrow_mut()[buffer] = Some(self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: data,
+                usage: wgpu::BufferUsages::VERTEX
+                    | wgpu::BufferUsages::INDEX
+                    | wgpu::BufferUsages::COPY_DST,
+            },
+        ));
+    }
+
+    // Draws are replayed in after_draw, after the queued writes, so every
+    // draw of the frame sees the last update
+    fn buffer_sub_data(&self, data: &[u8], target: BufferTarget, offset: i32) {
+        let buffer = self
+            .bound_buffer_for(target)
+            .expect("buffer_sub_data called without a bound buffer");
+
+        let mut buffer_data = self.buffer_data.borrow_mut();
+        let shadow = buffer_data
+            .get_mut(&buffer)
+            .expect("buffer_sub_data called on a buffer without data");
+        let buffers = self.buffers.borrow();
+        let buffer = buffers[buffer].as_ref().unwrap();
+
+        let offset = offset as usize;
+        assert!(
+            offset + data.len() <= shadow.len(),
+            "buffer_sub_data writes past the end of the buffer"
+        );
+        shadow[offset..offset + data.len()].copy_from_slice(data);
+
+        if data.is_empty() {
+            return;
+        }
+
+        // create_buffer_init pads the buffer with zeros to the alignment, so
+        // the rounded range may reach past the copy
+        let alignment = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+        let start = offset / alignment * alignment;
+        let end = (offset + data.len()).next_multiple_of(alignment);
+        let mut words = shadow[start..end.min(shadow.len())].to_vec();
+        words.resize(end - start, 0);
+
+        self.queue.write_buffer(buffer, start as u64, &words);
+    }
+
+    fn create_vertex_array(&self) -> Self::Vao {
+        let mut vaos = self.vaos.borrow_mut();
+        vaos.push(VertexArray::default());
+        vaos.len() - 1
+    }
+
+    fn bind_vertex_array(&self, vao: &Self::Vao) {
+        self.bound_vao.set(Some(*vao));
+    }
+
+    fn vertex_attrib_pointer(
+        &self,
+        index: u32,
+        size: i32,
+        type_: ScalarType,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    ) {
+        let format = vertex_format(size, type_, normalized);
+        let stride = match stride {
+            0 => format.size(),
+            _ => stride as u64,
+        };
+
+        let pointer = VertexPointer {
+            buffer: self
+                .bound_buffer
+                .get()
+                .expect("vertex_attrib_pointer called without a bound buffer"),
+            format,
+            stride,
+            offset: offset as u64,
+        };
+
+        self.with_bound_vao(|vao| {
+            vao.attributes.entry(index).or_default().pointer = Some(pointer);
+        });
+    }
+
+    // Draws are recorded and replayed in after_draw, there is nothing to
+    // time while they are issued. `Capabilities::timer_query` is false.
+    fn create_timer_query(&self) -> Self::Query {
+        panic!("Timer queries are not supported by the wgpu backend");
+    }
+
+    fn begin_timer_query(&self, _query: &Self::Query) {
+        panic!("Timer queries are not supported by the wgpu backend");
+    }
+
+    fn end_timer_query(&self, _query: &Self::Query) {
+        panic!("Timer queries are not supported by the wgpu backend");
+    }
+
+    fn timer_query_result(&self, _query: &Self::Query) -> QueryResult {
+        panic!("Timer queries are not supported by the wgpu backend");
+    }
+
+    fn enable_vertex_attrib_array(&self, index: u32) {
+        self.with_bound_vao(|vao| {
+            vao.attributes.entry(index).or_default().enabled = true;
+        });
+    }
+
+    // Attributes either advance per vertex or per instance, there are no
+    // divisors above 1
+    fn vertex_attrib_divisor(&self, index: u32, divisor: u32) {
+        assert!(
+            divisor <= 1,
+            "The wgpu backend only supports vertex attribute divisors of 0 and 1"
+        );
+
+        self.with_bound_vao(|vao| {
+            vao.attributes.entry(index).or_default().divisor = divisor;
+        });
+    }
+
+    fn draw_arrays(&self, topology: PrimitiveTopology, first: i32, vertex_count: i32) {
+        self.draw_arrays_instanced(topology, first, vertex_count, 1);
+    }
+
+    fn draw_elements(
+        &self,
+        topology: PrimitiveTopology,
+        index_count: i32,
+        index_type: IndexType,
+        offset: i32,
+    ) {
+        self.draw_elements_instanced(topology, index_count, index_type, offset, 1);
+    }
+
+    fn draw_arrays_instanced(
+        &self,
+        topology: PrimitiveTopology,
+        first: i32,
+        vertex_count: i32,
+        instance_count: i32,
+    ) {
+        self.record_draw(
+            topology,
+            first as u32,
+            vertex_count as u32,
+            instance_count as u32,
+            None,
+        );
+    }
+
+    fn draw_elements_instanced(
+        &self,
+        topology: PrimitiveTopology,
+        index_count: i32,
+        index_type: IndexType,
+        offset: i32,
+        instance_count: i32,
+    ) {
+        let element_buffer = self
+            .bound_vao
+            .get()
+            .and_then(|vao| self.vaos.borrow()[vao].element_buffer)
+            .expect("draw_elements called without a bound element buffer");
+
+        self.record_draw(
+            topology,
+            offset as u32 / index_type.size(),
+            index_count as u32,
+            instance_count as u32,
+            Some((element_buffer, index_type)),
+        );
+    }
+
+    fn set_clear_color(&mut self, red: f32, green: f32, blue: f32, alpha: f32) {
+        self.clear_color = wgpu::Color {
+            r: red as f64,
+            g: green as f64,
+            b: blue as f64,
+            a: alpha as f64,
+        };
+    }
+
+    fn clear(&self) {
+        self.clear_requested.set(true);
+    }
+
+    // Line polygon mode is a native-only wgpu feature,
+    // `Capabilities::polygon_mode` is false
+    fn set_polygon_mode(&self, _mode: PolygonMode) {}
+
+    fn before_draw(&mut self) {
+        self.draws.get_mut().clear();
+        self.fan_buffers.get_mut().clear();
+        self.uniform_data.get_mut().clear();
+        self.clear();
+    }
+
+    fn after_draw(&mut self) {
+        let draws = std::mem::take(self.draws.get_mut());
+
+        self.create_pipelines(&draws);
+        self.upload_uniforms();
+
+        #[cfg(target_arch = "wasm32")]
+        let frame = match &self.target {
+            Target::Surface(surface, _) => surface
+                .get_current_texture()
+                .expect("Failed to acquire next surface texture."),
+        };
+        #[cfg(target_arch = "wasm32")]
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let view = match &self.target {
+            Target::Offscreen(texture) => {
+                texture.create_view(&wgpu::TextureViewDescriptor::default())
+            }
+        };
+
+        let (color_load, depth_load) = if self.clear_requested.replace(false) {
+            (
+                wgpu::LoadOp::Clear(self.clear_color),
+                wgpu::LoadOp::Clear(1.0),
+            )
+        } else {
+            (wgpu::LoadOp::Load, wgpu::LoadOp::Load)
+        };
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("reindeer frame"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: color_load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth,
+                    depth_ops: Some(wgpu::Operations {
+                        load: depth_load,
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            let pipelines = self.pipelines.borrow();
+            let programs = self.programs.borrow();
+            let buffers = self.buffers.borrow();
+            let vaos = self.vaos.borrow();
+            let fan_buffers = self.fan_buffers.borrow();
+
+            for draw in &draws {
+                pass.set_pipeline(&pipelines[&(draw.program, draw.vao, draw.topology)]);
+
+                if let Some(offset) = draw.uniform_offset {
+                    pass.set_bind_group(0, programs[draw.program].bind_group.as_ref(), &[offset]);
+                }
+
+                for (slot, layout) in vertex_buffer_slots(&vaos[draw.vao]).iter().enumerate() {
+                    let buffer = buffers[layout.buffer]
+                        .as_ref()
+                        .expect("Vertex attribute points to a buffer without data");
+                    pass.set_vertex_buffer(slot as u32, buffer.slice(layout.base..));
+                }
+
+                if let Some(fan) = draw.fan {
+                    pass.set_index_buffer(fan_buffers[fan].slice(..), wgpu::IndexFormat::Uint32);
+                    pass.draw_indexed(0..draw.count, 0, 0..draw.instances);
+                    continue;
+                }
+
+                match draw.indices {
+                    Some((buffer, index_type)) => {
+                        let buffer = buffers[buffer]
+                            .as_ref()
+                            .expect("Element buffer has no data");
+                        pass.set_index_buffer(buffer.slice(..), index_format(index_type));
+                        pass.draw_indexed(
+                            draw.first..draw.first + draw.count,
+                            0,
+                            0..draw.instances,
+                        );
+                    }
+                    None => pass.draw(draw.first..draw.first + draw.count, 0..draw.instances),
+                }
+            }
+        }
+
+        self.queue.submit(Some(encoder.finish()));
+
+        #[cfg(target_arch = "wasm32")]
+        frame.present();
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn draw_loop(mut draw_frame: impl FnMut() + 'static) {
+        loop {
+            BACKEND.lock().unwrap().before_draw();
+            draw_frame();
+            BACKEND.lock().unwrap().after_draw();
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn draw_loop(mut draw_frame: impl FnMut() + 'static) {
+        let f = Rc::new(RefCell::new(None));
+        let g = f.clone();
+
+        *g.borrow_mut() = Some(Closure::wrap(Box::new(move || {
+            BACKEND.lock().unwrap().before_draw();
+            draw_frame();
+            BACKEND.lock().unwrap().after_draw();
+            request_animation_frame(f.borrow().as_ref().unwrap());
+        }) as Box<dyn FnMut()>));
+
+        request_animation_frame(g.borrow().as_ref().unwrap());
+    }
+}
+
+impl Context {
+    pub(crate) fn create_program(
+        &self,
+        name: &str,
+        source: &str,
+        uniforms: Option<UniformBlock>,
+    ) -> usize {
+        let module = self
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(name),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+
+        let bind_group_layout = uniforms.as_ref().map(|block| {
+            self.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some(name),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: NonZeroU64::new(block.size as u64),
+                        },
+                        count: None,
+                    }],
+                })
+        });
+
+        let layout = self
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(name),
+                bind_group_layouts: &bind_group_layout.iter().collect::<Vec<_>>(),
+                push_constant_ranges: &[],
+            });
+
+        let values = vec![0; uniforms.as_ref().map_or(0, |block| block.size as usize)];
+
+        let mut programs = self.programs.borrow_mut();
+        programs.push(Program {
+            module,
+            uniforms,
+            values,
+            bind_group_layout,
+            bind_group: None,
+            layout,
+        });
+        programs.len() - 1
+    }
+
+    pub(crate) fn write_uniform(&self, program: &usize, offset: &u32, bytes: &[u8]) {
+        let offset = *offset as usize;
+        self.programs.borrow_mut()[*program].values[offset..offset + bytes.len()]
+            .copy_from_slice(bytes);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_pixels(&self) -> Vec<u8> {
+        let Target::Offscreen(texture) = &self.target;
+
+        let row_bytes = WINDOW_WIDTH as u32 * 4;
+        let padded_row_bytes = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("reindeer readback"),
+            size: (padded_row_bytes * WINDOW_HEIGHT as u32) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            window_extent(),
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| {
+            result.expect("Failed to map readback buffer.")
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+
+        let mapped = slice.get_mapped_range();
+        mapped
+            .chunks(padded_row_bytes as usize)
+            .flat_map(|row| &row[..row_bytes as usize])
+            .copied()
+            .collect()
+    }
+
+    fn record_draw(
+        &self,
+        topology: PrimitiveTopology,
+        first: u32,
+        count: u32,
+        instances: u32,
+        indices: Option<(usize, IndexType)>,
+    ) {
+        let program = self
+            .bound_program
+            .get()
+            .expect("Draw called without a program in use");
+        let vao = self
+            .bound_vao
+            .get()
+            .expect("Draw called without a bound vertex array");
+
+        let (topology, first, count, fan) = if topology == PrimitiveTopology::TriangleFan {
+            let list = self.fan_to_list(first, count, indices);
+            if list.is_empty() {
+                return;
+            }
+
+            let mut fan_buffers = self.fan_buffers.borrow_mut();
+            fan_buffers.push(
+                self.device
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some("reindeer triangle fan"),
+                        contents: &list
+                            .iter()
+                            .flat_map(|index| index.to_ne_bytes())
+                            .collect::<Vec<_>>(),
+                        usage: wgpu::BufferUsages::INDEX,
+                    }),
+            );
+            let fan = Some(fan_buffers.len() - 1);
+            (PrimitiveTopology::Triangles, 0, list.len() as u32, fan)
+        } else {
+            (topology, first, count, None)
+        };
+
+        // Uniforms are program state in GL, so every draw gets a snapshot of
+        // the values the program has at the time of the call.
+        let uniform_offset = {
+            let programs = self.programs.borrow();
+            let values = &programs[program].values;
+
+            if values.is_empty() {
+                None
+            } else {
+                let alignment = self.device.limits().min_uniform_buffer_offset_alignment as usize;
+                let mut data = self.uniform_data.borrow_mut();
+                let offset = data.len().div_ceil(alignment) * alignment;
+                data.resize(offset, 0);
+                data.extend_from_slice(values);
+                Some(offset as u32)
+            }
+        };
+
+        self.draws.borrow_mut().push(Draw {
+            program,
+            vao,
+            topology,
+            first,
+            count,
+            instances,
+            indices,
+            uniform_offset,
+            fan,
+        });
+    }
+
+    // The vertex indices of a fan as a triangle list
+    fn fan_to_list(&self, first: u32, count: u32, indices: Option<(usize, IndexType)>) -> Vec<u32> {
+        let fan: Vec<u32> = match indices {
+            Some((buffer, index_type)) => {
+                let buffer_data = self.buffer_data.borrow();
+                let data = buffer_data
+                    .get(&buffer)
+                    .expect("Element buffer has no data");
+                let (first, count) = (first as usize, count as usize);
+
+                match index_type {
+                    IndexType::U16 => data
+                        .chunks_exact(2)
+                        .skip(first)
+                        .take(count)
+                        .map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]) as u32)
+                        .collect(),
+                    IndexType::U32 => data
+                        .chunks_exact(4)
+                        .skip(first)
+                        .take(count)
+                        .map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()))
+                        .collect(),
+                }
+            }
+            None => (first..first + count).collect(),
+        };
+
+        triangle_list(&fan, PrimitiveTopology::TriangleFan)
+    }
+
+    // The vertex layout is baked into the pipelines, they are created again
+    // after it changes
+    fn with_bound_vao(&self, f: impl FnOnce(&mut VertexArray)) {
+        let vao = self.bound_vao.get().expect("No vertex array is bound");
+        f(&mut self.vaos.borrow_mut()[vao]);
+        self.pipelines
+            .borrow_mut()
+            .retain(|(_, pipeline_vao, _), _| *pipeline_vao != vao);
+    }
+
+    fn bound_buffer_for(&self, target: BufferTarget) -> Option<usize> {
+        match target {
+            BufferTarget::Array => self.bound_buffer.get(),
+            BufferTarget::ElementArray => self
+                .bound_vao
+                .get()
+                .and_then(|vao| self.vaos.borrow()[vao].element_buffer),
+        }
+    }
+
+    fn create_pipelines(&self, draws: &[Draw]) {
+        let mut pipelines = self.pipelines.borrow_mut();
+        let programs = self.programs.borrow();
+        let vaos = self.vaos.borrow();
+
+        for draw in draws {
+            let key = (draw.program, draw.vao, draw.topology);
+            if pipelines.contains_key(&key) {
+                continue;
+            }
+
+            let program = &programs[draw.program];
+            let slots = vertex_buffer_slots(&vaos[draw.vao]);
+            let buffers = slots
+                .iter()
+                .map(|slot| wgpu::VertexBufferLayout {
+                    array_stride: slot.stride,
+                    step_mode: slot.step_mode,
+                    attributes: &slot.attributes,
+                })
+                .collect::<Vec<_>>();
+
+            let pipeline = self
+                .device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: None,
+                    layout: Some(&program.layout),
+                    vertex: wgpu::VertexState {
+                        module: &program.module,
+                        entry_point: Some("vs_main"),
+                        compilation_options: Default::default(),
+                        buffers: &buffers,
+                    },
+                    primitive: wgpu::PrimitiveState {
+                        topology: primitive_topology(draw.topology),
+                        ..Default::default()
+                    },
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: DEPTH_FORMAT,
+                        depth_write_enabled: true,
+                        depth_compare: wgpu::CompareFunction::Less,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState::default(),
+                    fragment: Some(wgpu::FragmentState {
+                        module: &program.module,
+                        entry_point: Some("fs_main"),
+                        compilation_options: Default::default(),
+                        targets: &[Some(self.target.format().into())],
+                    }),
+                    multiview: None,
+                    cache: None,
+                });
+
+            pipelines.insert(key, pipeline);
+        }
+    }
+
+    fn upload_uniforms(&mut self) {
+        let data = self.uniform_data.get_mut();
+        if data.is_empty() {
+            return;
+        }
+
+        let programs = self.programs.get_mut();
+
+        if data.len() as u64 > self.uniform_buffer.size() {
+            self.uniform_buffer =
+                create_uniform_buffer(&self.device, (data.len() as u64).next_power_of_two());
+            programs
+                .iter_mut()
+                .for_each(|program| program.bind_group = None);
+        }
+
+        for program in programs.iter_mut() {
+            if let (None, Some(layout)) = (&program.bind_group, &program.bind_group_layout) {
+                program.bind_group =
+                    Some(self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: None,
+                        layout,
+                        entries: &[wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                                buffer: &self.uniform_buffer,
+                                offset: 0,
+                                size: NonZeroU64::new(program.values.len() as u64),
+                            }),
+                        }],
+                    }));
+            }
+        }
+
+        self.queue.write_buffer(&self.uniform_buffer, 0, data);
+    }
+}
+
+// The adapter is only around while the device is requested, so everything is
+// queried up front.
+fn capabilities(
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+) -> Capabilities {
+    let info = adapter.get_info();
+    let limits = device.limits();
+
+    Capabilities {
+        version: format!("{:?} {} {}", info.backend, info.driver, info.driver_info)
+            .trim()
+            .to_string(),
+        renderer: info.name,
+        vendor: format!("{:#06x}", info.vendor),
+        max_texture_size: limits.max_texture_dimension_2d,
+        max_vertex_attributes: limits.max_vertex_attributes,
+        max_uniform_block_size: limits.max_uniform_buffer_binding_size,
+        max_samples: adapter
+            .get_texture_format_features(format)
+            .flags
+            .supported_sample_counts()
+            .into_iter()
+            .max()
+            .unwrap_or(1),
+        timer_query: false,
+        polygon_mode: false,
+        // WebGPU has no point sizes
+        max_point_size: 1.0,
+        extensions: device
+            .features()
+            .iter_names()
+            .map(|(name, _)| name.to_string())
+            .collect(),
+    }
+}
+
+// Attributes sourced from the same buffer with the same stride share a vertex
+// buffer slot. GL offsets past the stride become the start of the slice.
+fn vertex_buffer_slots(vao: &VertexArray) -> Vec<VertexBufferSlot> {
+    let mut slots: Vec<VertexBufferSlot> = Vec::new();
+
+    for (location, attribute) in &vao.attributes {
+        let pointer = match (attribute.enabled, attribute.pointer) {
+            (true, Some(pointer)) => pointer,
+            _ => continue,
+        };
+
+        let base = pointer.offset - pointer.offset % pointer.stride;
+        let step_mode = match attribute.divisor {
+            0 => wgpu::VertexStepMode::Vertex,
+            _ => wgpu::VertexStepMode::Instance,
+        };
+        let vertex_attribute = wgpu::VertexAttribute {
+            format: pointer.format,
+            offset: pointer.offset - base,
+            shader_location: *location,
+        };
+
+        match slots.iter_mut().find(|slot| {
+            slot.buffer == pointer.buffer
+                && slot.stride == pointer.stride
+                && slot.base == base
+                && slot.step_mode == step_mode
+        }) {
+            Some(slot) => slot.attributes.push(vertex_attribute),
+            None => slots.push(VertexBufferSlot {
+                buffer: pointer.buffer,
+                base,
+                stride: pointer.stride,
+                step_mode,
+                attributes: vec![vertex_attribute],
+            }),
+        }
+    }
+
+    slots
+}
+
+// WGSL has no scaled formats, integer attributes that aren't normalized
+// reach the shader as u32/i32 vectors
+fn vertex_format(size: i32, type_: ScalarType, normalized: bool) -> wgpu::VertexFormat {
+    match (type_, size, normalized) {
+        (ScalarType::F32, 1, _) => wgpu::VertexFormat::Float32,
+        (ScalarType::F32, 2, _) => wgpu::VertexFormat::Float32x2,
+        (ScalarType::F32, 3, _) => wgpu::VertexFormat::Float32x3,
+        (ScalarType::F32, 4, _) => wgpu::VertexFormat::Float32x4,
+        (ScalarType::F16, 1, _) => wgpu::VertexFormat::Float16,
+        (ScalarType::F16, 2, _) => wgpu::VertexFormat::Float16x2,
+        (ScalarType::F16, 4, _) => wgpu::VertexFormat::Float16x4,
+        (ScalarType::U8, 1, true) => wgpu::VertexFormat::Unorm8,
+        (ScalarType::U8, 2, true) => wgpu::VertexFormat::Unorm8x2,
+        (ScalarType::U8, 4, true) => wgpu::VertexFormat::Unorm8x4,
+        (ScalarType::U8, 1, false) => wgpu::VertexFormat::Uint8,
+        (ScalarType::U8, 2, false) => wgpu::VertexFormat::Uint8x2,
+        (ScalarType::U8, 4, false) => wgpu::VertexFormat::Uint8x4,
+        (ScalarType::I8, 1, true) => wgpu::VertexFormat::Snorm8,
+        (ScalarType::I8, 2, true) => wgpu::VertexFormat::Snorm8x2,
+        (ScalarType::I8, 4, true) => wgpu::VertexFormat::Snorm8x4,
+        (ScalarType::I8, 1, false) => wgpu::VertexFormat::Sint8,
+        (ScalarType::I8, 2, false) => wgpu::VertexFormat::Sint8x2,
+        (ScalarType::I8, 4, false) => wgpu::VertexFormat::Sint8x4,
+        (ScalarType::U16, 1, true) => wgpu::VertexFormat::Unorm16,
+        (ScalarType::U16, 2, true) => wgpu::VertexFormat::Unorm16x2,
+        (ScalarType::U16, 4, true) => wgpu::VertexFormat::Unorm16x4,
+        (ScalarType::U16, 1, false) => wgpu::VertexFormat::Uint16,
+        (ScalarType::U16, 2, false) => wgpu::VertexFormat::Uint16x2,
+        (ScalarType::U16, 4, false) => wgpu::VertexFormat::Uint16x4,
+        (ScalarType::I16, 1, true) => wgpu::VertexFormat::Snorm16,
+        (ScalarType::I16, 2, true) => wgpu::VertexFormat::Snorm16x2,
+        (ScalarType::I16, 4, true) => wgpu::VertexFormat::Snorm16x4,
+        (ScalarType::I16, 1, false) => wgpu::VertexFormat::Sint16,
+        (ScalarType::I16, 2, false) => wgpu::VertexFormat::Sint16x2,
+        (ScalarType::I16, 4, false) => wgpu::VertexFormat::Sint16x4,
+        (ScalarType::U32, 1, false) => wgpu::VertexFormat::Uint32,
+        (ScalarType::U32, 2, false) => wgpu::VertexFormat::Uint32x2,
+        (ScalarType::U32, 3, false) => wgpu::VertexFormat::Uint32x3,
+        (ScalarType::U32, 4, false) => wgpu::VertexFormat::Uint32x4,
+        (ScalarType::I32, 1, false) => wgpu::VertexFormat::Sint32,
+        (ScalarType::I32, 2, false) => wgpu::VertexFormat::Sint32x2,
+        (ScalarType::I32, 3, false) => wgpu::VertexFormat::Sint32x3,
+        (ScalarType::I32, 4, false) => wgpu::VertexFormat::Sint32x4,
+        _ => panic!(
+            "Unsupported vertex attribute format: {} x {:?} (normalized: {})",
+            size, type_, normalized
+        ),
+    }
+}
+
+fn index_format(index_type: IndexType) -> wgpu::IndexFormat {
+    match index_type {
+        IndexType::U16 => wgpu::IndexFormat::Uint16,
+        IndexType::U32 => wgpu::IndexFormat::Uint32,
+    }
+}
+
+fn primitive_topology(topology: PrimitiveTopology) -> wgpu::PrimitiveTopology {
+    match topology {
+        PrimitiveTopology::Points => wgpu::PrimitiveTopology::PointList,
+        PrimitiveTopology::Lines => wgpu::PrimitiveTopology::LineList,
+        PrimitiveTopology::LineStrip => wgpu::PrimitiveTopology::LineStrip,
+        PrimitiveTopology::Triangles => wgpu::PrimitiveTopology::TriangleList,
+        PrimitiveTopology::TriangleStrip => wgpu::PrimitiveTopology::TriangleStrip,
+        // Recorded as lists, see record_draw
+        PrimitiveTopology::TriangleFan => unreachable!("Triangle fans are drawn as lists"),
+    }
+}
+
+fn create_uniform_buffer(device: &wgpu::Device, size: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("reindeer uniforms"),
+        size,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn window_extent() -> wgpu::Extent3d {
+    wgpu::Extent3d {
+        width: WINDOW_WIDTH as u32,
+        height: WINDOW_HEIGHT as u32,
+        depth_or_array_layers: 1,
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn create_canvas() -> web_sys::HtmlCanvasElement {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let body = document.query_selector("body").unwrap().unwrap();
+    let canvas = document.create_element("canvas").unwrap();
+    canvas
+        .set_attribute("width", &WINDOW_WIDTH.to_string())
+        .unwrap();
+    canvas
+        .set_attribute("height", &WINDOW_HEIGHT.to_string())
+        .unwrap();
+    body.append_child(&canvas).unwrap();
+
+    canvas.dyn_into::<web_sys::HtmlCanvasElement>().unwrap()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn request_animation_frame(f: &Closure<dyn FnMut()>) -> i32 {
+    web_sys::window()
+        .unwrap()
+        .request_animation_frame(f.as_ref().unchecked_ref())
+        .expect("should register `requestAnimationFrame` OK")
+}
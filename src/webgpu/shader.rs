@@ -0,0 +1,275 @@
+use super::Context;
+use crate::{
+    common::{ShaderUtils, TextureUnit, Uniform, UniformInfo, UniformType},
+    SHADERS,
+};
+use std::collections::HashMap;
+use wgpu::naga;
+
+// How a value is laid out in the uniform struct
+trait UniformBytes {
+    fn write_bytes(&self, bytes: &mut Vec<u8>);
+}
+
+macro_rules! uniform_bytes {
+    ($($scalar:ty),*) => {
+        $(
+            impl UniformBytes for $scalar {
+                fn write_bytes(&self, bytes: &mut Vec<u8>) {
+                    bytes.extend_from_slice(&self.to_ne_bytes());
+                }
+            }
+
+            // Vectors are packed, a vec3<f32> takes 12 bytes
+            impl<const N: usize> UniformBytes for [$scalar; N] {
+                fn write_bytes(&self, bytes: &mut Vec<u8>) {
+                    self.iter().for_each(|value| value.write_bytes(bytes));
+                }
+            }
+        )*
+    };
+}
+
+uniform_bytes!(f32, i32, u32);
+
+impl UniformBytes for bool {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        // WGSL has no booleans in uniform buffers, they are written as u32
+        (*self as u32).write_bytes(bytes);
+    }
+}
+
+impl UniformBytes for [[f32; 2]; 2] {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        self.iter().for_each(|column| column.write_bytes(bytes));
+    }
+}
+
+impl UniformBytes for [[f32; 3]; 3] {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        // mat3x3<f32> columns are padded to 16 bytes in uniform buffers
+        for column in self {
+            column.write_bytes(bytes);
+            bytes.resize(bytes.len().next_multiple_of(16), 0);
+        }
+    }
+}
+
+impl UniformBytes for [[f32; 4]; 4] {
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        self.iter().for_each(|column| column.write_bytes(bytes));
+    }
+}
+
+impl<Input: UniformBytes> Uniform<Input> for Context {
+    fn set_uniform(&self, program: &Self::Program, location: &Self::UniformLocation, input: Input) {
+        let mut bytes = Vec::new();
+        input.write_bytes(&mut bytes);
+        self.write_uniform(program, location, &bytes);
+    }
+}
+
+impl<Input: UniformBytes> Uniform<&[Input]> for Context {
+    fn set_uniform(
+        &self,
+        program: &Self::Program,
+        location: &Self::UniformLocation,
+        input: &[Input],
+    ) {
+        // Arrays in uniform buffers have a stride of 16 bytes at least
+        let mut bytes = Vec::new();
+        for element in input {
+            element.write_bytes(&mut bytes);
+            bytes.resize(bytes.len().next_multiple_of(16), 0);
+        }
+        self.write_uniform(program, location, &bytes);
+    }
+}
+
+// Textures are bound in bind groups of their own, a WGSL uniform struct never
+// has a sampler to write to
+impl Uniform<TextureUnit> for Context {
+    fn set_uniform(&self, _: &Self::Program, _: &Self::UniformLocation, _: TextureUnit) {}
+}
+
+impl Uniform<&[TextureUnit]> for Context {
+    fn set_uniform(&self, _: &Self::Program, _: &Self::UniformLocation, _: &[TextureUnit]) {}
+}
+
+pub struct UniformBlock {
+    pub fields: HashMap<String, u32>,
+    pub uniforms: Vec<UniformInfo>,
+    pub size: u32,
+}
+
+impl ShaderUtils for Context {
+    fn compile_program(&self, name: &str) -> Self::Program {
+        let source = SHADERS[format!("{}.wgsl", name).as_str()];
+        let uniforms = reflect_uniforms(source).unwrap();
+
+        self.create_program(name, source, uniforms)
+    }
+
+    fn active_uniforms(&self, program: &Self::Program) -> Vec<UniformInfo> {
+        self.programs.borrow()[*program]
+            .uniforms
+            .as_ref()
+            .map(|block| block.uniforms.clone())
+            .unwrap_or_default()
+    }
+
+    fn uniform_location(&self, program: &Self::Program, name: &str) -> Self::UniformLocation {
+        self.programs.borrow()[*program]
+            .uniforms
+            .as_ref()
+            .and_then(|block| block.fields.get(name).copied())
+            .unwrap_or_else(|| panic!("Failed to find uniform location: {}", name))
+    }
+}
+
+// Every shader keeps its uniforms in a single struct at @group(0) @binding(0),
+// the offsets of its members are where `set_uniform` writes by name.
+fn reflect_uniforms(source: &str) -> Result<Option<UniformBlock>, String> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|err| err.emit_to_string(source))?;
+
+    let uniforms = module.global_variables.iter().find(|(_, variable)| {
+        variable.space == naga::AddressSpace::Uniform
+            && variable.binding
+                == Some(naga::ResourceBinding {
+                    group: 0,
+                    binding: 0,
+                })
+    });
+
+    let Some((_, uniforms)) = uniforms else {
+        return Ok(None);
+    };
+
+    match &module.types[uniforms.ty].inner {
+        naga::TypeInner::Struct { members, span } => Ok(Some(UniformBlock {
+            fields: members
+                .iter()
+                .filter_map(|member| member.name.clone().map(|name| (name, member.offset)))
+                .collect(),
+            uniforms: members
+                .iter()
+                .filter_map(|member| {
+                    member
+                        .name
+                        .clone()
+                        .map(|name| uniform_info(&module, name, member.ty))
+                })
+                .collect(),
+            size: *span,
+        })),
+        _ => Err(String::from(
+            "The uniform at @group(0) @binding(0) has to be a struct",
+        )),
+    }
+}
+
+fn uniform_info(module: &naga::Module, name: String, ty: naga::Handle<naga::Type>) -> UniformInfo {
+    let (inner, array_size) = match &module.types[ty].inner {
+        naga::TypeInner::Array {
+            base,
+            size: naga::ArraySize::Constant(size),
+            ..
+        } => (&module.types[*base].inner, size.get()),
+        inner => (inner, 1),
+    };
+
+    UniformInfo {
+        name,
+        uniform_type: to_uniform_type(inner),
+        array_size,
+    }
+}
+
+fn to_uniform_type(inner: &naga::TypeInner) -> UniformType {
+    use naga::{ScalarKind, TypeInner, VectorSize};
+
+    match *inner {
+        TypeInner::Scalar(scalar) => match scalar.kind {
+            ScalarKind::Float => UniformType::Float,
+            ScalarKind::Sint => UniformType::Int,
+            ScalarKind::Uint => UniformType::UInt,
+            ScalarKind::Bool => UniformType::Bool,
+            _ => UniformType::Other,
+        },
+        TypeInner::Vector { size, scalar } => match (scalar.kind, size) {
+            (ScalarKind::Float, VectorSize::Bi) => UniformType::Vec2,
+            (ScalarKind::Float, VectorSize::Tri) => UniformType::Vec3,
+            (ScalarKind::Float, VectorSize::Quad) => UniformType::Vec4,
+            (ScalarKind::Sint, VectorSize::Bi) => UniformType::IVec2,
+            (ScalarKind::Sint, VectorSize::Tri) => UniformType::IVec3,
+            (ScalarKind::Sint, VectorSize::Quad) => UniformType::IVec4,
+            (ScalarKind::Uint, VectorSize::Bi) => UniformType::UVec2,
+            (ScalarKind::Uint, VectorSize::Tri) => UniformType::UVec3,
+            (ScalarKind::Uint, VectorSize::Quad) => UniformType::UVec4,
+            (ScalarKind::Bool, VectorSize::Bi) => UniformType::BVec2,
+            (ScalarKind::Bool, VectorSize::Tri) => UniformType::BVec3,
+            (ScalarKind::Bool, VectorSize::Quad) => UniformType::BVec4,
+            _ => UniformType::Other,
+        },
+        TypeInner::Matrix { columns, rows, .. } => match (columns, rows) {
+            (VectorSize::Bi, VectorSize::Bi) => UniformType::Mat2,
+            (VectorSize::Tri, VectorSize::Tri) => UniformType::Mat3,
+            (VectorSize::Quad, VectorSize::Quad) => UniformType::Mat4,
+            _ => UniformType::Other,
+        },
+        _ => UniformType::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reflects_the_uniform_struct() {
+        let source = "
+            struct Uniforms {
+              u_model: mat4x4<f32>,
+              u_color: vec3<f32>,
+              u_count: i32,
+              u_weights: array<vec4<f32>, 4>,
+            }
+            @group(0) @binding(0) var<uniform> uniforms: Uniforms;
+            @fragment fn fs_main() -> @location(0) vec4<f32> {
+              return uniforms.u_weights[uniforms.u_count] * uniforms.u_model[0];
+            }
+        ";
+        let block = reflect_uniforms(source).unwrap().unwrap();
+
+        let uniform = |name: &str| {
+            block
+                .uniforms
+                .iter()
+                .find(|uniform| uniform.name == name)
+                .map(|uniform| (uniform.uniform_type, uniform.array_size, block.fields[name]))
+        };
+        assert_eq!(uniform("u_model"), Some((UniformType::Mat4, 1, 0)));
+        assert_eq!(uniform("u_color"), Some((UniformType::Vec3, 1, 64)));
+        assert_eq!(uniform("u_count"), Some((UniformType::Int, 1, 76)));
+        assert_eq!(uniform("u_weights"), Some((UniformType::Vec4, 4, 80)));
+        assert_eq!(block.size, 144);
+    }
+
+    #[test]
+    fn bundled_shaders() {
+        for (name, source) in SHADERS.iter().filter(|(name, _)| name.ends_with(".wgsl")) {
+            if let Err(err) = reflect_uniforms(source) {
+                panic!("{name}: {err}");
+            }
+        }
+
+        let block = reflect_uniforms(SHADERS["phong_light_object.wgsl"])
+            .unwrap()
+            .unwrap();
+        assert!(block.uniforms.contains(&UniformInfo {
+            name: String::from("u_lod_fade"),
+            uniform_type: UniformType::Float,
+            array_size: 1,
+        }));
+    }
+}